@@ -0,0 +1,240 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::Result;
+
+use crate::{
+    config::Config,
+    context::Context,
+    ignore::{user_file, Pattern},
+};
+
+/// The state of an attribute for a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum State {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+/// The attributes assigned by a line (or a macro), where `None` makes the
+/// attribute unspecified again (`!attr`).
+type Assignments = Vec<(String, Option<State>)>;
+
+/// `[attr]binary -diff -merge -text`, which is always defined.
+const BINARY_MACRO: &str = "-diff -merge -text";
+
+/// Decides the attributes of the paths (e.g. `text`, `eol=lf` or
+/// `filter=lfs`), with the patterns of:
+///
+/// 1. the file set by `core.attributesFile` (by default
+///    `~/.config/git/attributes`),
+/// 2. the `.gitattributes` files in the working tree,
+/// 3. `.git/info/attributes`,
+///
+/// in increasing order of precedence. The patterns of a `.gitattributes` file
+/// apply to the paths in its directory, and take precedence over the ones in
+/// the parent directories. Within a file, the later lines take precedence.
+///
+/// Macros (`[attr]<name> <attributes>`) can only be defined at the top level
+/// (not in the `.gitattributes` files of subdirectories).
+pub(crate) struct Attributes {
+    repo_root: PathBuf,
+    /// Rules of the `.gitattributes` file in each directory ("" for the root),
+    /// loaded on demand.
+    rules: RefCell<HashMap<String, Rc<Vec<Rule>>>>,
+    global: Vec<Rule>,
+    info: Vec<Rule>,
+    /// The attributes set by each macro.
+    macros: HashMap<String, Assignments>,
+}
+
+/// A line of a `.gitattributes` file: the pattern, and the attributes it
+/// assigns.
+struct Rule {
+    pattern: Pattern,
+    attributes: Assignments,
+}
+
+impl Attributes {
+    pub(crate) fn new(context: &Context) -> Result<Self> {
+        let mut macros = HashMap::from([("binary".to_string(), parse_attributes(BINARY_MACRO))]);
+        let global = match user_file(&Config::load(context)?, "core.attributesFile", "attributes") {
+            Some(path) => parse_file(&path, "", Some(&mut macros)),
+            None => Vec::new(),
+        };
+        let root = parse_file(
+            &context.repo_root.join(".gitattributes"),
+            "",
+            Some(&mut macros),
+        );
+        let info = context.git_dir.join("info").join("attributes");
+        let info = parse_file(&info, "", Some(&mut macros));
+        Ok(Self {
+            repo_root: context.repo_root.clone(),
+            rules: RefCell::new(HashMap::from([(String::new(), Rc::new(root))])),
+            global,
+            info,
+            macros,
+        })
+    }
+
+    /// The specified attributes of the path (relative to the repository root).
+    pub(crate) fn get(&self, path: &str) -> Result<HashMap<String, State>> {
+        let mut sources = Vec::new();
+        let mut dir = path;
+        while !dir.is_empty() {
+            dir = dir.rsplit_once('/').map_or("", |(parent, _)| parent);
+            sources.push(self.load(dir)?);
+        }
+
+        let mut attributes = HashMap::new();
+        let rules = self
+            .global
+            .iter()
+            .chain(sources.iter().rev().flat_map(|rules| rules.iter()))
+            .chain(self.info.iter());
+        for rule in rules.filter(|rule| rule.pattern.matches(path, false)) {
+            for (name, state) in rule.attributes.iter() {
+                self.assign(&mut attributes, name, state);
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// Assigns the attribute, and the ones of the macro with this name (if the
+    /// attribute is set).
+    fn assign(&self, attributes: &mut HashMap<String, State>, name: &str, state: &Option<State>) {
+        match state {
+            Some(state) => attributes.insert(name.to_string(), state.clone()),
+            None => attributes.remove(name),
+        };
+        if let (Some(State::Set), Some(expansion)) = (state, self.macros.get(name)) {
+            for (name, state) in expansion {
+                self.assign(attributes, name, state);
+            }
+        }
+    }
+
+    /// The rules of the `.gitattributes` file in the directory.
+    fn load(&self, dir: &str) -> Result<Rc<Vec<Rule>>> {
+        if let Some(rules) = self.rules.borrow().get(dir) {
+            return Ok(rules.clone());
+        }
+        let path = self.repo_root.join(dir).join(".gitattributes");
+        let rules = Rc::new(parse_file(&path, dir, None));
+        self.rules
+            .borrow_mut()
+            .insert(dir.to_string(), rules.clone());
+        Ok(rules)
+    }
+}
+
+/// Parses the rules of the file (and the macros, where they're allowed).
+fn parse_file(
+    path: &Path,
+    base: &str,
+    mut macros: Option<&mut HashMap<String, Assignments>>,
+) -> Vec<Rule> {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let source: Rc<str> = path.to_string_lossy().into();
+    let mut rules = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim_start();
+        let (pattern, attributes) = line
+            .split_once([' ', '\t'])
+            .map_or((line, ""), |(pattern, attributes)| (pattern, attributes));
+        if let Some(name) = pattern.strip_prefix("[attr]") {
+            if let Some(macros) = macros.as_mut() {
+                macros.insert(name.to_string(), parse_attributes(attributes));
+            }
+            continue;
+        }
+        // Negative patterns are ignored, like git does.
+        match Pattern::parse(pattern, base, &source, i + 1) {
+            Some(pattern) if !pattern.negated => rules.push(Rule {
+                pattern,
+                attributes: parse_attributes(attributes),
+            }),
+            _ => {}
+        }
+    }
+    rules
+}
+
+/// Parses the attributes of a line: `attr`, `-attr`, `!attr` or `attr=value`.
+fn parse_attributes(attributes: &str) -> Assignments {
+    attributes
+        .split_whitespace()
+        .map(|attribute| {
+            if let Some(name) = attribute.strip_prefix('-') {
+                (name.to_string(), Some(State::Unset))
+            } else if let Some(name) = attribute.strip_prefix('!') {
+                (name.to_string(), None)
+            } else if let Some((name, value)) = attribute.split_once('=') {
+                (name.to_string(), Some(State::Value(value.to_string())))
+            } else {
+                (attribute.to_string(), Some(State::Set))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{Attributes, State};
+    use crate::context::tests::TestContext;
+
+    #[test]
+    fn gitattributes() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::create_dir_all(context.git_dir.join("info")).unwrap();
+        fs::write(
+            root.join(".gitattributes"),
+            "# comment\n\
+            [attr]generated -diff linguist-generated\n\
+            * text=auto\n\
+            *.png binary\n\
+            *.sh eol=lf\n\
+            /docs/*.md text eol=crlf\n\
+            !*.txt -text\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/.gitattributes"),
+            "gen/ -text\ngen/* generated !text\n",
+        )
+        .unwrap();
+        fs::write(context.git_dir.join("info/attributes"), "*.sh -eol\n").unwrap();
+
+        let attributes = Attributes::new(context).unwrap();
+        let get = |path, name| attributes.get(path).unwrap().get(name).cloned();
+        let value = |value: &str| Some(State::Value(value.to_string()));
+        assert_eq!(get("README", "text"), value("auto"));
+        assert_eq!(get("logo.png", "text"), Some(State::Unset));
+        assert_eq!(get("logo.png", "binary"), Some(State::Set));
+        assert_eq!(get("docs/a.md", "eol"), value("crlf"));
+        assert_eq!(get("src/docs/a.md", "eol"), None);
+        assert_eq!(get("run.sh", "eol"), Some(State::Unset));
+        // The negative pattern is ignored.
+        assert_eq!(get("a.txt", "text"), value("auto"));
+        assert_eq!(get("src/gen/a.rs", "text"), None);
+        assert_eq!(get("src/gen/a.rs", "diff"), Some(State::Unset));
+        assert_eq!(get("src/gen/a.rs", "linguist-generated"), Some(State::Set));
+    }
+}
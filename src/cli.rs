@@ -1,6 +1,13 @@
+use std::process;
+
 use clap::Parser;
 
-use crate::commands::{CatFileCliOptions, HashObjectOptions, InitOptions, LsTreeOptions};
+use crate::commands::{
+    AddOptions, CatFileCliOptions, CheckIgnoreOptions, CleanOptions, CommitOptions,
+    CommitTreeOptions, HashObjectOptions, InitOptions, LogOptions, LsFilesOptions, LsTreeOptions,
+    PackObjectsOptions, RevListCliOptions, RevParseOptions, RmOptions, StatusOptions, TagOptions,
+    UpdateRefOptions, WriteTreeOptions,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about)]
@@ -16,10 +23,60 @@ pub(crate) enum Cli {
 
     /// Displays contents of the tree (or a commit's tree) object
     LsTree(LsTreeOptions),
+
+    /// Creates a packed archive of objects
+    PackObjects(PackObjectsOptions),
+
+    /// Creates a tree object from the current index
+    WriteTree(WriteTreeOptions),
+
+    /// Creates a new commit object for the tree
+    CommitTree(CommitTreeOptions),
+
+    /// Updates the object name stored in a ref safely
+    UpdateRef(UpdateRefOptions),
+
+    /// Resolves revisions to object names
+    RevParse(RevParseOptions),
+
+    /// Shows the files in the index
+    LsFiles(LsFilesOptions),
+
+    /// Adds file contents to the index
+    Add(AddOptions),
+
+    /// Removes files from the working tree and the index
+    Rm(RmOptions),
+
+    /// Records the changes staged in the index as a new commit
+    Commit(CommitOptions),
+
+    /// Shows the staged, unstaged and untracked changes
+    Status(StatusOptions),
+
+    /// Shows the ignored paths, and the patterns that match them
+    CheckIgnore(CheckIgnoreOptions),
+
+    /// Removes the untracked files from the working tree
+    Clean(CleanOptions),
+
+    /// Shows the commit logs
+    Log(LogOptions),
+
+    /// Lists the commits reachable from the revisions, newest first
+    RevList(RevListCliOptions),
+
+    /// Creates, lists or deletes the tags
+    Tag(TagOptions),
 }
 
+/// Parses the arguments, or exits with the usage (with the status 129 on
+/// errors, like git).
 pub(crate) fn parse() -> Cli {
-    Cli::parse()
+    Cli::try_parse().unwrap_or_else(|err| {
+        let _ = err.print();
+        process::exit(if err.use_stderr() { 129 } else { 0 })
+    })
 }
 
 #[test]
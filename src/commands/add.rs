@@ -0,0 +1,301 @@
+use std::{collections::BTreeSet, fs, io::ErrorKind};
+
+use anyhow::{bail, Result};
+use clap::Args;
+
+use super::hash_object::hash_blob;
+use crate::{
+    context::Context,
+    ignore::Ignore,
+    index::{Index, IndexEntry, StatData},
+    objects::mode::FileMode,
+    pathspec::{self, Pathspec},
+    utils::path_from_bytes,
+    worktree::{self, file_mode, read_file},
+};
+
+/// Mode of the entries for submodules, which are directories in the working
+/// tree.
+const GITLINK_MODE: u32 = FileMode::Gitlink.bits();
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct AddOptions {
+    /// Stage all the changes (including the removed files) matching the
+    /// paths, or in the whole working tree if no path is given
+    #[arg(short = 'A', long)]
+    pub(crate) all: bool,
+
+    /// Only stage the changes to the tracked files (including the removed
+    /// files)
+    #[arg(short, long, conflicts_with = "all")]
+    pub(crate) update: bool,
+
+    /// Show what would be staged, without staging it
+    #[arg(short = 'n', long)]
+    pub(crate) dry_run: bool,
+
+    /// Show the staged files
+    #[arg(short, long)]
+    pub(crate) verbose: bool,
+
+    /// Also stage the ignored files
+    #[arg(short, long)]
+    pub(crate) force: bool,
+
+    /// Files to stage (e.g. `.`, `src/` or `'*.rs'`)
+    pub(crate) pathspecs: Vec<String>,
+}
+
+/// Stages the files matching the pathspecs: new and modified files are
+/// hashed and added to the index, and the removed ones are removed from it.
+pub(crate) fn add(context: &Context, options: AddOptions) -> Result<()> {
+    if options.pathspecs.is_empty() && !options.all && !options.update {
+        eprintln!("Nothing specified, nothing added.");
+        eprintln!("hint: Maybe you wanted to say 'git add .'?");
+        return Ok(());
+    }
+    let pathspec = Pathspec::new(context, &options.pathspecs)?;
+    let ignore = Ignore::new(context)?;
+    let mut index = Index::read(context)?;
+    let index_mtime = Index::mtime(context);
+    let files = match options.update {
+        true => Vec::new(),
+        false => worktree::walk(context, (!options.force).then_some(&ignore))?,
+    };
+
+    let mut ignored = Vec::new();
+    for pattern in pathspec.patterns() {
+        let matches = |path: &str| pathspec::matches(pattern, path);
+        if index.entries.iter().any(|entry| matches(&entry.path_str()))
+            || files.iter().any(|file| matches(&file.path))
+        {
+            continue;
+        }
+        if !options.update && !pathspec::is_glob(pattern) {
+            if let Ok(metadata) = fs::symlink_metadata(context.repo_root.join(pattern)) {
+                if ignore.is_path_ignored(pattern, metadata.is_dir())? {
+                    ignored.push(pattern.clone());
+                    continue;
+                }
+            }
+        }
+        bail!("pathspec '{pattern}' did not match any files");
+    }
+
+    let write = !options.dry_run;
+    let mut output = Vec::new();
+    let mut changed = false;
+    let tracked: BTreeSet<_> = index
+        .entries
+        .iter()
+        .filter(|entry| pathspec.matches(&entry.path_str()))
+        .map(|entry| entry.path.clone())
+        .collect();
+    for path in tracked.iter() {
+        let existing = index.entry(path).cloned();
+        let is_gitlink = existing.as_ref().is_some_and(|e| e.mode == GITLINK_MODE);
+        let display = String::from_utf8_lossy(path);
+        match fs::symlink_metadata(context.repo_root.join(path_from_bytes(path))) {
+            Ok(metadata) if metadata.is_dir() && is_gitlink => {}
+            Ok(metadata) if !metadata.is_dir() => {
+                let (entry, modified) =
+                    stage_file(context, path, &metadata, existing, index_mtime, write)?;
+                if modified {
+                    output.push(format!("add '{display}'"));
+                }
+                if let Some(entry) = entry {
+                    changed = true;
+                    if write {
+                        index.add(entry);
+                    }
+                }
+            }
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+            // Removed, or replaced by a directory (whose files are added below).
+            _ => {
+                output.push(format!("remove '{display}'"));
+                changed = true;
+                if write {
+                    index.remove(path);
+                }
+            }
+        }
+    }
+    for file in files.iter() {
+        if tracked.contains(file.path.as_bytes()) || !pathspec.matches(&file.path) {
+            continue;
+        }
+        let path = file.path.as_bytes();
+        let (entry, _) = stage_file(context, path, &file.metadata, None, index_mtime, write)?;
+        output.push(format!("add '{}'", file.path));
+        changed = true;
+        if let Some(entry) = entry.filter(|_| write) {
+            index.add(entry);
+        }
+    }
+
+    if options.dry_run || options.verbose {
+        output.iter().for_each(|line| println!("{line}"));
+    }
+    if changed && write {
+        index.write(context)?;
+    }
+    if !ignored.is_empty() {
+        bail!(
+            "The following paths are ignored by one of your .gitignore files:\n{}\n\
+            hint: Use -f if you really want to add them.",
+            ignored.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Hashes the file (unless its stat data is unchanged, and not racy), and
+/// returns the new entry if the index needs to be updated, along with whether
+/// the contents (or the mode) changed.
+fn stage_file(
+    context: &Context,
+    path: &[u8],
+    metadata: &fs::Metadata,
+    existing: Option<IndexEntry>,
+    index_mtime: Option<(u32, u32)>,
+    write: bool,
+) -> Result<(Option<IndexEntry>, bool)> {
+    if let Some(existing) = &existing {
+        if existing.is_unchanged(metadata, index_mtime) && !existing.is_intent_to_add() {
+            return Ok((None, false));
+        }
+    }
+    let stat = StatData::from(metadata);
+    let mode = file_mode(metadata);
+    let contents = read_file(&context.repo_root.join(path_from_bytes(path)), metadata)?;
+    let hash = hash_blob(context, &contents, write)?;
+    let modified = existing.is_none_or(|existing| {
+        existing.hash != hash || existing.mode != mode || existing.is_intent_to_add()
+    });
+    let entry = IndexEntry {
+        stat,
+        mode,
+        hash,
+        path: path.to_vec(),
+        ..Default::default()
+    };
+    Ok((Some(entry), modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::symlink};
+
+    use super::{add, AddOptions};
+    use crate::{
+        context::tests::TestContext,
+        index::{Index, StatData},
+    };
+
+    /// `<path> <mode> <abbreviated hash>` for each entry.
+    fn paths(index: &Index) -> Vec<String> {
+        index
+            .entries
+            .iter()
+            .map(|e| format!("{} {:o} {:.7}", e.path_str(), e.mode, e.hash))
+            .collect()
+    }
+
+    #[test]
+    fn add_files() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("a.txt"), "hello\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        fs::write(root.join("debug.log"), "").unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        symlink("a.txt", root.join("link")).unwrap();
+
+        let options = AddOptions {
+            pathspecs: vec![
+                root.join("src").to_string_lossy().into_owned(),
+                "a.txt".to_string(),
+            ],
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+        let index = Index::read(context).unwrap();
+        assert_eq!(
+            paths(&index),
+            ["a.txt 100644 ce01362", "src/lib.rs 100644 e69de29"]
+        );
+        assert!(context.object_path(&index.entries[0].hash).exists());
+
+        // Ignored files are only added with --force.
+        let options = AddOptions {
+            pathspecs: vec!["debug.log".to_string()],
+            ..Default::default()
+        };
+        assert!(add(context, options).is_err());
+        let options = AddOptions {
+            pathspecs: vec!["missing".to_string()],
+            ..Default::default()
+        };
+        assert!(add(context, options).is_err());
+
+        fs::write(root.join("a.txt"), "changed\n").unwrap();
+        fs::remove_file(root.join("src/lib.rs")).unwrap();
+        let options = AddOptions {
+            update: true,
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+        let index = Index::read(context).unwrap();
+        assert_eq!(paths(&index), ["a.txt 100644 5ea2ed4"]);
+
+        let options = AddOptions {
+            all: true,
+            dry_run: true,
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+        assert_eq!(Index::read(context).unwrap().entries.len(), 1);
+
+        let options = AddOptions {
+            all: true,
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+        let index = Index::read(context).unwrap();
+        assert_eq!(
+            paths(&index),
+            [
+                ".gitignore 100644 397b4a7",
+                "a.txt 100644 5ea2ed4",
+                "link 120000 8d14cbf",
+            ]
+        );
+    }
+
+    #[test]
+    fn racy_files() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let path = context.repo_root.join("a.txt");
+        fs::write(&path, "hello\n").unwrap();
+        let options = || AddOptions {
+            pathspecs: vec!["a.txt".to_string()],
+            ..Default::default()
+        };
+        add(context, options()).unwrap();
+
+        // Same size, and the stat data recorded in the index matches the new
+        // contents: only the index being written after the file was modified
+        // shows that the entry can't be trusted.
+        fs::write(&path, "world\n").unwrap();
+        let mut index = Index::read(context).unwrap();
+        index.entries[0].stat = StatData::from(&fs::metadata(&path).unwrap());
+        index.write(context).unwrap();
+        add(context, options()).unwrap();
+        let index = Index::read(context).unwrap();
+        assert_eq!(paths(&index), ["a.txt 100644 cc628cc"]);
+    }
+}
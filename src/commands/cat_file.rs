@@ -1,24 +1,43 @@
-use anyhow::Result;
+use std::io::{self, BufRead, BufWriter, Write};
+
+use anyhow::{bail, Result};
 use clap::Args;
 
 use crate::{
-    context::Context,
-    objects::{find_hash, object::Contents, ObjectFile},
+    context::Context, error::Error, objects::object::Contents, revision::resolve_revision,
 };
 
+const DEFAULT_BATCH_FORMAT: &str = "%(objectname) %(objecttype) %(objectsize)";
+
 #[derive(Args, Debug)]
 pub(crate) struct CatFileCliOptions {
     #[command(flatten)]
     flag: DisplayFlagGroup,
 
-    /// The object name (currently only object hash is supported)
-    object: String,
+    /// With `--batch` or `--batch-check`, show all the objects instead of
+    /// reading their names from the standard input
+    #[arg(long)]
+    batch_all_objects: bool,
+
+    /// With the batch modes, don't flush the output after each object (the
+    /// `flush` command of `--batch-command` flushes it)
+    #[arg(long)]
+    buffer: bool,
+
+    /// The object name (e.g. a hash, HEAD, main~3 or HEAD:src/lib.rs)
+    #[arg(
+        required_unless_present_any = ["batch", "batch_check", "batch_command"],
+        conflicts_with_all = ["batch", "batch_check", "batch_command"]
+    )]
+    object: Option<String>,
 }
 
 #[derive(Debug)]
 pub(crate) struct CatFileOptions {
     flag: DisplayFlag,
-    object: String,
+    object: Option<String>,
+    all_objects: bool,
+    buffer: bool,
 }
 
 #[derive(Args, Debug)]
@@ -39,6 +58,21 @@ pub(crate) struct DisplayFlagGroup {
     /// Show the object type
     #[arg(short)]
     type_: bool,
+
+    /// For each object name read from the standard input, show the object
+    /// info (in the format) and its contents
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = DEFAULT_BATCH_FORMAT)]
+    batch: Option<String>,
+
+    /// For each object name read from the standard input, show the object
+    /// info (in the format)
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = DEFAULT_BATCH_FORMAT)]
+    batch_check: Option<String>,
+
+    /// Read the commands from the standard input: `contents <object>`, `info
+    /// <object>` and `flush`
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = DEFAULT_BATCH_FORMAT)]
+    batch_command: Option<String>,
 }
 
 #[derive(Debug)]
@@ -47,6 +81,17 @@ enum DisplayFlag {
     Pretty,
     Size,
     Type,
+    Batch(BatchMode, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BatchMode {
+    /// `--batch`: the info and the contents of the objects.
+    Contents,
+    /// `--batch-check`: only the info of the objects.
+    Check,
+    /// `--batch-command`: the info or the contents, depending on the command.
+    Command,
 }
 
 impl From<DisplayFlagGroup> for DisplayFlag {
@@ -55,7 +100,15 @@ impl From<DisplayFlagGroup> for DisplayFlag {
             (true, _, _, _) => DisplayFlag::Exists,
             (_, true, _, _) => DisplayFlag::Pretty,
             (_, _, true, _) => DisplayFlag::Size,
-            (_, _, _, _) => DisplayFlag::Type,
+            (_, _, _, true) => DisplayFlag::Type,
+            _ => match (f.batch, f.batch_check, f.batch_command) {
+                (Some(format), _, _) => DisplayFlag::Batch(BatchMode::Contents, format),
+                (_, Some(format), _) => DisplayFlag::Batch(BatchMode::Check, format),
+                (_, _, format) => DisplayFlag::Batch(
+                    BatchMode::Command,
+                    format.unwrap_or(DEFAULT_BATCH_FORMAT.to_string()),
+                ),
+            },
         }
     }
 }
@@ -65,23 +118,270 @@ impl From<CatFileCliOptions> for CatFileOptions {
         Self {
             flag: opt.flag.into(),
             object: opt.object,
+            all_objects: opt.batch_all_objects,
+            buffer: opt.buffer,
         }
     }
 }
 
 pub(crate) fn cat_file(context: &Context, options: CatFileOptions) -> Result<()> {
-    let hash = find_hash(context, &options.object)?;
-    let file = ObjectFile::new(context, &hash);
-    let object = file.parse()?;
-    match options.flag {
-        DisplayFlag::Exists => {}
+    let flag = match options.flag {
+        DisplayFlag::Batch(mode, format) => {
+            let options = BatchOptions {
+                mode,
+                format: parse_format(&format)?,
+                all_objects: options.all_objects,
+                buffer: options.buffer,
+            };
+            let mut output = BufWriter::new(io::stdout().lock());
+            batch(context, &options, io::stdin().lock(), &mut output)?;
+            return Ok(output.flush()?);
+        }
+        _ if options.all_objects => bail!("'--batch-all-objects' requires a batch mode"),
+        flag => flag,
+    };
+    let object = options.object.unwrap_or_default();
+    let hash = resolve_revision(context, &object).map_err(|err| match err {
+        Error::UnknownRevision(name) => Error::NotFound(name),
+        err => err,
+    })?;
+    let object = context.database.read(&hash)?;
+    match flag {
+        DisplayFlag::Exists | DisplayFlag::Batch(..) => {}
         DisplayFlag::Pretty => match object.contents {
             Contents::Blob(blob) => print!("{}", blob.try_string()?),
             Contents::Tree(tree) => println!("{tree}"),
-            Contents::Commit(commit) => print!("{commit}"),
+            Contents::Commit(commit) => io::stdout().write_all(&commit.serialize())?,
+            Contents::Tag(tag) => io::stdout().write_all(&tag.serialize())?,
         },
         DisplayFlag::Size => println!("{}", object.size),
         DisplayFlag::Type => println!("{}", object.kind()),
     }
     Ok(())
 }
+
+struct BatchOptions {
+    mode: BatchMode,
+    format: Vec<Atom>,
+    all_objects: bool,
+    buffer: bool,
+}
+
+/// A part of the batch format.
+#[derive(Debug, PartialEq)]
+enum Atom {
+    Literal(String),
+    /// `%(objectname)`
+    Name,
+    /// `%(objecttype)`
+    Type,
+    /// `%(objectsize)`
+    Size,
+    /// `%(rest)`: the rest of the input line, after the object name.
+    Rest,
+}
+
+/// Parses the `%(...)` placeholders of the format. As in git, `%%` is a `%`,
+/// and the other `%` are kept as is.
+fn parse_format(format: &str) -> Result<Vec<Atom>> {
+    let mut atoms = Vec::new();
+    let mut literal = String::new();
+    let mut rest = format;
+    while let Some(i) = rest.find('%') {
+        literal.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('%') {
+            literal.push('%');
+            rest = after;
+            continue;
+        }
+        if !rest.starts_with('(') {
+            literal.push('%');
+            continue;
+        }
+        let Some(end) = rest.find(')') else {
+            bail!("format element '{rest}' does not end in ')'");
+        };
+        if !literal.is_empty() {
+            atoms.push(Atom::Literal(std::mem::take(&mut literal)));
+        }
+        atoms.push(match &rest[1..end] {
+            "objectname" => Atom::Name,
+            "objecttype" => Atom::Type,
+            "objectsize" => Atom::Size,
+            "rest" => Atom::Rest,
+            atom => bail!("unknown format element: {atom}"),
+        });
+        rest = &rest[end + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        atoms.push(Atom::Literal(literal));
+    }
+    Ok(atoms)
+}
+
+/// Shows the objects named in the input (or all the objects), in git's batch
+/// format: `<info>\n` (or `<name> missing\n`), followed by `<contents>\n` for
+/// `--batch` and the `contents` command.
+fn batch(
+    context: &Context,
+    options: &BatchOptions,
+    input: impl BufRead,
+    output: &mut impl Write,
+) -> Result<()> {
+    if options.all_objects {
+        let mut hashes = context.database.hashes()?;
+        hashes.sort();
+        hashes.dedup();
+        let contents = options.mode == BatchMode::Contents;
+        for hash in hashes {
+            show_object(context, options, &hash.to_string(), contents, output)?;
+        }
+        return Ok(());
+    }
+
+    for line in input.lines() {
+        let line = line?;
+        if options.mode != BatchMode::Command {
+            show_object(
+                context,
+                options,
+                &line,
+                options.mode == BatchMode::Contents,
+                output,
+            )?;
+        } else if line.is_empty() {
+            bail!("empty command in input");
+        } else if line.starts_with(char::is_whitespace) {
+            bail!("whitespace before command: '{line}'");
+        } else {
+            match line.split_once(' ').unwrap_or((&line, "")) {
+                ("contents" | "info", "") => bail!("{line} requires arguments"),
+                ("contents", object) => show_object(context, options, object, true, output)?,
+                ("info", object) => show_object(context, options, object, false, output)?,
+                ("flush", "") if options.buffer => output.flush()?,
+                ("flush", "") => bail!("flush is only for --buffer mode"),
+                ("flush", _) => bail!("flush takes no arguments"),
+                _ => bail!("unknown command: '{line}'"),
+            }
+        }
+        if !options.buffer {
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Shows the info of the object (and its contents). The name is split at the
+/// first whitespace only if the format uses `%(rest)`.
+fn show_object(
+    context: &Context,
+    options: &BatchOptions,
+    line: &str,
+    contents: bool,
+    output: &mut impl Write,
+) -> Result<()> {
+    let (name, rest) = match line.split_once([' ', '\t']) {
+        Some((name, rest)) if options.format.contains(&Atom::Rest) => (name, rest.trim_start()),
+        _ => (line, ""),
+    };
+    let object = match resolve_revision(context, name) {
+        Ok(hash) => context
+            .database
+            .read_raw(&hash)?
+            .map(|object| (hash, object)),
+        Err(Error::Ambiguous { .. }) => {
+            writeln!(output, "{name} ambiguous")?;
+            return Ok(());
+        }
+        Err(_) => None,
+    };
+    let Some((hash, (kind, body))) = object else {
+        writeln!(output, "{name} missing")?;
+        return Ok(());
+    };
+
+    for atom in options.format.iter() {
+        match atom {
+            Atom::Literal(literal) => write!(output, "{literal}")?,
+            Atom::Name => write!(output, "{hash}")?,
+            Atom::Type => write!(output, "{kind}")?,
+            Atom::Size => write!(output, "{}", body.len())?,
+            Atom::Rest => write!(output, "{rest}")?,
+        }
+    }
+    writeln!(output)?;
+    if contents {
+        output.write_all(&body)?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{batch, parse_format, Atom, BatchMode, BatchOptions};
+    use crate::{context::tests::TestContext, objects::object::Object};
+
+    #[test]
+    fn batch_modes() {
+        let context = TestContext::in_memory();
+        let context = &context.context;
+        let blob = context
+            .database
+            .write(&Object::new_blob(b"hello\n"))
+            .unwrap();
+
+        let run = |mode, format: &str, all_objects, input: &str| {
+            let options = BatchOptions {
+                mode,
+                format: parse_format(format)?,
+                all_objects,
+                buffer: true,
+            };
+            let mut output = Vec::new();
+            batch(context, &options, input.as_bytes(), &mut output)?;
+            anyhow::Ok(String::from_utf8(output).unwrap())
+        };
+        let default = "%(objectname) %(objecttype) %(objectsize)";
+        let contents = |input: &str| run(BatchMode::Contents, default, false, input).unwrap();
+        let check = |format, input: &str| run(BatchMode::Check, format, false, input).unwrap();
+        let command = |input: &str| run(BatchMode::Command, default, false, input);
+
+        assert_eq!(
+            contents(&format!("{blob}\nnope\n")),
+            format!("{blob} blob 6\nhello\n\nnope missing\n")
+        );
+        // The name is only split at the whitespace with `%(rest)`.
+        let format = "%(objecttype)|%(rest)|%%|%x";
+        assert_eq!(check(format, &format!("{blob} a  b\n")), "blob|a  b|%|%x\n");
+        assert_eq!(check(format, "nope a\n"), "nope missing\n");
+        assert_eq!(
+            check(default, &format!("{blob} a\n")),
+            format!("{blob} a missing\n")
+        );
+        assert_eq!(
+            run(BatchMode::Check, "%(objectsize)", true, "").unwrap(),
+            "6\n"
+        );
+
+        assert_eq!(
+            command(&format!("info {blob}\ncontents {blob}\nflush\n")).unwrap(),
+            format!("{blob} blob 6\n{blob} blob 6\nhello\n\n")
+        );
+        assert!(command("info\n").is_err());
+        assert!(command("bogus x\n").is_err());
+        assert!(command("\n").is_err());
+
+        // Both blobs start with 6bb2.
+        for contents in [b"195\n", b"389\n"] {
+            context.database.write(&Object::new_blob(contents)).unwrap();
+        }
+        assert_eq!(check(default, "6bb2\n"), "6bb2 ambiguous\n");
+
+        assert_eq!(parse_format("%(rest)").unwrap(), [Atom::Rest]);
+        assert!(parse_format("%(size)").is_err());
+        assert!(parse_format("%(objectname").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::{self, BufRead};
+
+use anyhow::{bail, Result};
+use clap::Args;
+
+use crate::{
+    context::Context,
+    ignore::Ignore,
+    index::Index,
+    pathspec::{current_prefix, repo_path},
+    utils::quote_path,
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct CheckIgnoreOptions {
+    /// Show the matching pattern (and where it's defined) for each path,
+    /// including the negated patterns
+    #[arg(short, long)]
+    pub(crate) verbose: bool,
+
+    /// Also show the paths that don't match any pattern (with `--verbose`)
+    #[arg(short, long, requires = "verbose")]
+    pub(crate) non_matching: bool,
+
+    /// Read the paths from the standard input (one per line)
+    #[arg(long)]
+    pub(crate) stdin: bool,
+
+    /// Separate the paths (of the input and the output) with NUL instead of
+    /// LF, and don't quote them
+    #[arg(short = 'z', requires = "stdin")]
+    pub(crate) null_terminated: bool,
+
+    /// Don't show anything, only set the exit status
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub(crate) quiet: bool,
+
+    /// Check the tracked paths as well (which are never ignored otherwise)
+    #[arg(long)]
+    pub(crate) no_index: bool,
+
+    /// Paths to check
+    pub(crate) paths: Vec<String>,
+}
+
+/// Shows the paths that are ignored (or, with `--verbose`, the pattern that
+/// matches them), and returns whether any path matched.
+pub(crate) fn check_ignore(context: &Context, options: CheckIgnoreOptions) -> Result<bool> {
+    let paths = match (options.stdin, options.paths.is_empty()) {
+        (true, true) => read_paths(options.null_terminated)?,
+        (true, false) => bail!("cannot specify pathnames with --stdin"),
+        (false, true) => bail!("no path specified"),
+        (false, false) => options.paths.clone(),
+    };
+    let ignore = Ignore::new(context)?;
+    let index = match options.no_index {
+        true => Index::default(),
+        false => Index::read(context)?,
+    };
+    let prefix = current_prefix(context);
+    let end = match options.null_terminated {
+        true => '\0',
+        false => '\n',
+    };
+
+    let mut matched = false;
+    for path in paths.iter() {
+        let repo_path = repo_path(context, &prefix, path)?;
+        let is_dir = path.ends_with('/') || context.repo_root.join(&repo_path).is_dir();
+        let pattern = match index
+            .entries
+            .iter()
+            .any(|entry| entry.path == repo_path.as_bytes())
+        {
+            true => None,
+            false => ignore.find_path_match(&repo_path, is_dir)?,
+        };
+        // Negated patterns are only shown (and count as a match) with -v.
+        let pattern = pattern.filter(|pattern| options.verbose || !pattern.negated);
+        matched |= pattern.is_some();
+        if options.quiet {
+            continue;
+        }
+        let display = match options.null_terminated {
+            true => path.into(),
+            false => quote_path(path),
+        };
+        match (pattern, options.verbose) {
+            (Some(pattern), true) if options.null_terminated => print!(
+                "{}\0{}\0{}\0{display}\0",
+                pattern.source, pattern.line_number, pattern.line
+            ),
+            (Some(pattern), true) => print!(
+                "{}:{}:{}\t{display}{end}",
+                pattern.source, pattern.line_number, pattern.line
+            ),
+            (Some(_), false) => print!("{display}{end}"),
+            (None, _) if options.non_matching && options.null_terminated => {
+                print!("\0\0\0{display}\0")
+            }
+            (None, _) if options.non_matching => print!("::\t{display}{end}"),
+            (None, _) => {}
+        }
+    }
+    Ok(matched)
+}
+
+fn read_paths(null_terminated: bool) -> Result<Vec<String>> {
+    let separator = match null_terminated {
+        true => b'\0',
+        false => b'\n',
+    };
+    let mut paths = Vec::new();
+    for path in io::stdin().lock().split(separator) {
+        let path = String::from_utf8(path?)?;
+        paths.push(path.trim_end_matches('\r').to_string());
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{check_ignore, CheckIgnoreOptions};
+    use crate::context::tests::TestContext;
+
+    #[test]
+    fn check_ignore_paths() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::write(root.join(".gitignore"), "*.log\n!keep.log\nbuild/\n").unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+
+        let check = |paths: &[&str], verbose| {
+            let options = CheckIgnoreOptions {
+                verbose,
+                quiet: !verbose,
+                paths: paths
+                    .iter()
+                    .map(|path| root.join(path).display().to_string())
+                    .collect(),
+                ..Default::default()
+            };
+            check_ignore(context, options).unwrap()
+        };
+        assert!(check(&["a.log"], false));
+        assert!(check(&["build/out/x"], false));
+        assert!(!check(&["keep.log", "src/lib.rs"], false));
+        assert!(check(&["keep.log"], true));
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs,
+};
+
+use anyhow::{bail, Result};
+use clap::Args;
+
+use crate::{
+    config::Config,
+    context::Context,
+    ignore::Ignore,
+    index::Index,
+    pathspec::{current_prefix, relative_to, Pathspec},
+    utils::quote_path,
+    worktree,
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct CleanOptions {
+    /// Show what would be removed, without removing it
+    #[arg(short = 'n', long)]
+    pub(crate) dry_run: bool,
+
+    /// Remove the files (required unless `clean.requireForce` is false)
+    #[arg(short, long)]
+    pub(crate) force: bool,
+
+    /// Also remove the untracked directories
+    #[arg(short)]
+    pub(crate) directories: bool,
+
+    /// Also remove the ignored files
+    #[arg(short = 'x', conflicts_with = "only_ignored")]
+    pub(crate) ignored: bool,
+
+    /// Only remove the ignored files
+    #[arg(short = 'X')]
+    pub(crate) only_ignored: bool,
+
+    /// Don't show the removed files
+    #[arg(short, long)]
+    pub(crate) quiet: bool,
+
+    /// Only remove the files matching these paths
+    pub(crate) pathspecs: Vec<String>,
+}
+
+/// Removes the untracked files (and directories, with `-d`) from the working
+/// tree. The ignored files are kept, unless `-x` or `-X` is given.
+pub(crate) fn clean(context: &Context, options: CleanOptions) -> Result<()> {
+    let require_force = Config::load(context)?.get("clean.requireForce") != Some("false");
+    if require_force && !options.force && !options.dry_run {
+        bail!("clean.requireForce defaults to true and neither -n nor -f given; refusing to clean");
+    }
+    let pathspec = Pathspec::new(context, &options.pathspecs)?;
+    let ignore = Ignore::new(context)?;
+    let index = Index::read(context)?;
+
+    // The ignored files are listed too, to know which untracked directories
+    // can't be removed as a whole.
+    let files = worktree::untracked(context, &index, None)?;
+    let mut selected = Vec::new();
+    let mut kept_dirs = HashSet::new();
+    for file in files.iter() {
+        let ignored = ignore.is_path_ignored(&file.path, false)?;
+        let removable = match (options.ignored, options.only_ignored) {
+            (true, _) => true,
+            (_, true) => ignored,
+            _ => !ignored,
+        };
+        if removable && pathspec.matches(&file.path) {
+            selected.push(file);
+        } else if let Some(dir) = &file.untracked_dir {
+            kept_dirs.insert(dir.as_str());
+        }
+    }
+
+    let mut paths = BTreeSet::new();
+    for file in selected {
+        // Untracked directories are only removed with -d: as a whole if all
+        // their files are removed, otherwise file by file.
+        match &file.untracked_dir {
+            Some(_) if !options.directories => false,
+            Some(dir) if !kept_dirs.contains(dir.as_str()) => paths.insert(format!("{dir}/")),
+            _ => paths.insert(file.path.clone()),
+        };
+    }
+
+    let prefix = current_prefix(context);
+    for path in paths.iter() {
+        if !options.quiet {
+            let mut display = relative_to(&prefix, path);
+            if path.ends_with('/') {
+                display.push('/');
+            }
+            match options.dry_run {
+                true => println!("Would remove {}", quote_path(&display)),
+                false => println!("Removing {}", quote_path(&display)),
+            }
+        }
+        if options.dry_run {
+            continue;
+        }
+        let full_path = context.repo_root.join(path);
+        match path.strip_suffix('/') {
+            Some(_) => fs::remove_dir_all(full_path)?,
+            None => fs::remove_file(full_path)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{clean, CleanOptions};
+    use crate::{
+        commands::add::{add, AddOptions},
+        context::tests::TestContext,
+    };
+
+    #[test]
+    fn clean_untracked() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("new")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        let options = AddOptions {
+            all: true,
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+        for path in ["a.txt", "debug.log", "src/b.rs", "new/c.txt"] {
+            fs::write(root.join(path), "").unwrap();
+        }
+
+        // -f is required.
+        assert!(clean(context, CleanOptions::default()).is_err());
+        let options = CleanOptions {
+            force: true,
+            quiet: true,
+            ..Default::default()
+        };
+        clean(context, options).unwrap();
+        assert!(!root.join("a.txt").exists());
+        assert!(!root.join("src/b.rs").exists());
+        assert!(root.join("new/c.txt").exists());
+        assert!(root.join("debug.log").exists());
+
+        let options = CleanOptions {
+            force: true,
+            quiet: true,
+            directories: true,
+            only_ignored: true,
+            ..Default::default()
+        };
+        clean(context, options).unwrap();
+        assert!(!root.join("debug.log").exists());
+        assert!(root.join("new/c.txt").exists());
+
+        let options = CleanOptions {
+            force: true,
+            quiet: true,
+            directories: true,
+            ..Default::default()
+        };
+        clean(context, options).unwrap();
+        assert!(!root.join("new").exists());
+        assert!(root.join("src/lib.rs").exists());
+    }
+
+    #[test]
+    fn clean_mixed_untracked_dir() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::create_dir_all(root.join("new")).unwrap();
+        fs::write(root.join("new/keep.txt"), "").unwrap();
+        fs::write(root.join("new/debug.log"), "").unwrap();
+
+        let options = CleanOptions {
+            force: true,
+            quiet: true,
+            directories: true,
+            only_ignored: true,
+            ..Default::default()
+        };
+        clean(context, options).unwrap();
+        assert!(!root.join("new/debug.log").exists());
+        assert!(root.join("new/keep.txt").exists());
+
+        fs::write(root.join("new/debug.log"), "").unwrap();
+        let options = CleanOptions {
+            force: true,
+            quiet: true,
+            directories: true,
+            ..Default::default()
+        };
+        clean(context, options).unwrap();
+        assert!(!root.join("new/keep.txt").exists());
+        assert!(root.join("new/debug.log").exists());
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+
+use super::{
+    commit_tree::join_messages,
+    write_tree::{write_index_tree, WriteTreeOptions},
+};
+use crate::{
+    config::Config,
+    context::Context,
+    identity::{identity, Role},
+    index::Index,
+    objects::{
+        commit::{CommitBuilder, CommitContents},
+        id::ObjectId,
+        object::{Contents, Object},
+    },
+    refs,
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct CommitOptions {
+    /// A paragraph of the commit message (can be given more than once)
+    #[arg(short, long = "message", value_name = "MESSAGE")]
+    pub(crate) messages: Vec<String>,
+
+    /// Read the commit message from the file ("-" for the standard input)
+    #[arg(short = 'F', long = "file", value_name = "FILE")]
+    pub(crate) files: Vec<String>,
+
+    /// Replace the current commit (keeping its parents, author and message,
+    /// unless a new message is given)
+    #[arg(long)]
+    pub(crate) amend: bool,
+
+    /// Allow a commit with the same tree as its parent
+    #[arg(long)]
+    pub(crate) allow_empty: bool,
+
+    /// Allow an empty commit message
+    #[arg(long)]
+    pub(crate) allow_empty_message: bool,
+
+    /// Don't show the summary of the commit
+    #[arg(short, long)]
+    pub(crate) quiet: bool,
+}
+
+/// Creates a commit from the index, with the current commit as its parent,
+/// and moves the current branch (or the detached `HEAD`) to it.
+pub(crate) fn commit(context: &Context, options: CommitOptions) -> Result<()> {
+    let (head_ref, head) = refs::resolve_ref(context, "HEAD")?;
+    let amended = match (&head, options.amend) {
+        (Some(head), true) => Some(read_commit(context, head)?),
+        (None, true) => bail!("You have nothing to amend."),
+        (_, false) => None,
+    };
+
+    let index = Index::read(context)?;
+    let tree = write_index_tree(context, &index, &WriteTreeOptions::default())?;
+    let parents = match &amended {
+        Some(amended) => amended.parents().to_vec(),
+        None => head.iter().copied().collect(),
+    };
+    if !options.amend && !options.allow_empty {
+        let unchanged = match parents.first() {
+            Some(parent) => *read_commit(context, parent)?.tree() == tree,
+            None => index.entries.is_empty(),
+        };
+        if unchanged {
+            bail!("nothing to commit (use \"git add\" to stage the changes)");
+        }
+    }
+
+    let given = !options.messages.is_empty() || !options.files.is_empty();
+    let message = match &amended {
+        _ if given => cleanup_message(&join_messages(&options.messages, &options.files)?),
+        Some(amended) => amended.message().into_owned(),
+        None => bail!("no commit message given (use -m or -F)"),
+    };
+    if message.is_empty() && !options.allow_empty_message {
+        bail!("Aborting commit due to empty commit message.");
+    }
+
+    let config = Config::load(context)?;
+    let author = match amended {
+        Some(amended) => amended.author().clone(),
+        None => identity(&config, Role::Author)?,
+    };
+    let mut builder = CommitBuilder::new(tree);
+    for parent in parents.iter() {
+        builder = builder.parent(*parent);
+    }
+    let commit = builder
+        .author(author)
+        .committer(identity(&config, Role::Committer)?)
+        .message(message.clone())
+        .build()?;
+    let hash = context.database.write(&Object::new_commit(commit))?;
+    let old = head.unwrap_or(ObjectId::null(context.hash_algorithm()));
+    refs::update_ref(context, &head_ref, &hash, Some(&old), false)?;
+
+    if !options.quiet {
+        let branch = head_ref
+            .strip_prefix("refs/heads/")
+            .unwrap_or("detached HEAD");
+        let root = match parents.is_empty() {
+            true => " (root-commit)",
+            false => "",
+        };
+        let subject = message.lines().next().unwrap_or_default();
+        let hash = context.database.abbreviate(&hash, 7)?;
+        println!("[{branch}{root} {hash}] {subject}");
+    }
+    Ok(())
+}
+
+fn read_commit(context: &Context, hash: &ObjectId) -> Result<CommitContents> {
+    match context.database.read(hash)?.contents {
+        Contents::Commit(commit) => Ok(commit),
+        _ => Err(anyhow!("{hash} is not a commit")),
+    }
+}
+
+/// Removes the trailing whitespace of the lines, and the leading, trailing and
+/// repeated blank lines. The message ends with a newline (unless it's empty).
+pub(crate) fn cleanup_message(message: &str) -> String {
+    let mut cleaned = String::new();
+    let mut blank = false;
+    for line in message.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if blank && !cleaned.is_empty() {
+            cleaned.push('\n');
+        }
+        blank = false;
+        cleaned.push_str(line);
+        cleaned.push('\n');
+    }
+    cleaned
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{cleanup_message, commit, read_commit, CommitOptions};
+    use crate::{
+        commands::add::{add, AddOptions},
+        context::tests::TestContext,
+        refs::{self, Ref},
+        revision::resolve_revision,
+    };
+
+    #[test]
+    fn cleanup() {
+        assert_eq!(
+            cleanup_message("\n\nSubject  \n\n\n\nBody\t\n\n"),
+            "Subject\n\nBody\n"
+        );
+        assert_eq!(cleanup_message(" \n\n"), "");
+    }
+
+    #[test]
+    fn commit_and_amend() {
+        let context = TestContext::init().with_identity();
+        let context = &context.context;
+        let message = |message: &str| CommitOptions {
+            messages: vec![message.to_string()],
+            quiet: true,
+            ..Default::default()
+        };
+        // Nothing is staged yet.
+        assert!(commit(context, message("Empty")).is_err());
+
+        fs::write(context.repo_root.join("a.txt"), "hello\n").unwrap();
+        let options = AddOptions {
+            all: true,
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+        commit(context, message("Initial")).unwrap();
+        let initial = resolve_revision(context, "main").unwrap();
+        let first = read_commit(context, &initial).unwrap();
+        assert!(first.parents().is_empty());
+        assert_eq!(first.message(), "Initial\n");
+
+        assert!(commit(context, message("Unchanged")).is_err());
+        let options = CommitOptions {
+            allow_empty: true,
+            ..message("Empty")
+        };
+        commit(context, options).unwrap();
+        let second = resolve_revision(context, "HEAD").unwrap();
+        assert_eq!(read_commit(context, &second).unwrap().parents(), [initial]);
+
+        let options = CommitOptions {
+            amend: true,
+            ..message("Amended")
+        };
+        commit(context, options).unwrap();
+        let amended = read_commit(context, &resolve_revision(context, "main").unwrap()).unwrap();
+        assert_eq!(amended.parents(), [initial]);
+        assert_eq!(amended.message(), "Amended\n");
+
+        // A detached HEAD is moved, without changing the branch.
+        let main = resolve_revision(context, "main").unwrap();
+        refs::update_ref(context, "HEAD", &initial, None, false).unwrap();
+        let options = CommitOptions {
+            allow_empty: true,
+            ..message("Detached")
+        };
+        commit(context, options).unwrap();
+        assert_eq!(resolve_revision(context, "main").unwrap(), main);
+        let Some(Ref::Direct(head)) = refs::read_ref(context, "HEAD").unwrap() else {
+            panic!("Expected a detached HEAD");
+        };
+        assert_eq!(read_commit(context, &head).unwrap().parents(), [initial]);
+    }
+}
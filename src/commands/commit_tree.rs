@@ -0,0 +1,168 @@
+use std::{
+    fs,
+    io::{self, Read},
+};
+
+use anyhow::{bail, Result};
+use clap::Args;
+
+use crate::{
+    config::Config,
+    context::Context,
+    identity::{identity, Role},
+    objects::{commit::CommitBuilder, id::ObjectId, kind::ObjectKind, object::Object},
+    revision::resolve_revision,
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct CommitTreeOptions {
+    /// An existing tree object
+    pub(crate) tree: String,
+
+    /// A parent commit object (can be given more than once)
+    #[arg(short = 'p', value_name = "PARENT")]
+    pub(crate) parents: Vec<String>,
+
+    /// A paragraph of the commit message (can be given more than once)
+    #[arg(short = 'm', value_name = "MESSAGE")]
+    pub(crate) messages: Vec<String>,
+
+    /// Read the commit message from the file ("-" for the standard input)
+    #[arg(short = 'F', value_name = "FILE")]
+    pub(crate) files: Vec<String>,
+}
+
+/// Creates a commit object for the tree, and returns its hash.
+pub(crate) fn commit_tree(context: &Context, options: CommitTreeOptions) -> Result<ObjectId> {
+    let tree = find_object(context, &options.tree, ObjectKind::Tree)?;
+    let mut builder = CommitBuilder::new(tree);
+    let mut parents = Vec::new();
+    for parent in options.parents.iter() {
+        let parent = find_object(context, parent, ObjectKind::Commit)?;
+        if parents.contains(&parent) {
+            eprintln!("error: duplicate parent {parent} ignored");
+            continue;
+        }
+        parents.push(parent);
+        builder = builder.parent(parent);
+    }
+
+    let config = Config::load(context)?;
+    let commit = builder
+        .author(identity(&config, Role::Author)?)
+        .committer(identity(&config, Role::Committer)?)
+        .message(message(&options)?)
+        .build()?;
+    Ok(context.database.write(&Object::new_commit(commit))?)
+}
+
+/// Finds the object, and verifies its type.
+fn find_object(context: &Context, name: &str, kind: ObjectKind) -> Result<ObjectId> {
+    let hash = resolve_revision(context, name)?;
+    if context.database.read(&hash)?.kind() != kind {
+        bail!("{hash} is not a valid '{kind}' object");
+    }
+    Ok(hash)
+}
+
+/// Reads the message from the standard input, unless paragraphs (`-m`) or
+/// files (`-F`) are given.
+fn message(options: &CommitTreeOptions) -> Result<String> {
+    if options.messages.is_empty() && options.files.is_empty() {
+        let mut message = String::new();
+        io::stdin().read_to_string(&mut message)?;
+        return Ok(message);
+    }
+    join_messages(&options.messages, &options.files)
+}
+
+/// Joins the paragraphs (`-m`) and the contents of the files (`-F`, where "-"
+/// is the standard input) with blank lines.
+pub(crate) fn join_messages(messages: &[String], files: &[String]) -> Result<String> {
+    let mut message = String::new();
+    for paragraph in messages.iter() {
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        message.push_str(paragraph);
+        if !message.ends_with('\n') {
+            message.push('\n');
+        }
+    }
+    for file in files.iter() {
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        if file == "-" {
+            io::stdin().read_to_string(&mut message)?;
+        } else {
+            message.push_str(&fs::read_to_string(file)?);
+        }
+    }
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{commit_tree, CommitTreeOptions};
+    use crate::{
+        context::tests::TestContext,
+        objects::{
+            mode::FileMode,
+            object::{Contents, Object},
+            tree::{TreeContents, TreeRowItem},
+        },
+    };
+
+    #[test]
+    fn commit_tree_with_parent() {
+        let context = TestContext::init().with_identity();
+        let context = &context.context;
+        let blob = context
+            .database
+            .write(&Object::new_blob(b"hello\n"))
+            .unwrap();
+
+        let options = CommitTreeOptions {
+            tree: "4b825dc642cb6eb9a060e54bf8d69288fbee4904".to_string(),
+            ..Default::default()
+        };
+        // The empty tree doesn't exist yet.
+        assert!(commit_tree(context, options).is_err());
+
+        let tree = Object::new_tree(TreeContents {
+            lines: vec![TreeRowItem::new(FileMode::Regular, blob, "hello")],
+        });
+        let tree = context.database.write(&tree).unwrap();
+        let options = CommitTreeOptions {
+            tree: tree.to_string(),
+            messages: vec!["Initial".to_string()],
+            ..Default::default()
+        };
+        let parent = commit_tree(context, options).unwrap();
+
+        let options = CommitTreeOptions {
+            tree: format!("{tree:.7}"),
+            parents: vec![parent.to_string(), parent.to_string()],
+            messages: vec!["Subject".to_string(), "Body\n".to_string()],
+            ..Default::default()
+        };
+        let hash = commit_tree(context, options).unwrap();
+        let Contents::Commit(commit) = context.database.read(&hash).unwrap().contents else {
+            panic!("Expected a commit");
+        };
+        assert_eq!(*commit.tree(), tree);
+        assert_eq!(commit.parents(), [parent]);
+        assert_eq!(commit.author().name, "A U Thor");
+        assert_eq!(commit.message(), "Subject\n\nBody\n");
+
+        // Blobs can't be parents.
+        let options = CommitTreeOptions {
+            tree: tree.to_string(),
+            parents: vec![blob.to_string()],
+            messages: vec!["Invalid".to_string()],
+            ..Default::default()
+        };
+        assert!(commit_tree(context, options).is_err());
+    }
+}
@@ -1,38 +1,115 @@
-use std::fs;
+use std::{
+    fs,
+    io::{self, BufRead, Read},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Args;
 
 use crate::{
     context::Context,
-    objects::{object::Object, ObjectFile},
+    filters::Filters,
+    objects::{id::ObjectId, kind::ObjectKind, object::Object},
+    pathspec::{current_prefix, repo_path},
 };
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Default)]
 pub(crate) struct HashObjectOptions {
     /// Actually write the object
     #[arg(short)]
     pub(crate) write: bool,
 
-    /// Compute Object ID (hash) of this file
-    pub(crate) path: String,
+    /// The type of the object (blob by default)
+    #[arg(short = 't', value_name = "TYPE")]
+    pub(crate) kind: Option<ObjectKind>,
+
+    /// Read the object from the standard input
+    #[arg(long)]
+    pub(crate) stdin: bool,
+
+    /// Read the paths of the files from the standard input, one per line
+    #[arg(long, conflicts_with_all = ["stdin", "paths", "path"])]
+    pub(crate) stdin_paths: bool,
+
+    /// Don't check that the object is valid for its type
+    #[arg(long)]
+    pub(crate) literally: bool,
+
+    /// Apply the filters (e.g. CRLF conversion) of this path instead of the
+    /// ones of the files
+    #[arg(long, conflicts_with = "no_filters")]
+    pub(crate) path: Option<String>,
+
+    /// Hash the contents as is, without applying any filter
+    #[arg(long)]
+    pub(crate) no_filters: bool,
+
+    /// Compute Object ID (hash) of these files
+    pub(crate) paths: Vec<String>,
 }
 
-pub(crate) fn hash_object(context: &Context, options: HashObjectOptions) -> Result<String> {
-    let contents = fs::read(options.path)?;
-    let object = Object::new_blob(&contents);
-    let hash = object.compute_hash();
-    if options.write {
-        let object_file = ObjectFile::new(context, &hash);
-        object_file.save(&object)?;
+/// Computes the hashes of the standard input (with `--stdin`), and then of the
+/// files, in order.
+pub(crate) fn hash_object(context: &Context, options: HashObjectOptions) -> Result<Vec<ObjectId>> {
+    let kind = options.kind.unwrap_or(ObjectKind::Blob);
+    let filters = match kind == ObjectKind::Blob && !options.no_filters {
+        true => Some(Filters::new(context)?),
+        false => None,
+    };
+    let prefix = current_prefix(context);
+    let hash = |contents: Vec<u8>, path: Option<&str>| {
+        let filter_path = options.path.as_deref().or(path);
+        let filter_path = filter_path.and_then(|path| repo_path(context, &prefix, path).ok());
+        let contents = match (&filters, filter_path) {
+            (Some(filters), Some(path)) => filters.clean(&path, contents)?,
+            _ => contents,
+        };
+        if !options.literally {
+            Object::from_raw(kind, &contents, context.hash_algorithm())
+                .map_err(|_| anyhow!("corrupt {kind}"))?;
+        }
+        if options.write {
+            context.database.write_raw(kind, &contents)
+        } else {
+            Ok(Object::hash_raw(context.hash_algorithm(), kind, &contents))
+        }
+    };
+
+    let mut hashes = Vec::new();
+    if options.stdin {
+        let mut contents = Vec::new();
+        io::stdin().read_to_end(&mut contents)?;
+        hashes.push(hash(contents, None)?);
+    }
+    let mut paths = options.paths.clone();
+    if options.stdin_paths {
+        for line in io::stdin().lock().lines() {
+            paths.push(line?);
+        }
+    }
+    for path in paths.iter() {
+        let contents =
+            fs::read(path).map_err(|err| anyhow!("could not open '{path}' for reading: {err}"))?;
+        hashes.push(hash(contents, Some(path))?);
+    }
+    Ok(hashes)
+}
+
+/// Computes the hash of the blob, and writes it to the database if `write` is
+/// set.
+pub(crate) fn hash_blob(context: &Context, contents: &[u8], write: bool) -> Result<ObjectId> {
+    let object = Object::new_blob(contents);
+    if write {
+        Ok(context.database.write(&object)?)
+    } else {
+        Ok(object.compute_hash(context.hash_algorithm()))
     }
-    Ok(hash)
 }
 
 #[cfg(test)]
 mod tests {
     use super::{hash_object, HashObjectOptions};
-    use crate::context::tests::TestContext;
+    use crate::{context::tests::TestContext, objects::kind::ObjectKind};
     use std::fs;
 
     #[test]
@@ -44,10 +121,11 @@ mod tests {
         fs::write(&fp, "This is a test file.\n").unwrap();
 
         let options = HashObjectOptions {
-            path: fp.to_string_lossy().to_string(),
+            paths: vec![fp.to_string_lossy().to_string()],
             write: true,
+            ..Default::default()
         };
-        let hash = hash_object(context, options).unwrap();
+        let hash = hash_object(context, options).unwrap().remove(0);
         assert_eq!(hash, "6de7b8c69d65923eb48b10a560f3d72939df256a");
         assert!(context.object_path(&hash).exists());
     }
@@ -61,11 +139,62 @@ mod tests {
         fs::write(&fp, "This is a test file.\n").unwrap();
 
         let options = HashObjectOptions {
-            path: fp.to_string_lossy().to_string(),
+            paths: vec![fp.to_string_lossy().to_string()],
             write: false,
+            ..Default::default()
         };
-        let hash = hash_object(&context, options).unwrap();
+        let hash = hash_object(context, options).unwrap().remove(0);
         assert_eq!(hash, "6de7b8c69d65923eb48b10a560f3d72939df256a");
         assert!(!context.object_path(&hash).exists());
     }
+
+    #[test]
+    fn hash_object_types_and_filters() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::write(root.join(".gitattributes"), "*.txt text\n").unwrap();
+        fs::write(root.join("crlf.txt"), "This is a test file.\r\n").unwrap();
+        fs::write(root.join("crlf.bin"), "This is a test file.\r\n").unwrap();
+        fs::write(root.join("tree"), "bogus").unwrap();
+        let path = |name: &str| root.join(name).to_string_lossy().to_string();
+
+        let hash = |options| hash_object(context, options);
+        let hashes = hash(HashObjectOptions {
+            paths: vec![path("crlf.txt"), path("crlf.bin")],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(hashes[0], "6de7b8c69d65923eb48b10a560f3d72939df256a");
+        assert_ne!(hashes[1], hashes[0]);
+
+        let unfiltered = hash(HashObjectOptions {
+            paths: vec![path("crlf.txt")],
+            no_filters: true,
+            ..Default::default()
+        });
+        assert_eq!(unfiltered.unwrap(), [hashes[1]]);
+        let filtered = hash(HashObjectOptions {
+            paths: vec![path("crlf.bin")],
+            path: Some("a.txt".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(filtered.unwrap(), [hashes[0]]);
+
+        let tree = |literally| {
+            hash(HashObjectOptions {
+                paths: vec![path("tree")],
+                kind: Some(ObjectKind::Tree),
+                literally,
+                ..Default::default()
+            })
+        };
+        assert_eq!(tree(false).unwrap_err().to_string(), "corrupt tree");
+        assert!(tree(true).is_ok());
+        assert!(hash(HashObjectOptions {
+            paths: vec![path("missing")],
+            ..Default::default()
+        })
+        .is_err());
+    }
 }
@@ -1,9 +1,13 @@
-use std::{env, fs, path::PathBuf};
+use std::{env, path::PathBuf};
 
 use anyhow::Result;
 use clap::Args;
 
-const DEFAULT_BRANCH: &str = "main";
+use crate::{
+    objects::hash::HashAlgorithm,
+    repository::{Repository, DEFAULT_BRANCH},
+    utils::is_repo_root,
+};
 
 #[derive(Args, Debug)]
 pub(crate) struct InitOptions {
@@ -12,6 +16,10 @@ pub(crate) struct InitOptions {
 
     #[arg(short = 'b', long, name = "BRANCH_NAME", default_value = DEFAULT_BRANCH)]
     pub(crate) initial_branch: String,
+
+    /// The hash function naming the objects: sha1 (the default) or sha256
+    #[arg(long, value_name = "FORMAT")]
+    pub(crate) object_format: Option<HashAlgorithm>,
 }
 
 impl Default for InitOptions {
@@ -19,6 +27,7 @@ impl Default for InitOptions {
         Self {
             directory: None,
             initial_branch: DEFAULT_BRANCH.to_string(),
+            object_format: None,
         }
     }
 }
@@ -29,21 +38,14 @@ pub(crate) fn init(options: InitOptions) -> Result<()> {
         Some(path) => PathBuf::from(path),
         None => env::current_dir()?,
     };
-    fs::create_dir_all(repo_root.clone())?;
-
-    // TODO: handle when repo is already initialized.
-    let git_root = repo_root.join(".git");
-    fs::create_dir_all(git_root.join("objects"))?;
-    fs::create_dir_all(git_root.join("refs").join("heads"))?;
-    fs::create_dir_all(git_root.join("refs").join("tags"))?;
-    fs::write(
-        git_root.join("HEAD"),
-        format!("ref: refs/heads/{}\n", options.initial_branch),
-    )?;
-    println!(
-        "Initialized empty Git repository in {}",
-        git_root.to_string_lossy()
-    );
+    let existing = is_repo_root(&repo_root);
+    let repository =
+        Repository::init_with(repo_root, &options.initial_branch, options.object_format)?;
+    let git_dir = repository.git_dir().to_string_lossy();
+    match existing {
+        true => println!("Reinitialized existing Git repository in {git_dir}"),
+        false => println!("Initialized empty Git repository in {git_dir}"),
+    }
     Ok(())
 }
 
@@ -51,7 +53,13 @@ pub(crate) fn init(options: InitOptions) -> Result<()> {
 mod tests {
     use crate::{
         commands::{self, InitOptions},
-        context::tests::TestContext,
+        context::{tests::TestContext, Context},
+        objects::{
+            hash::HashAlgorithm,
+            mode::FileMode,
+            object::{Contents, Object},
+            tree::{TreeContents, TreeRowItem},
+        },
     };
     #[test]
     fn init() {
@@ -72,4 +80,36 @@ mod tests {
         assert!(git_dir.join("refs").is_dir());
         assert!(git_dir.join("HEAD").is_file());
     }
+
+    #[test]
+    fn init_sha256() {
+        let context = TestContext::no_init();
+        let options = InitOptions {
+            directory: Some(context.context.repo_root.to_str().unwrap().to_string()),
+            object_format: Some(HashAlgorithm::Sha256),
+            ..Default::default()
+        };
+        commands::init::init(options).unwrap();
+
+        let context = Context::new(context.context.repo_root.clone()).unwrap();
+        assert_eq!(context.hash_algorithm(), HashAlgorithm::Sha256);
+        let blob = context.database.write(&Object::new_blob(b"hi\n")).unwrap();
+        assert_eq!(
+            blob,
+            "96c18f0297e38d01f4b2dacddea4259aea6b2961eb0822bd2c0c3f6029030045"
+        );
+        let tree = TreeContents {
+            lines: vec![TreeRowItem::new(FileMode::Regular, blob, "a")],
+        };
+        let tree = context.database.write(&Object::new_tree(tree)).unwrap();
+        assert_eq!(
+            tree,
+            "001cc91be706c78c4f70ff2e0d4034fb366e9742e04bcd0754666eeb9687b95a"
+        );
+        let Contents::Tree(tree) = context.database.read(&tree).unwrap().contents else {
+            panic!("not a tree");
+        };
+        assert_eq!(tree.lines[0].hash, blob);
+        assert_eq!(context.database.find_hash(&blob.prefix(10)).unwrap(), blob);
+    }
 }
@@ -0,0 +1,317 @@
+use anyhow::{bail, Result};
+use clap::Args;
+
+use super::rev_list::{walk, RevListOptions};
+use crate::{
+    context::Context,
+    date::{format_date, DateFormat},
+    objects::{
+        commit::{Author, CommitContents},
+        id::ObjectId,
+    },
+    refs,
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct LogOptions {
+    #[command(flatten)]
+    pub(crate) revisions: RevListOptions,
+
+    /// Show the commits in a format: "oneline", "short", "medium" (default),
+    /// "full", "fuller", "format:<string>" or "tformat:<string>"
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "medium"
+    )]
+    pub(crate) pretty: Option<String>,
+
+    /// Show the commits with placeholders (e.g. "%h %s"), like
+    /// `--pretty=tformat:<string>`
+    #[arg(long, value_name = "FORMAT", conflicts_with = "pretty")]
+    pub(crate) format: Option<String>,
+
+    /// Show the dates in a format: "default", "local", "iso", "iso-strict",
+    /// "rfc", "short", "raw", "unix" or "relative"
+    #[arg(long, value_name = "FORMAT", value_parser = DateFormat::NAMES)]
+    pub(crate) date: Option<String>,
+
+    /// Shorthand for `--pretty=oneline`
+    #[arg(long, conflicts_with_all = ["pretty", "format"])]
+    pub(crate) oneline: bool,
+}
+
+enum Format {
+    /// The hash (abbreviated with `--oneline`) and the subject.
+    Oneline {
+        abbreviate: bool,
+    },
+    Short,
+    Medium,
+    Full,
+    Fuller,
+    /// `format:`, where the commits are separated by newlines.
+    Separated(String),
+    /// `tformat:`, where the commits are terminated by newlines.
+    Terminated(String),
+}
+
+impl Format {
+    fn parse(format: &str) -> Result<Self> {
+        Ok(match format {
+            "oneline" => Format::Oneline { abbreviate: false },
+            "short" => Format::Short,
+            "medium" => Format::Medium,
+            "full" => Format::Full,
+            "fuller" => Format::Fuller,
+            format => match (
+                format.strip_prefix("format:"),
+                format.strip_prefix("tformat:"),
+            ) {
+                (Some(format), _) => Format::Separated(format.to_string()),
+                (_, Some(format)) => Format::Terminated(format.to_string()),
+                _ if format.contains('%') => Format::Terminated(format.to_string()),
+                _ => bail!("invalid --pretty format: {format}"),
+            },
+        })
+    }
+}
+
+/// Shows the commits reachable from the revisions (`HEAD` by default).
+pub(crate) fn log(context: &Context, mut options: LogOptions) -> Result<()> {
+    let format = match (&options.pretty, &options.format, options.oneline) {
+        (_, _, true) => Format::Oneline { abbreviate: true },
+        (Some(format), _, _) | (_, Some(format), _) => Format::parse(format)?,
+        _ => Format::Medium,
+    };
+    let date = match &options.date {
+        Some(date) => date.parse()?,
+        None => DateFormat::Default,
+    };
+    if options.revisions.revisions.is_empty() {
+        if let (head_ref, None) = refs::resolve_ref(context, "HEAD")? {
+            let branch = head_ref.strip_prefix("refs/heads/").unwrap_or(&head_ref);
+            bail!("your current branch '{branch}' does not have any commits yet");
+        }
+        options.revisions.revisions.push("HEAD".to_string());
+    }
+
+    let mut first = true;
+    walk(context, &options.revisions, |hash, commit| {
+        let output = match &format {
+            Format::Oneline { abbreviate } => {
+                let hash = match abbreviate {
+                    true => context.database.abbreviate(hash, 7)?,
+                    false => hash.to_string(),
+                };
+                format!("{hash} {}\n", subject(&commit.message()))
+            }
+            Format::Separated(format) => expand(context, format, date, hash, commit)?,
+            Format::Terminated(format) => expand(context, format, date, hash, commit)? + "\n",
+            format => header(context, format, date, hash, commit)?,
+        };
+        let separator = match &format {
+            Format::Oneline { .. } | Format::Terminated(_) => "",
+            _ if first => "",
+            _ => "\n",
+        };
+        print!("{separator}{output}");
+        first = false;
+        Ok(())
+    })
+}
+
+/// The `short`, `medium`, `full` and `fuller` formats.
+fn header(
+    context: &Context,
+    format: &Format,
+    date_format: DateFormat,
+    hash: &ObjectId,
+    commit: &CommitContents,
+) -> Result<String> {
+    let mut output = format!("commit {hash}\n");
+    if commit.parents().len() > 1 {
+        let parents = commit
+            .parents()
+            .iter()
+            .map(|parent| context.database.abbreviate(parent, 7))
+            .collect::<Result<Vec<_>, _>>()?;
+        output.push_str(&format!("Merge: {}\n", parents.join(" ")));
+    }
+    let author = commit.author();
+    let committer = commit.committer();
+    let ident = |author: &Author| format!("{} <{}>", author.name, author.email);
+    let date = |author: &Author| format_date(author.timestamp, author.offset, date_format);
+    match format {
+        Format::Short => output.push_str(&format!("Author: {}\n", ident(author))),
+        Format::Medium => output.push_str(&format!(
+            "Author: {}\nDate:   {}\n",
+            ident(author),
+            date(author)
+        )),
+        Format::Full => output.push_str(&format!(
+            "Author: {}\nCommit: {}\n",
+            ident(author),
+            ident(committer)
+        )),
+        _ => output.push_str(&format!(
+            "Author:     {}\nAuthorDate: {}\nCommit:     {}\nCommitDate: {}\n",
+            ident(author),
+            date(author),
+            ident(committer),
+            date(committer)
+        )),
+    }
+    output.push('\n');
+    let message = commit.message();
+    let message = message.trim_start_matches('\n');
+    let message = match format {
+        Format::Short => message.split("\n\n").next().unwrap_or_default(),
+        _ => message.trim_end_matches('\n'),
+    };
+    for line in message.lines() {
+        output.push_str(&format!("    {line}\n"));
+    }
+    Ok(output)
+}
+
+/// Expands the placeholders of the format:
+///
+/// - `%H`, `%h`: commit hash (abbreviated), `%T`, `%t`: tree hash, `%P`, `%p`:
+///   parent hashes
+/// - `%an`, `%ae`: author name and email, `%ad`: author date (in the `--date`
+///   format), `%ai`, `%aI`, `%aD`, `%as`, `%ar`, `%at`: author date in the
+///   iso, iso-strict, rfc, short, relative and unix formats, and `%cn`, `%ce`,
+///   `%cd`... for the committer
+/// - `%s`: subject, `%b`: body, `%B`: raw message
+/// - `%n`: newline, `%%`: `%`, `%x00`: byte in hex
+fn expand(
+    context: &Context,
+    format: &str,
+    date_format: DateFormat,
+    hash: &ObjectId,
+    commit: &CommitContents,
+) -> Result<String> {
+    let abbreviate = |hash: &ObjectId| context.database.abbreviate(hash, 7);
+    let author = commit.author();
+    let committer = commit.committer();
+    let mut output = String::new();
+    let mut rest = format;
+    while let Some(i) = rest.find('%') {
+        output.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        let (expanded, len) = match rest.as_bytes() {
+            [b'H', ..] => (hash.to_string(), 1),
+            [b'h', ..] => (abbreviate(hash)?, 1),
+            [b'T', ..] => (commit.tree().to_string(), 1),
+            [b't', ..] => (abbreviate(commit.tree())?, 1),
+            [b'P', ..] => {
+                let parents = commit.parents().iter().map(ObjectId::to_string);
+                (parents.collect::<Vec<_>>().join(" "), 1)
+            }
+            [b'p', ..] => {
+                let parents = commit.parents().iter().map(&abbreviate);
+                (parents.collect::<Result<Vec<_>, _>>()?.join(" "), 1)
+            }
+            [b'a' | b'c', field, ..] => {
+                let author = match rest.as_bytes()[0] {
+                    b'a' => author,
+                    _ => committer,
+                };
+                let date = |format| format_date(author.timestamp, author.offset, format);
+                match field {
+                    b'n' => (author.name.clone(), 2),
+                    b'e' => (author.email.clone(), 2),
+                    b'd' => (date(date_format), 2),
+                    b'i' => (date(DateFormat::Iso), 2),
+                    b'I' => (date(DateFormat::IsoStrict), 2),
+                    b'D' => (date(DateFormat::Rfc), 2),
+                    b's' => (date(DateFormat::Short), 2),
+                    b'r' => (date(DateFormat::Relative), 2),
+                    b't' => (date(DateFormat::Unix), 2),
+                    _ => (String::from("%"), 0),
+                }
+            }
+            [b's', ..] => (subject(&commit.message()), 1),
+            [b'b', ..] => (body(&commit.message()), 1),
+            [b'B', ..] => (commit.message().into_owned(), 1),
+            [b'n', ..] => ("\n".to_string(), 1),
+            [b'%', ..] => ("%".to_string(), 1),
+            [b'x', a, b, ..] if a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => {
+                let byte = u8::from_str_radix(&rest[1..3], 16)?;
+                (char::from(byte).to_string(), 3)
+            }
+            _ => (String::from("%"), 0),
+        };
+        output.push_str(&expanded);
+        rest = &rest[len..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// The first paragraph of the message, on a single line.
+fn subject(message: &str) -> String {
+    let message = message.trim_start_matches('\n');
+    let end = message.find("\n\n").unwrap_or(message.len());
+    message[..end]
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The message after the subject.
+fn body(message: &str) -> String {
+    let message = message.trim_start_matches('\n');
+    match message.find("\n\n") {
+        Some(end) => message[end..].trim_start_matches('\n').to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{body, expand, subject};
+    use crate::{
+        context::tests::TestContext,
+        date::{DateFormat, Offset},
+        objects::commit::{tests::author, Author, CommitBuilder},
+    };
+
+    #[test]
+    fn placeholders() {
+        let context = TestContext::in_memory();
+        let context = &context.context;
+        let message = "Subject\nline 2\n\nBody\n\n  indented\n";
+        assert_eq!(subject(message), "Subject line 2");
+        assert_eq!(body(message), "Body\n\n  indented\n");
+        assert_eq!(subject("Subject\n"), "Subject");
+        assert_eq!(body("Subject\n"), "");
+
+        let author = Author {
+            offset: Offset(60),
+            ..author(1700000000)
+        };
+        let tree = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+        let commit = CommitBuilder::new(tree.parse().unwrap())
+            .author(author)
+            .message(message.to_string())
+            .build()
+            .unwrap();
+        let hash = "0123456789abcdef0123456789abcdef01234567".parse().unwrap();
+        let date = DateFormat::Default;
+        let expanded = |format| expand(context, format, date, &hash, &commit).unwrap();
+        assert_eq!(expanded("%H %T"), format!("{hash} {tree}"));
+        assert_eq!(
+            expanded("%an <%ae> %at%n%ad"),
+            "A U Thor <author@example.com> 1700000000\nTue Nov 14 23:13:20 2023 +0100"
+        );
+        assert_eq!(
+            expanded("%cn|%s|%P|%%|%x41|%z"),
+            "A U Thor|Subject line 2||%|A|%z"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::{context::Context, index::Index, utils::quote_path};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct LsFilesOptions {
+    /// Show the mode, object name and stage of the entries
+    #[arg(short, long)]
+    pub(crate) stage: bool,
+
+    /// Terminate the lines with NUL, and don't quote the paths
+    #[arg(short = 'z')]
+    pub(crate) null_terminated: bool,
+}
+
+/// Lists the entries in the index.
+pub(crate) fn ls_files(context: &Context, options: LsFilesOptions) -> Result<String> {
+    let index = Index::read(context)?;
+    let mut output = String::new();
+    for entry in index.entries.iter() {
+        let path = match options.null_terminated {
+            true => entry.path_str(),
+            false => quote_path(&entry.path),
+        };
+        if options.stage {
+            output.push_str(&format!(
+                "{:06o} {} {}\t{path}",
+                entry.mode,
+                entry.hash,
+                entry.stage()
+            ));
+        } else {
+            output.push_str(&path);
+        }
+        output.push(if options.null_terminated { '\0' } else { '\n' });
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ls_files, LsFilesOptions};
+    use crate::{
+        context::tests::TestContext,
+        index::{Index, IndexEntry},
+    };
+
+    #[test]
+    fn ls_files_stage() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let hash = "ce013625030ba8dba906f756967f9e9ca394464a";
+        let entry = |mode, path: &str, flags| IndexEntry {
+            mode,
+            hash: hash.parse().unwrap(),
+            flags,
+            path: path.into(),
+            ..Default::default()
+        };
+        let index = Index {
+            entries: vec![
+                entry(0o100644, "a b.txt", 0),
+                entry(0o100755, "conflict", 0x2000),
+                entry(0o100644, "conflict", 0x3000),
+                entry(0o120000, "tab\tlink", 0),
+            ],
+            ..Default::default()
+        };
+        index.write(context).unwrap();
+
+        let options = LsFilesOptions {
+            stage: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ls_files(context, options).unwrap(),
+            format!(
+                "100644 {hash} 0\ta b.txt\n\
+                100755 {hash} 2\tconflict\n\
+                100644 {hash} 3\tconflict\n\
+                120000 {hash} 0\t\"tab\\tlink\"\n"
+            )
+        );
+
+        let options = LsFilesOptions {
+            null_terminated: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ls_files(context, options).unwrap(),
+            "a b.txt\0conflict\0conflict\0tab\tlink\0"
+        );
+    }
+}
@@ -1,44 +1,387 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use clap::Args;
 
 use crate::{
     context::Context,
-    objects::{find_hash, object::Contents, ObjectFile},
+    objects::{
+        id::ObjectId, kind::ObjectKind, mode::FileMode, object::Contents, tree::TreeRowItem,
+    },
+    pathspec::{current_prefix, relative_to, repo_path},
+    revision::{peel, resolve_revision},
+    utils::quote_path,
 };
 
-#[derive(Args, Debug)]
+const DEFAULT_FORMAT: &str = "%(objectmode) %(objecttype) %(objectname)%x09%(path)";
+const LONG_FORMAT: &str =
+    "%(objectmode) %(objecttype) %(objectname) %(objectsize:padded)%x09%(path)";
+
+#[derive(Args, Debug, Default)]
 pub(crate) struct LsTreeOptions {
-    #[arg(long, alias = "")]
-    name_only: bool,
+    /// Recurse into the subtrees
+    #[arg(short)]
+    pub(crate) recursive: bool,
+
+    /// Show the trees even when recursing into them
+    #[arg(short = 't')]
+    pub(crate) show_trees: bool,
+
+    /// Show only the trees (and the submodules)
+    #[arg(short = 'd')]
+    pub(crate) trees_only: bool,
+
+    /// Show the size of the blobs
+    #[arg(short, long, conflicts_with = "name_only")]
+    pub(crate) long: bool,
+
+    /// Show only the paths
+    #[arg(long, alias = "name-status")]
+    pub(crate) name_only: bool,
+
+    /// Terminate the lines with NUL, and don't quote the paths
+    #[arg(short = 'z')]
+    pub(crate) null_terminated: bool,
+
+    /// Abbreviate the object names to at least N hex digits (7 by default)
+    #[arg(long, value_name = "N", num_args = 0..=1, require_equals = true, default_missing_value = "7")]
+    pub(crate) abbrev: Option<usize>,
+
+    /// Show the paths relative to the repository root, instead of the current
+    /// directory
+    #[arg(long)]
+    pub(crate) full_name: bool,
+
+    /// Show the entries in the format, with the placeholders `%(objectmode)`,
+    /// `%(objecttype)`, `%(objectname)`, `%(objectsize)`,
+    /// `%(objectsize:padded)` and `%(path)`
+    #[arg(long, value_name = "FORMAT", conflicts_with_all = ["long", "name_only"])]
+    pub(crate) format: Option<String>,
+
+    /// The tree (or commit) object name (e.g. a hash, HEAD or v1.0:src)
+    pub(crate) object: String,
+
+    /// Show only the entries in these paths (`<dir>/` for the entries in the
+    /// directory, instead of the directory itself)
+    pub(crate) paths: Vec<String>,
+}
 
-    /// The object name (currently only object hash is supported)
-    object: String,
+/// The state of the listing, shared by the subtrees.
+struct Listing<'a> {
+    context: &'a Context,
+    options: &'a LsTreeOptions,
+    format: &'a str,
+    /// The current directory, relative to the repository root.
+    prefix: String,
+    /// The paths, relative to the repository root (with the trailing `/`).
+    paths: Vec<String>,
+    output: String,
 }
 
-pub(crate) fn ls_tree(context: Context, options: LsTreeOptions) -> Result<()> {
-    let hash = find_hash(&context, &options.object)?;
-    let file = ObjectFile::new(&context, &hash);
-    let object = file.parse()?;
-    match object.contents {
-        Contents::Blob(_) => eprintln!("fatal: not a tree object"),
-        Contents::Tree(tree) => {
-            if options.name_only {
-                for line in tree.lines {
-                    println!("{}", line.name);
+/// Lists the entries of the tree, like `git ls-tree`. When run in a
+/// subdirectory, only the entries in that directory are listed (with the paths
+/// relative to it).
+pub(crate) fn ls_tree(context: &Context, options: LsTreeOptions) -> Result<String> {
+    let hash = resolve_revision(context, &options.object)?;
+    let tree =
+        peel(context, &hash, "tree", &options.object).map_err(|_| anyhow!("not a tree object"))?;
+
+    let prefix = current_prefix(context);
+    let paths = match options.paths.as_slice() {
+        [] if prefix.as_os_str().is_empty() => Vec::new(),
+        [] => vec![format!("{}/", prefix.display())],
+        paths => paths
+            .iter()
+            .map(|path| {
+                let mut repo_path = repo_path(context, &prefix, path)?;
+                if (path.ends_with('/') || path == ".") && !repo_path.is_empty() {
+                    repo_path.push('/');
                 }
-            } else {
-                for line in tree.lines {
-                    println!("{line}");
+                Ok(repo_path)
+            })
+            .collect::<Result<_>>()?,
+    };
+    let format = match &options.format {
+        Some(format) => format.as_str(),
+        None if options.name_only => "%(path)",
+        None if options.long => LONG_FORMAT,
+        None => DEFAULT_FORMAT,
+    };
+    let mut listing = Listing {
+        context,
+        options: &options,
+        format,
+        prefix: prefix.to_string_lossy().into_owned(),
+        paths,
+        output: String::new(),
+    };
+    listing.list(&tree, "")?;
+    Ok(listing.output)
+}
+
+impl Listing<'_> {
+    fn list(&mut self, tree: &ObjectId, base: &str) -> Result<()> {
+        let Contents::Tree(tree) = self.context.database.read(tree)?.contents else {
+            bail!("not a tree object");
+        };
+        for entry in tree.lines {
+            let path = format!("{base}{}", entry.name);
+            let is_tree = entry.mode() == FileMode::Tree;
+            if !self.is_interesting(&path, is_tree) {
+                continue;
+            }
+            if is_tree && self.is_recursive(&path) {
+                // `-d -r` shows the trees, but `-d` alone doesn't have to.
+                let options = self.options;
+                if options.show_trees || (options.trees_only && options.recursive) {
+                    self.show(&entry, &path)?;
                 }
+                self.list(&entry.hash, &format!("{path}/"))?;
+                continue;
+            }
+            if self.options.trees_only && entry.kind() == ObjectKind::Blob {
+                continue;
             }
+            self.show(&entry, &path)?;
         }
-        Contents::Commit(commit) => {
-            let options = LsTreeOptions {
-                name_only: options.name_only,
-                object: commit.tree,
+        Ok(())
+    }
+
+    /// Whether the entry is in one of the paths, or is a directory leading to
+    /// one of them.
+    fn is_interesting(&self, path: &str, is_tree: bool) -> bool {
+        self.paths.is_empty()
+            || self.paths.iter().any(|spec| {
+                let dir = spec.trim_end_matches('/');
+                dir.is_empty()
+                    || path == dir
+                    || path
+                        .strip_prefix(dir)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                    || (is_tree
+                        && spec
+                            .strip_prefix(path)
+                            .is_some_and(|rest| rest.starts_with('/')))
+            })
+    }
+
+    /// Whether to list the entries of the subtree, instead of the subtree
+    /// itself: with `-r`, or when a path is in it.
+    fn is_recursive(&self, path: &str) -> bool {
+        self.options.recursive
+            || self.paths.iter().any(|spec| {
+                spec.strip_prefix(path)
+                    .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+
+    fn show(&mut self, entry: &TreeRowItem, path: &str) -> Result<()> {
+        let line = self.expand(entry, path)?;
+        let terminator = if self.options.null_terminated {
+            '\0'
+        } else {
+            '\n'
+        };
+        self.output.push_str(&line);
+        self.output.push(terminator);
+        Ok(())
+    }
+
+    /// Expands the placeholders of the format:
+    ///
+    /// - `%(objectmode)`, `%(objecttype)`, `%(objectname)`: mode, type and
+    ///   hash (abbreviated with `--abbrev`) of the entry
+    /// - `%(objectsize)`: size of the blob (`-` for other objects), and
+    ///   `%(objectsize:padded)` to pad it to 7 characters
+    /// - `%(path)`: path of the entry
+    /// - `%n`: newline, `%%`: `%`, `%x00`: byte in hex
+    fn expand(&self, entry: &TreeRowItem, path: &str) -> Result<String> {
+        let mut output = String::new();
+        let mut rest = self.format;
+        while let Some(i) = rest.find('%') {
+            output.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+            let (expanded, len) = match rest.as_bytes() {
+                [b'n', ..] => ("\n".to_string(), 1),
+                [b'%', ..] => ("%".to_string(), 1),
+                [b'x', a, b, ..] if a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => {
+                    let byte = u8::from_str_radix(&rest[1..3], 16)?;
+                    (char::from(byte).to_string(), 3)
+                }
+                [b'(', ..] => {
+                    let Some(end) = rest.find(')') else {
+                        bail!("bad ls-tree format: element '{rest}' does not end in ')'");
+                    };
+                    let expanded = match &rest[1..end] {
+                        "objectmode" => entry.mode().to_string(),
+                        "objecttype" => entry.kind().to_string(),
+                        "objectname" => self.object_name(&entry.hash)?,
+                        "objectsize" => self.object_size(entry)?,
+                        "objectsize:padded" => format!("{:>7}", self.object_size(entry)?),
+                        "path" => self.display_path(path),
+                        _ => bail!("bad ls-tree format: %{}", &rest[..=end]),
+                    };
+                    (expanded, end + 1)
+                }
+                _ => bail!("bad ls-tree format: element '{rest}' does not start with '('"),
             };
-            return ls_tree(context, options);
+            output.push_str(&expanded);
+            rest = &rest[len..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn object_name(&self, hash: &ObjectId) -> Result<String> {
+        match self.options.abbrev {
+            None | Some(0) => Ok(hash.to_string()),
+            Some(len) => Ok(self.context.database.abbreviate(hash, len)?),
+        }
+    }
+
+    fn object_size(&self, entry: &TreeRowItem) -> Result<String> {
+        match entry.kind() {
+            ObjectKind::Blob => Ok(self.context.database.read(&entry.hash)?.size.to_string()),
+            _ => Ok("-".to_string()),
+        }
+    }
+
+    /// The path relative to the current directory (unless `--full-name`),
+    /// quoted unless `-z`.
+    fn display_path(&self, path: &str) -> String {
+        let path = match self.options.full_name {
+            true => path.to_string(),
+            false => match relative_to(self.prefix.as_ref(), path) {
+                relative if relative.is_empty() => "./".to_string(),
+                relative => relative,
+            },
+        };
+        match self.options.null_terminated {
+            true => path,
+            false => quote_path(&path).into_owned(),
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ls_tree, LsTreeOptions};
+    use crate::{
+        context::tests::TestContext,
+        objects::{
+            mode::FileMode,
+            object::Object,
+            tree::{TreeContents, TreeRowItem},
+        },
     };
-    Ok(())
+
+    #[test]
+    fn recursive_and_paths() {
+        let context = TestContext::in_memory();
+        let context = &context.context;
+        let blob = context
+            .database
+            .write(&Object::new_blob(b"hello\n"))
+            .unwrap();
+        let submodule = "d517ef779ad42129300a58d9cbe8be913bc9718b".parse().unwrap();
+        let tree = |lines| {
+            let tree = Object::new_tree(TreeContents { lines });
+            context.database.write(&tree).unwrap()
+        };
+        let objects = tree(vec![
+            TreeRowItem::new(FileMode::Regular, blob, "kind.rs"),
+            TreeRowItem::new(FileMode::Gitlink, submodule, "sub"),
+        ]);
+        let src = tree(vec![
+            TreeRowItem::new(FileMode::Executable, blob, "main.rs"),
+            TreeRowItem::new(FileMode::Tree, objects, "objects"),
+        ]);
+        let root = tree(vec![
+            TreeRowItem::new(FileMode::Symlink, blob, "link"),
+            TreeRowItem::new(FileMode::Tree, src, "src"),
+        ]);
+
+        let list = |args: &[&str], options: LsTreeOptions| {
+            let paths = args.iter().map(|arg| arg.to_string()).collect();
+            let options = LsTreeOptions {
+                object: root.to_string(),
+                paths,
+                ..options
+            };
+            ls_tree(context, options).unwrap()
+        };
+        let names = |args: &[&str], options: LsTreeOptions| {
+            let options = LsTreeOptions {
+                format: Some("%(path)".to_string()),
+                ..options
+            };
+            list(args, options)
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let recursive = || LsTreeOptions {
+            recursive: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            list(&[], LsTreeOptions::default()),
+            format!("120000 blob {blob}\tlink\n040000 tree {src}\tsrc\n")
+        );
+        assert_eq!(
+            names(&[], recursive()),
+            [
+                "link",
+                "src/main.rs",
+                "src/objects/kind.rs",
+                "src/objects/sub"
+            ]
+        );
+        assert_eq!(names(&["src/objects"], Default::default()), ["src/objects"]);
+        assert_eq!(
+            names(&["src/objects/"], Default::default()),
+            ["src/objects/kind.rs", "src/objects/sub"]
+        );
+        let show_trees = LsTreeOptions {
+            show_trees: true,
+            ..recursive()
+        };
+        assert_eq!(
+            names(&["src/objects/kind.rs"], show_trees),
+            ["src", "src/objects", "src/objects/kind.rs"]
+        );
+        let trees_only = LsTreeOptions {
+            trees_only: true,
+            ..recursive()
+        };
+        assert_eq!(
+            names(&[], trees_only),
+            ["src", "src/objects", "src/objects/sub"]
+        );
+
+        let long = LsTreeOptions {
+            long: true,
+            abbrev: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(
+            list(&["src/"], long),
+            format!(
+                "100755 blob {blob:.7}       6\tsrc/main.rs\n040000 tree {objects:.7}       -\tsrc/objects\n"
+            )
+        );
+        let gitlink = LsTreeOptions {
+            format: Some("%(objecttype) %(objectsize)%x09%(objectname)%n%%".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            list(&["src/objects/sub"], gitlink),
+            format!("commit -\t{submodule}\n%\n")
+        );
+        let bad = LsTreeOptions {
+            object: root.to_string(),
+            format: Some("%(size)".to_string()),
+            ..Default::default()
+        };
+        assert!(ls_tree(context, bad).is_err());
+    }
 }
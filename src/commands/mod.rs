@@ -1,9 +1,39 @@
+pub(crate) mod add;
 pub(crate) mod cat_file;
+pub(crate) mod check_ignore;
+pub(crate) mod clean;
+pub(crate) mod commit;
+pub(crate) mod commit_tree;
 pub(crate) mod hash_object;
 pub(crate) mod init;
+pub(crate) mod log;
+pub(crate) mod ls_files;
 pub(crate) mod ls_tree;
+pub(crate) mod pack_objects;
+pub(crate) mod rev_list;
+pub(crate) mod rev_parse;
+pub(crate) mod rm;
+pub(crate) mod status;
+pub(crate) mod tag;
+pub(crate) mod update_ref;
+pub(crate) mod write_tree;
 
+pub(crate) use add::{add, AddOptions};
 pub(crate) use cat_file::{cat_file, CatFileCliOptions};
+pub(crate) use check_ignore::{check_ignore, CheckIgnoreOptions};
+pub(crate) use clean::{clean, CleanOptions};
+pub(crate) use commit::{commit, CommitOptions};
+pub(crate) use commit_tree::{commit_tree, CommitTreeOptions};
 pub(crate) use hash_object::{hash_object, HashObjectOptions};
 pub(crate) use init::{init, InitOptions};
+pub(crate) use log::{log, LogOptions};
+pub(crate) use ls_files::{ls_files, LsFilesOptions};
 pub(crate) use ls_tree::{ls_tree, LsTreeOptions};
+pub(crate) use pack_objects::{pack_objects, PackObjectsOptions};
+pub(crate) use rev_list::{rev_list, RevListCliOptions};
+pub(crate) use rev_parse::{rev_parse, RevParseOptions};
+pub(crate) use rm::{rm, RmOptions};
+pub(crate) use status::{status, StatusOptions};
+pub(crate) use tag::{tag, TagOptions};
+pub(crate) use update_ref::{update_ref, UpdateRefOptions};
+pub(crate) use write_tree::{write_tree, WriteTreeOptions};
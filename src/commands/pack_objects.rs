@@ -0,0 +1,138 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+};
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+
+use crate::{
+    context::Context,
+    objects::{id::ObjectId, write_pack, PackObject, PackOptions},
+};
+
+#[derive(Args, Debug)]
+pub(crate) struct PackObjectsOptions {
+    /// Write the pack to the standard output, instead of creating the files
+    #[arg(long)]
+    pub(crate) stdout: bool,
+
+    /// Number of preceding objects that are tried as the delta base
+    #[arg(long, default_value_t = 10)]
+    pub(crate) window: usize,
+
+    /// Maximum length of a delta chain
+    #[arg(long, default_value_t = 50)]
+    pub(crate) depth: usize,
+
+    /// Write <BASE_NAME>-<checksum>.pack and <BASE_NAME>-<checksum>.idx
+    #[arg(required_unless_present = "stdout")]
+    pub(crate) base_name: Option<String>,
+}
+
+/// Creates a pack with the objects listed (one per line) in the standard input.
+pub(crate) fn pack_objects(context: &Context, options: PackObjectsOptions) -> Result<()> {
+    let mut hashes = Vec::new();
+    for line in io::stdin().lock().lines() {
+        // Lines may also contain the path of the object, after the hash.
+        if let Some(hash) = line?.split_whitespace().next() {
+            hashes.push(hash.parse()?);
+        }
+    }
+    if let Some(checksum) = write_pack_files(context, &hashes, &options)? {
+        println!("{checksum}");
+    }
+    Ok(())
+}
+
+/// Returns the checksum of the pack, unless it's written to the standard
+/// output.
+pub(crate) fn write_pack_files(
+    context: &Context,
+    hashes: &[ObjectId],
+    options: &PackObjectsOptions,
+) -> Result<Option<String>> {
+    let objects = hashes
+        .iter()
+        .map(|&hash| {
+            let (kind, body) = context
+                .database
+                .read_raw(&hash)?
+                .ok_or(anyhow!("No object found for hash: {hash}"))?;
+            Ok(PackObject { hash, kind, body })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let pack_options = PackOptions {
+        window: options.window,
+        depth: options.depth,
+        algorithm: context.hash_algorithm(),
+    };
+    let files = write_pack(objects, &pack_options)?;
+
+    match &options.base_name {
+        Some(base_name) if !options.stdout => {
+            let base_name = format!("{base_name}-{}", files.checksum);
+            fs::write(format!("{base_name}.pack"), &files.pack)?;
+            fs::write(format!("{base_name}.idx"), &files.index)?;
+            Ok(Some(files.checksum))
+        }
+        _ => {
+            io::stdout().lock().write_all(&files.pack)?;
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{write_pack_files, PackObjectsOptions};
+    use crate::{
+        commands::{hash_object, HashObjectOptions},
+        context::{tests::TestContext, Context},
+    };
+
+    #[test]
+    fn pack_objects() {
+        let context = TestContext::init();
+        let context = &context.context;
+
+        let mut hashes = Vec::new();
+        for i in 1..=3 {
+            let fp = context.repo_root.join(format!("{i}.txt"));
+            fs::write(&fp, "This is a test file.\n".repeat(i * 10)).unwrap();
+            let options = HashObjectOptions {
+                paths: vec![fp.to_string_lossy().to_string()],
+                write: true,
+                ..Default::default()
+            };
+            hashes.push(hash_object(context, options).unwrap().remove(0));
+        }
+
+        let base_name = context.pack_dir().join("pack");
+        fs::create_dir_all(context.pack_dir()).unwrap();
+        let options = PackObjectsOptions {
+            stdout: false,
+            window: 10,
+            depth: 50,
+            base_name: Some(base_name.to_string_lossy().to_string()),
+        };
+        let checksum = write_pack_files(context, &hashes, &options)
+            .unwrap()
+            .unwrap();
+        let pack = context.pack_dir().join(format!("pack-{checksum}.pack"));
+        assert!(pack.is_file());
+        assert!(pack.with_extension("idx").is_file());
+
+        // Objects are still readable after the loose objects are removed.
+        for hash in hashes.iter() {
+            fs::remove_file(context.object_path(hash)).unwrap();
+        }
+        let context = Context::new(context.repo_root.clone()).unwrap();
+        for hash in hashes.iter() {
+            let object = context.database.read(hash).unwrap();
+            assert_eq!(object.size % 21, 0);
+        }
+    }
+}
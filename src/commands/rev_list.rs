@@ -0,0 +1,248 @@
+use anyhow::{bail, Result};
+use clap::Args;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    context::Context,
+    date::{now, parse_approxidate},
+    objects::{commit::CommitContents, id::ObjectId},
+    pathspec::Pathspec,
+    revision::resolve_revision,
+    revwalk::RevWalk,
+};
+
+/// Options selecting the commits, shared by `rev-list` and `log`.
+#[derive(Args, Debug, Default)]
+pub(crate) struct RevListOptions {
+    /// Show at most this many commits
+    #[arg(short = 'n', long, value_name = "NUMBER")]
+    pub(crate) max_count: Option<usize>,
+
+    /// Skip this many commits before showing them
+    #[arg(long, value_name = "NUMBER")]
+    pub(crate) skip: Option<usize>,
+
+    /// Only follow the first parent of the merge commits
+    #[arg(long)]
+    pub(crate) first_parent: bool,
+
+    /// Only show the commits whose author matches the regex (can be given more
+    /// than once)
+    #[arg(long, value_name = "PATTERN")]
+    pub(crate) author: Vec<String>,
+
+    /// Only show the commits whose message matches the regex (can be given
+    /// more than once)
+    #[arg(long, value_name = "PATTERN")]
+    pub(crate) grep: Vec<String>,
+
+    /// Match the `--author` and `--grep` patterns case-insensitively
+    #[arg(short = 'i', long)]
+    pub(crate) regexp_ignore_case: bool,
+
+    /// Only show the commits committed after the date (e.g. "2 weeks ago" or
+    /// "2024-01-31")
+    #[arg(long, alias = "after", value_name = "DATE")]
+    pub(crate) since: Option<String>,
+
+    /// Only show the commits committed before the date
+    #[arg(long, alias = "before", value_name = "DATE")]
+    pub(crate) until: Option<String>,
+
+    /// The commits to start from (`<rev>`), and to exclude along with their
+    /// ancestors (`^<rev>`, or `<from>..<to>`)
+    #[arg(value_name = "REVISION")]
+    pub(crate) revisions: Vec<String>,
+
+    /// Only show the commits changing these paths (after `--`)
+    #[arg(last = true, value_name = "PATH")]
+    pub(crate) paths: Vec<String>,
+}
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct RevListCliOptions {
+    #[command(flatten)]
+    pub(crate) revisions: RevListOptions,
+
+    /// Show the parents after each commit
+    #[arg(long)]
+    pub(crate) parents: bool,
+
+    /// Only show the number of commits
+    #[arg(long)]
+    pub(crate) count: bool,
+}
+
+/// Lists the commits reachable from the given revisions, newest first.
+pub(crate) fn rev_list(context: &Context, options: RevListCliOptions) -> Result<()> {
+    if options.revisions.revisions.is_empty() {
+        bail!("rev-list requires a revision (e.g. HEAD)");
+    }
+    let mut count = 0;
+    walk(context, &options.revisions, |hash, commit| {
+        count += 1;
+        if options.count {
+            return Ok(());
+        }
+        let mut line = hash.to_string();
+        if options.parents {
+            for parent in commit.parents().iter() {
+                line.push_str(&format!(" {parent}"));
+            }
+        }
+        println!("{line}");
+        Ok(())
+    })?;
+    if options.count {
+        println!("{count}");
+    }
+    Ok(())
+}
+
+/// Walks the commits selected by the options, newest first.
+pub(crate) fn walk(
+    context: &Context,
+    options: &RevListOptions,
+    mut f: impl FnMut(&ObjectId, &CommitContents) -> Result<()>,
+) -> Result<()> {
+    let mut walk = RevWalk::new(context);
+    walk.first_parent(options.first_parent);
+    if !options.paths.is_empty() {
+        let pathspec = Pathspec::new(context, &options.paths)?;
+        walk.paths(pathspec.patterns().to_vec());
+    }
+    for revision in options.revisions.iter() {
+        if revision.contains("...") {
+            bail!("symmetric differences ({revision}) are not supported");
+        }
+        if let Some((from, to)) = revision.split_once("..") {
+            let from = if from.is_empty() { "HEAD" } else { from };
+            let to = if to.is_empty() { "HEAD" } else { to };
+            walk.hide(&resolve_commit(context, from)?)?;
+            walk.push(&resolve_commit(context, to)?)?;
+        } else if let Some(revision) = revision.strip_prefix('^') {
+            walk.hide(&resolve_commit(context, revision)?)?;
+        } else {
+            walk.push(&resolve_commit(context, revision)?)?;
+        }
+    }
+
+    let now = now();
+    let since = match &options.since {
+        Some(date) => Some(parse_approxidate(date, now)?),
+        None => None,
+    };
+    let until = match &options.until {
+        Some(date) => Some(parse_approxidate(date, now)?),
+        None => None,
+    };
+    let authors = regexes(&options.author, options.regexp_ignore_case)?;
+    let greps = regexes(&options.grep, options.regexp_ignore_case)?;
+
+    let mut skip = options.skip.unwrap_or(0);
+    let mut remaining = options.max_count.unwrap_or(usize::MAX);
+    for commit in walk {
+        if remaining == 0 {
+            break;
+        }
+        let (hash, commit) = commit?;
+        let timestamp = commit.committer().timestamp;
+        if since.is_some_and(|since| timestamp < since)
+            || until.is_some_and(|until| timestamp > until)
+        {
+            continue;
+        }
+        let author = format!("{} <{}>", commit.author().name, commit.author().email);
+        if !authors.is_empty() && !authors.iter().any(|regex| regex.is_match(&author)) {
+            continue;
+        }
+        if !greps.is_empty() && !greps.iter().any(|regex| regex.is_match(&commit.message())) {
+            continue;
+        }
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        remaining -= 1;
+        f(&hash, &commit)?;
+    }
+    Ok(())
+}
+
+/// Resolves the revision to a commit.
+fn resolve_commit(context: &Context, revision: &str) -> Result<ObjectId> {
+    let hash = resolve_revision(context, revision)?;
+    Ok(resolve_revision(context, &format!("{hash}^{{commit}}"))?)
+}
+
+fn regexes(patterns: &[String], ignore_case: bool) -> Result<Vec<Regex>> {
+    let mut regexes = Vec::new();
+    for pattern in patterns {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .multi_line(true)
+            .build()?;
+        regexes.push(regex);
+    }
+    Ok(regexes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{walk, RevListOptions};
+    use crate::{context::tests::TestContext, revwalk::tests::commit};
+
+    #[test]
+    fn filters() {
+        let context = TestContext::in_memory();
+        let context = &context.context;
+        let a = commit(context, ("a", "1"), &[], 100);
+        let b = commit(context, ("a", "2"), &[&a], 200);
+        let c = commit(context, ("b", "1"), &[&b], 300);
+        let d = commit(context, ("a", "3"), &[&c], 400);
+
+        let hashes = |options: RevListOptions| {
+            let mut hashes = Vec::new();
+            walk(context, &options, |hash, _| {
+                hashes.push(*hash);
+                Ok(())
+            })
+            .unwrap();
+            hashes
+        };
+        let revisions = |revisions: &[&str]| RevListOptions {
+            revisions: revisions.iter().map(|r| r.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(hashes(revisions(&[&d.to_string()])), [d, c, b, a]);
+        assert_eq!(hashes(revisions(&[&format!("{a}..{c}")])), [c, b]);
+        assert_eq!(
+            hashes(revisions(&[&d.to_string(), &format!("^{b}")])),
+            [d, c]
+        );
+
+        let options = RevListOptions {
+            max_count: Some(2),
+            skip: Some(1),
+            ..revisions(&[&d.to_string()])
+        };
+        assert_eq!(hashes(options), [c, b]);
+        let options = RevListOptions {
+            since: Some("@200".to_string()),
+            until: Some("@300".to_string()),
+            ..revisions(&[&d.to_string()])
+        };
+        assert_eq!(hashes(options), [c, b]);
+        let options = RevListOptions {
+            grep: vec!["AT [34]00$".to_string()],
+            regexp_ignore_case: true,
+            ..revisions(&[&d.to_string()])
+        };
+        assert_eq!(hashes(options), [d, c]);
+        let options = RevListOptions {
+            author: vec!["nobody".to_string()],
+            ..revisions(&[&d.to_string()])
+        };
+        assert!(hashes(options).is_empty());
+    }
+}
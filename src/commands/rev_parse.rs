@@ -0,0 +1,133 @@
+use anyhow::{bail, Result};
+use clap::Args;
+
+use crate::{
+    context::Context,
+    revision::{resolve_ref_name, resolve_revision},
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct RevParseOptions {
+    /// Verify that exactly one revision is given, and that it names an
+    /// existing object
+    #[arg(long)]
+    pub(crate) verify: bool,
+
+    /// Exit with a non-zero status, without an error message, if the
+    /// revision is invalid (with --verify)
+    #[arg(short, long, requires = "verify")]
+    pub(crate) quiet: bool,
+
+    /// Show the shortest unique prefix (of at least <LENGTH> characters)
+    #[arg(
+        long,
+        value_name = "LENGTH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "7"
+    )]
+    pub(crate) short: Option<usize>,
+
+    /// Show the full name of the ref (e.g. refs/heads/main)
+    #[arg(long, conflicts_with = "abbrev_ref")]
+    pub(crate) symbolic_full_name: bool,
+
+    /// Show the short, unambiguous, name of the ref (e.g. main)
+    #[arg(long)]
+    pub(crate) abbrev_ref: bool,
+
+    /// Revisions, e.g. HEAD, main~3, v1.0^{tree} or HEAD:src/lib.rs
+    #[arg(required = true)]
+    pub(crate) revisions: Vec<String>,
+}
+
+/// Resolves the revisions, and returns a line of output for each.
+pub(crate) fn rev_parse(context: &Context, options: RevParseOptions) -> Result<Vec<String>> {
+    if options.verify && options.revisions.len() != 1 {
+        bail!("Needed a single revision");
+    }
+    let mut lines = Vec::new();
+    for rev in options.revisions.iter() {
+        if options.symbolic_full_name || options.abbrev_ref {
+            // Only refs have a symbolic name.
+            if let Some(name) = resolve_ref_name(context, rev)? {
+                lines.push(match options.abbrev_ref {
+                    true => abbreviate_ref(&name).to_string(),
+                    false => name,
+                });
+            } else {
+                resolve_revision(context, rev)?;
+            }
+            continue;
+        }
+
+        let hash = resolve_revision(context, rev)?;
+        if options.verify && !context.database.exists(&hash)? {
+            bail!("Needed a single revision");
+        }
+        lines.push(match options.short {
+            Some(len) => context.database.abbreviate(&hash, len)?,
+            None => hash.to_string(),
+        });
+    }
+    Ok(lines)
+}
+
+fn abbreviate_ref(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rev_parse, RevParseOptions};
+    use crate::{context::tests::TestContext, objects::object::Object, refs};
+
+    #[test]
+    fn rev_parse_names() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let blob = context.database.write(&Object::new_blob(b"1\n")).unwrap();
+        refs::update_ref(context, "refs/heads/main", &blob, None, true).unwrap();
+        let blob = blob.to_string();
+
+        let options = |revisions: &[&str]| RevParseOptions {
+            revisions: revisions.iter().map(|rev| rev.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            rev_parse(context, options(&["HEAD"])).unwrap(),
+            [blob.as_str()]
+        );
+
+        let short = RevParseOptions {
+            short: Some(7),
+            ..options(&["main", &blob])
+        };
+        assert_eq!(rev_parse(context, short).unwrap(), [&blob[..7]; 2]);
+
+        let names = RevParseOptions {
+            abbrev_ref: true,
+            ..options(&["HEAD", "refs/heads/main"])
+        };
+        assert_eq!(rev_parse(context, names).unwrap(), ["main", "main"]);
+        let names = RevParseOptions {
+            symbolic_full_name: true,
+            ..options(&["HEAD", &blob])
+        };
+        assert_eq!(rev_parse(context, names).unwrap(), ["refs/heads/main"]);
+
+        let verify = RevParseOptions {
+            verify: true,
+            ..options(&["HEAD", "main"])
+        };
+        assert!(rev_parse(context, verify).is_err());
+        let verify = RevParseOptions {
+            verify: true,
+            ..options(&["0000000000000000000000000000000000000001"])
+        };
+        assert!(rev_parse(context, verify).is_err());
+    }
+}
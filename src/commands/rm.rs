@@ -0,0 +1,199 @@
+use std::{collections::BTreeSet, fs, io::ErrorKind, path::Path};
+
+use anyhow::{bail, Result};
+use clap::Args;
+
+use super::hash_object::hash_blob;
+use crate::{
+    context::Context,
+    index::Index,
+    pathspec::{self, Pathspec},
+    revision::resolve_revision,
+    utils::path_from_bytes,
+    worktree::{file_mode, read_file},
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct RmOptions {
+    /// Only remove the files from the index, and keep them in the working tree
+    #[arg(long)]
+    pub(crate) cached: bool,
+
+    /// Allow removing the files in the given directories
+    #[arg(short)]
+    pub(crate) recursive: bool,
+
+    /// Remove the files even if they have changes
+    #[arg(short, long)]
+    pub(crate) force: bool,
+
+    /// Show what would be removed, without removing it
+    #[arg(short = 'n', long)]
+    pub(crate) dry_run: bool,
+
+    /// Don't show the removed files
+    #[arg(short, long)]
+    pub(crate) quiet: bool,
+
+    /// Files to remove
+    #[arg(required = true)]
+    pub(crate) pathspecs: Vec<String>,
+}
+
+/// Removes the files matching the pathspecs from the index (and the working
+/// tree, unless `--cached` is set).
+///
+/// Unless forced, files with changes that would be lost are kept: the staged
+/// contents must match `HEAD`, and the file in the working tree must match
+/// the index (with `--cached`, one of them is enough).
+pub(crate) fn rm(context: &Context, options: RmOptions) -> Result<()> {
+    let pathspec = Pathspec::new(context, &options.pathspecs)?;
+    let mut index = Index::read(context)?;
+    for pattern in pathspec.patterns() {
+        let mut matched = index
+            .entries
+            .iter()
+            .filter(|entry| pathspec::matches(pattern, &entry.path_str()));
+        let Some(entry) = matched.next() else {
+            bail!("pathspec '{pattern}' did not match any files");
+        };
+        if !options.recursive && entry.path != pattern.as_bytes() && !pathspec::is_glob(pattern) {
+            bail!("not removing '{pattern}' recursively without -r");
+        }
+    }
+    let paths: BTreeSet<_> = index
+        .entries
+        .iter()
+        .filter(|entry| pathspec.matches(&entry.path_str()))
+        .map(|entry| entry.path.clone())
+        .collect();
+
+    if !options.force {
+        for path in paths.iter() {
+            check_changes(context, &index, path, options.cached)?;
+        }
+    }
+
+    for path in paths.iter() {
+        if !options.quiet {
+            println!("rm '{}'", String::from_utf8_lossy(path));
+        }
+        if options.dry_run {
+            continue;
+        }
+        index.remove(path);
+        if !options.cached {
+            remove_file(&context.repo_root, path_from_bytes(path))?;
+        }
+    }
+    if !options.dry_run {
+        index.write(context)?;
+    }
+    Ok(())
+}
+
+/// Fails if removing the path would lose changes.
+fn check_changes(context: &Context, index: &Index, path: &[u8], cached: bool) -> Result<()> {
+    let Some(entry) = index.entry(path) else {
+        // Unmerged paths can always be removed.
+        return Ok(());
+    };
+    let full_path = context.repo_root.join(path_from_bytes(path));
+    let path = entry.path_str();
+    let head = resolve_revision(context, &format!("HEAD:{path}")).ok();
+    let staged = head.as_ref() != Some(&entry.hash);
+    let modified = match fs::symlink_metadata(&full_path) {
+        Ok(metadata) if !metadata.is_dir() => {
+            let hash = hash_blob(context, &read_file(&full_path, &metadata)?, false)?;
+            hash != entry.hash || file_mode(&metadata) != entry.mode
+        }
+        _ => false,
+    };
+    if staged && modified {
+        bail!(
+            "the following file has staged content different from both the file and the HEAD:\n    {path}\n\
+            (use -f to force removal)"
+        );
+    }
+    if !cached && staged {
+        bail!(
+            "the following file has changes staged in the index:\n    {path}\n\
+            (use --cached to keep the file, or -f to force removal)"
+        );
+    }
+    if !cached && modified {
+        bail!(
+            "the following file has local modifications:\n    {path}\n\
+            (use --cached to keep the file, or -f to force removal)"
+        );
+    }
+    Ok(())
+}
+
+/// Removes the file, and the directories left empty.
+fn remove_file(repo_root: &Path, path: &Path) -> Result<()> {
+    match fs::remove_file(repo_root.join(path)) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    }
+    let mut dir = path.parent();
+    while let Some(parent) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        if fs::remove_dir(repo_root.join(parent)).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{rm, RmOptions};
+    use crate::{
+        commands::add::{add, AddOptions},
+        context::tests::TestContext,
+        index::Index,
+    };
+
+    #[test]
+    fn rm_files() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::write(root.join("a.txt"), "hello\n").unwrap();
+        fs::write(root.join("src/nested/lib.rs"), "").unwrap();
+        let options = AddOptions {
+            all: true,
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+
+        let options = |pathspecs: &[&str]| RmOptions {
+            pathspecs: pathspecs.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        };
+        // There is no HEAD, so all the changes are staged.
+        assert!(rm(context, options(&["a.txt"])).is_err());
+        let cached = RmOptions {
+            cached: true,
+            ..options(&["a.txt"])
+        };
+        rm(context, cached).unwrap();
+        assert!(root.join("a.txt").exists());
+
+        assert!(rm(context, options(&["src"])).is_err());
+        assert!(rm(context, options(&["missing"])).is_err());
+        let forced = RmOptions {
+            recursive: true,
+            force: true,
+            ..options(&["src"])
+        };
+        rm(context, forced).unwrap();
+        assert!(!root.join("src").exists());
+        assert!(Index::read(context).unwrap().entries.is_empty());
+    }
+}
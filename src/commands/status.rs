@@ -0,0 +1,648 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::ErrorKind,
+};
+
+use anyhow::{bail, Result};
+use clap::Args;
+
+use super::hash_object::hash_blob;
+use crate::{
+    context::Context,
+    ignore::Ignore,
+    index::{Index, IndexEntry},
+    objects::{id::ObjectId, mode::FileMode, object::Contents},
+    pathspec::{current_prefix, relative_to, Pathspec},
+    refs,
+    revision::resolve_revision,
+    utils::quote_path,
+    worktree::{self, file_mode, read_file},
+};
+
+const GITLINK_MODE: u32 = FileMode::Gitlink.bits();
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct StatusOptions {
+    /// Show the status in the short format
+    #[arg(short, long)]
+    pub(crate) short: bool,
+
+    /// Show the branch (in the short and porcelain formats)
+    #[arg(short, long)]
+    pub(crate) branch: bool,
+
+    /// Show the status in a stable format for scripts ("v1" or "v2")
+    #[arg(
+        long,
+        value_name = "VERSION",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "v1",
+        value_parser = ["v1", "v2"]
+    )]
+    pub(crate) porcelain: Option<String>,
+
+    /// Terminate the entries with NUL instead of LF, and don't quote the paths
+    /// (implies `--porcelain=v1` unless another format is given)
+    #[arg(short = 'z')]
+    pub(crate) null_terminated: bool,
+
+    /// Show the untracked files: "no", "normal" (only the directories, for
+    /// untracked directories) or "all"
+    #[arg(
+        short = 'u',
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "all",
+        value_parser = ["no", "normal", "all"]
+    )]
+    pub(crate) untracked_files: Option<String>,
+
+    /// Only show the status of these paths
+    pub(crate) pathspecs: Vec<String>,
+}
+
+/// Mode and object name of a file in a tree, the index or the working tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Version {
+    mode: u32,
+    hash: ObjectId,
+}
+
+/// The status of a tracked path: `staged` compares `HEAD` with the index, and
+/// `unstaged` compares the index with the working tree (`' '` if unchanged).
+/// For unmerged paths, both together give the kind of conflict (e.g. "UU").
+#[derive(Debug)]
+struct Change {
+    path: String,
+    staged: char,
+    unstaged: char,
+    head: Option<Version>,
+    index: Option<Version>,
+    /// Mode of the file in the working tree (if it exists).
+    worktree: Option<u32>,
+    /// Stages 1 (base), 2 (ours) and 3 (theirs) of unmerged paths.
+    stages: Option<[Option<Version>; 3]>,
+}
+
+impl Change {
+    fn is_unmerged(&self) -> bool {
+        self.stages.is_some()
+    }
+}
+
+struct Status {
+    /// The checked out branch (e.g. `refs/heads/main`), or `HEAD` if detached.
+    head_ref: String,
+    head: Option<ObjectId>,
+    changes: Vec<Change>,
+    /// Untracked files (and directories, ending with `/`).
+    untracked: Vec<String>,
+    /// Whether the untracked files were listed (not `-uno`).
+    show_untracked: bool,
+    /// Whether a merge is in progress (`MERGE_HEAD` exists).
+    merging: bool,
+}
+
+enum Format {
+    Long,
+    Short,
+    PorcelainV1,
+    PorcelainV2,
+}
+
+/// Shows the differences between `HEAD` and the index (the staged changes),
+/// between the index and the working tree (the unstaged changes), and the
+/// untracked files.
+pub(crate) fn status(context: &Context, options: StatusOptions) -> Result<String> {
+    let format = match (options.porcelain.as_deref(), options.short) {
+        (Some("v2"), _) => Format::PorcelainV2,
+        (Some(_), _) => Format::PorcelainV1,
+        (None, true) => Format::Short,
+        (None, false) if options.null_terminated => Format::PorcelainV1,
+        (None, false) => Format::Long,
+    };
+    let status = collect(context, &options)?;
+    let prefix = current_prefix(context);
+    let display = |path: &str| match format {
+        Format::Long | Format::Short => {
+            let relative = relative_to(&prefix, path);
+            match path.ends_with('/') {
+                true => format!("{relative}/"),
+                false => relative,
+            }
+        }
+        Format::PorcelainV1 | Format::PorcelainV2 => path.to_string(),
+    };
+    let display = |path: &str| match options.null_terminated {
+        true => display(path),
+        false => quote_path(&display(path)).into_owned(),
+    };
+    let output = match format {
+        Format::Long => long_format(&status, &display),
+        Format::Short | Format::PorcelainV1 => short_format(&status, &options, &display),
+        Format::PorcelainV2 => {
+            let null = ObjectId::null(context.hash_algorithm());
+            porcelain_v2(&status, &options, &display, &null)
+        }
+    };
+    Ok(output)
+}
+
+fn collect(context: &Context, options: &StatusOptions) -> Result<Status> {
+    let pathspec = Pathspec::new(context, &options.pathspecs)?;
+    let (head_ref, head) = refs::resolve_ref(context, "HEAD")?;
+    let head_tree = match &head {
+        Some(head) => read_tree(
+            context,
+            &resolve_revision(context, &format!("{head}^{{tree}}"))?,
+        )?,
+        None => BTreeMap::new(),
+    };
+    let index = Index::read(context)?;
+    let index_mtime = Index::mtime(context);
+
+    // Paths are compared with the trees (and the working tree) as strings.
+    let mut unmerged: BTreeMap<Cow<str>, [Option<Version>; 3]> = BTreeMap::new();
+    let mut staged: BTreeMap<Cow<str>, &IndexEntry> = BTreeMap::new();
+    for entry in index.entries.iter() {
+        match entry.stage() {
+            0 => _ = staged.insert(entry.path_str(), entry),
+            stage => {
+                let stages = unmerged.entry(entry.path_str()).or_default();
+                stages[stage as usize - 1] = Some(Version {
+                    mode: entry.mode,
+                    hash: entry.hash,
+                });
+            }
+        }
+    }
+
+    let paths: BTreeSet<&str> = head_tree
+        .keys()
+        .map(String::as_str)
+        .chain(staged.keys().map(|path| path.as_ref()))
+        .chain(unmerged.keys().map(|path| path.as_ref()))
+        .filter(|path| pathspec.matches(path))
+        .collect();
+    let mut changes = Vec::new();
+    for path in paths {
+        let head = head_tree.get(path).cloned();
+        let worktree = fs::symlink_metadata(context.repo_root.join(path));
+        let worktree = match worktree {
+            Ok(metadata) => Some(metadata),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        if let Some(stages) = unmerged.get(path).cloned() {
+            let code = match stages.each_ref().map(Option::is_some) {
+                [true, false, false] => "DD",
+                [false, true, false] => "AU",
+                [true, false, true] => "DU",
+                [false, false, true] => "UA",
+                [true, true, false] => "UD",
+                [false, true, true] => "AA",
+                _ => "UU",
+            };
+            let mut code = code.chars();
+            changes.push(Change {
+                path: path.to_string(),
+                staged: code.next().unwrap_or(' '),
+                unstaged: code.next().unwrap_or(' '),
+                head,
+                index: None,
+                worktree: worktree.as_ref().map(file_mode),
+                stages: Some(stages),
+            });
+            continue;
+        }
+
+        let entry = staged.get(path).copied();
+        let index = entry
+            .filter(|entry| !entry.is_intent_to_add())
+            .map(|entry| Version {
+                mode: entry.mode,
+                hash: entry.hash,
+            });
+        let staged = compare(head.as_ref(), index.as_ref());
+        let unstaged = match (entry, &worktree) {
+            (None, _) => ' ',
+            (Some(entry), _) if entry.is_intent_to_add() => 'A',
+            (Some(entry), Some(metadata)) if metadata.is_dir() => match entry.mode {
+                GITLINK_MODE => ' ',
+                _ => 'D',
+            },
+            (Some(_), None) => 'D',
+            (Some(entry), Some(metadata)) => {
+                if entry.is_unchanged(metadata, index_mtime) {
+                    ' '
+                } else {
+                    let full_path = context.repo_root.join(path);
+                    let contents = read_file(&full_path, metadata)?;
+                    let current = Version {
+                        mode: file_mode(metadata),
+                        hash: hash_blob(context, &contents, false)?,
+                    };
+                    compare(index.as_ref(), Some(&current))
+                }
+            }
+        };
+        if staged == ' ' && unstaged == ' ' {
+            continue;
+        }
+        changes.push(Change {
+            path: path.to_string(),
+            staged,
+            unstaged,
+            head,
+            index,
+            worktree: worktree.as_ref().map(file_mode),
+            stages: None,
+        });
+    }
+
+    let show_untracked = options.untracked_files.as_deref() != Some("no");
+    let untracked = match options.untracked_files.as_deref() {
+        Some("no") => Vec::new(),
+        mode => {
+            let all = mode == Some("all");
+            untracked_files(context, &index, &pathspec, all)?
+        }
+    };
+    Ok(Status {
+        head_ref,
+        head,
+        changes,
+        untracked,
+        show_untracked,
+        merging: context.git_dir.join("MERGE_HEAD").exists(),
+    })
+}
+
+/// The status letter for the change from `old` to `new`.
+fn compare(old: Option<&Version>, new: Option<&Version>) -> char {
+    match (old, new) {
+        (None, None) => ' ',
+        (None, Some(_)) => 'A',
+        (Some(_), None) => 'D',
+        (Some(old), Some(new)) if old.mode >> 12 != new.mode >> 12 => 'T',
+        (Some(old), Some(new)) if old != new => 'M',
+        _ => ' ',
+    }
+}
+
+/// The untracked files, sorted. Unless `all` is set, untracked directories are
+/// shown instead of their files.
+fn untracked_files(
+    context: &Context,
+    index: &Index,
+    pathspec: &Pathspec,
+    all: bool,
+) -> Result<Vec<String>> {
+    let ignore = Ignore::new(context)?;
+    let mut untracked = BTreeSet::new();
+    for file in worktree::untracked(context, index, Some(&ignore))? {
+        if !pathspec.matches(&file.path) {
+            continue;
+        }
+        match file.untracked_dir {
+            Some(dir) if !all => untracked.insert(format!("{dir}/")),
+            _ => untracked.insert(file.path),
+        };
+    }
+    Ok(untracked.into_iter().collect())
+}
+
+/// The files in the tree (and its subtrees), by path.
+fn read_tree(context: &Context, hash: &ObjectId) -> Result<BTreeMap<String, Version>> {
+    let mut files = BTreeMap::new();
+    read_subtree(context, hash, "", &mut files)?;
+    Ok(files)
+}
+
+fn read_subtree(
+    context: &Context,
+    hash: &ObjectId,
+    prefix: &str,
+    files: &mut BTreeMap<String, Version>,
+) -> Result<()> {
+    let Contents::Tree(tree) = context.database.read(hash)?.contents else {
+        bail!("{hash} is not a tree");
+    };
+    for line in tree.lines {
+        let path = format!("{prefix}{}", line.name);
+        if line.mode() == FileMode::Tree {
+            read_subtree(context, &line.hash, &format!("{path}/"), files)?;
+        } else {
+            let (mode, hash) = (line.mode().bits(), line.hash);
+            files.insert(path, Version { mode, hash });
+        }
+    }
+    Ok(())
+}
+
+fn long_format(status: &Status, display: &dyn Fn(&str) -> String) -> String {
+    let mut output = String::new();
+    match status.head_ref.strip_prefix("refs/heads/") {
+        Some(branch) => output.push_str(&format!("On branch {branch}\n")),
+        None => output.push_str("Not currently on any branch.\n"),
+    }
+    if status.head.is_none() {
+        output.push_str("\nNo commits yet\n\n");
+    }
+    if status.merging && status.changes.iter().any(Change::is_unmerged) {
+        output.push_str("You have unmerged paths.\n");
+        output.push_str("  (fix conflicts and run \"git commit\")\n");
+        output.push_str("  (use \"git merge --abort\" to abort the merge)\n\n");
+    }
+
+    let staged: Vec<_> = status
+        .changes
+        .iter()
+        .filter(|change| !change.is_unmerged() && change.staged != ' ')
+        .collect();
+    let unmerged: Vec<_> = status.changes.iter().filter(|c| c.is_unmerged()).collect();
+    let unstaged: Vec<_> = status
+        .changes
+        .iter()
+        .filter(|change| !change.is_unmerged() && change.unstaged != ' ')
+        .collect();
+
+    if !staged.is_empty() {
+        output.push_str("Changes to be committed:\n");
+        match status.head {
+            Some(_) => output.push_str("  (use \"git restore --staged <file>...\" to unstage)\n"),
+            None => output.push_str("  (use \"git rm --cached <file>...\" to unstage)\n"),
+        }
+        for change in staged.iter() {
+            let label = label(change.staged);
+            output.push_str(&format!("\t{label:<12}{}\n", display(&change.path)));
+        }
+        output.push('\n');
+    }
+    if !unmerged.is_empty() {
+        output.push_str("Unmerged paths:\n");
+        if !status.merging {
+            output.push_str("  (use \"git restore --staged <file>...\" to unstage)\n");
+        }
+        match unmerged
+            .iter()
+            .any(|c| c.staged == 'D' || c.unstaged == 'D')
+        {
+            true => output
+                .push_str("  (use \"git add/rm <file>...\" as appropriate to mark resolution)\n"),
+            false => output.push_str("  (use \"git add <file>...\" to mark resolution)\n"),
+        }
+        for change in unmerged.iter() {
+            let label = match (change.staged, change.unstaged) {
+                ('D', 'D') => "both deleted:",
+                ('A', 'U') => "added by us:",
+                ('U', 'D') => "deleted by them:",
+                ('U', 'A') => "added by them:",
+                ('D', 'U') => "deleted by us:",
+                ('A', 'A') => "both added:",
+                _ => "both modified:",
+            };
+            output.push_str(&format!("\t{label:<17}{}\n", display(&change.path)));
+        }
+        output.push('\n');
+    }
+    if !unstaged.is_empty() {
+        output.push_str("Changes not staged for commit:\n");
+        match unstaged.iter().any(|change| change.unstaged == 'D') {
+            true => output
+                .push_str("  (use \"git add/rm <file>...\" to update what will be committed)\n"),
+            false => {
+                output.push_str("  (use \"git add <file>...\" to update what will be committed)\n")
+            }
+        }
+        output.push_str(
+            "  (use \"git restore <file>...\" to discard changes in working directory)\n",
+        );
+        for change in unstaged.iter() {
+            let label = label(change.unstaged);
+            output.push_str(&format!("\t{label:<12}{}\n", display(&change.path)));
+        }
+        output.push('\n');
+    }
+    if !status.untracked.is_empty() {
+        output.push_str("Untracked files:\n");
+        output.push_str("  (use \"git add <file>...\" to include in what will be committed)\n");
+        for path in status.untracked.iter() {
+            output.push_str(&format!("\t{}\n", display(path)));
+        }
+        output.push('\n');
+    }
+
+    let summary = if !staged.is_empty() {
+        match status.show_untracked {
+            true => return output,
+            false => "Untracked files not listed (use -u option to show untracked files)",
+        }
+    } else if !unstaged.is_empty() || !unmerged.is_empty() {
+        "no changes added to commit (use \"git add\" and/or \"git commit -a\")"
+    } else if !status.show_untracked {
+        "nothing to commit (use -u to show untracked files)"
+    } else if !status.untracked.is_empty() {
+        "nothing added to commit but untracked files present (use \"git add\" to track)"
+    } else if status.head.is_none() {
+        "nothing to commit (create/copy files and use \"git add\" to track)"
+    } else {
+        "nothing to commit, working tree clean"
+    };
+    output.push_str(summary);
+    output.push('\n');
+    output
+}
+
+fn label(status: char) -> &'static str {
+    match status {
+        'A' => "new file:",
+        'D' => "deleted:",
+        'T' => "typechange:",
+        _ => "modified:",
+    }
+}
+
+/// `XY <path>` for the changed paths, and `?? <path>` for the untracked ones.
+fn short_format(
+    status: &Status,
+    options: &StatusOptions,
+    display: &dyn Fn(&str) -> String,
+) -> String {
+    let end = match options.null_terminated {
+        true => '\0',
+        false => '\n',
+    };
+    let mut output = String::new();
+    if options.branch {
+        let header = match (status.head_ref.strip_prefix("refs/heads/"), &status.head) {
+            (Some(branch), Some(_)) => branch.to_string(),
+            (Some(branch), None) => format!("No commits yet on {branch}"),
+            (None, _) => "HEAD (no branch)".to_string(),
+        };
+        output.push_str(&format!("## {header}{end}"));
+    }
+    for change in status.changes.iter() {
+        let (x, y) = (change.staged, change.unstaged);
+        output.push_str(&format!("{x}{y} {}{end}", display(&change.path)));
+    }
+    for path in status.untracked.iter() {
+        output.push_str(&format!("?? {}{end}", display(path)));
+    }
+    output
+}
+
+/// The porcelain v2 format, e.g.
+///
+/// ```txt
+/// # branch.oid <commit> | (initial)
+/// # branch.head <branch> | (detached)
+/// 1 <XY> N... <mode HEAD> <mode index> <mode worktree> <hash HEAD> <hash index> <path>
+/// u <XY> N... <mode 1> <mode 2> <mode 3> <mode worktree> <hash 1> <hash 2> <hash 3> <path>
+/// ? <path>
+/// ```
+fn porcelain_v2(
+    status: &Status,
+    options: &StatusOptions,
+    display: &dyn Fn(&str) -> String,
+    null: &ObjectId,
+) -> String {
+    let end = match options.null_terminated {
+        true => '\0',
+        false => '\n',
+    };
+    let mode = |version: Option<&Version>| format!("{:06o}", version.map_or(0, |v| v.mode));
+    let hash = |version: Option<&Version>| version.map_or(*null, |version| version.hash);
+    let mut output = String::new();
+    if options.branch {
+        let oid = status
+            .head
+            .map_or("(initial)".to_string(), |head| head.to_string());
+        let head = status
+            .head_ref
+            .strip_prefix("refs/heads/")
+            .unwrap_or("(detached)");
+        output.push_str(&format!("# branch.oid {oid}{end}# branch.head {head}{end}"));
+    }
+    for change in status.changes.iter() {
+        let worktree = format!("{:06o}", change.worktree.unwrap_or(0));
+        let dot = |c: char| if c == ' ' { '.' } else { c };
+        let xy = format!("{}{}", dot(change.staged), dot(change.unstaged));
+        let path = display(&change.path);
+        let line = match &change.stages {
+            Some(stages) => {
+                let [base, ours, theirs] = stages.each_ref().map(Option::as_ref);
+                format!(
+                    "u {xy} N... {} {} {} {worktree} {} {} {} {path}",
+                    mode(base),
+                    mode(ours),
+                    mode(theirs),
+                    hash(base),
+                    hash(ours),
+                    hash(theirs),
+                )
+            }
+            None => {
+                let (head, index) = (change.head.as_ref(), change.index.as_ref());
+                format!(
+                    "1 {xy} N... {} {} {worktree} {} {} {path}",
+                    mode(head),
+                    mode(index),
+                    hash(head),
+                    hash(index),
+                )
+            }
+        };
+        output.push_str(&line);
+        output.push(end);
+    }
+    for path in status.untracked.iter() {
+        output.push_str(&format!("? {}{end}", display(path)));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{status, StatusOptions};
+    use crate::{
+        commands::{
+            add::{add, AddOptions},
+            commit::{commit, CommitOptions},
+        },
+        context::tests::TestContext,
+    };
+
+    #[test]
+    fn status_formats() {
+        let context = TestContext::init().with_identity();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::create_dir_all(root.join("new/nested")).unwrap();
+        fs::write(root.join("a.txt"), "hello\n").unwrap();
+        fs::write(root.join("b.txt"), "").unwrap();
+        let options = AddOptions {
+            all: true,
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+        let options = CommitOptions {
+            messages: vec!["Initial".to_string()],
+            quiet: true,
+            ..Default::default()
+        };
+        commit(context, options).unwrap();
+
+        let short = |options: StatusOptions| {
+            let options = StatusOptions {
+                porcelain: Some("v1".to_string()),
+                ..options
+            };
+            status(context, options).unwrap()
+        };
+        assert_eq!(short(StatusOptions::default()), "");
+
+        fs::write(root.join("a.txt"), "changed\n").unwrap();
+        fs::remove_file(root.join("b.txt")).unwrap();
+        fs::write(root.join("c.txt"), "").unwrap();
+        fs::write(root.join("new/nested/d.txt"), "").unwrap();
+        let options = AddOptions {
+            pathspecs: vec!["c.txt".to_string()],
+            ..Default::default()
+        };
+        add(context, options).unwrap();
+        assert_eq!(
+            short(StatusOptions::default()),
+            " M a.txt\n D b.txt\nA  c.txt\n?? new/\n"
+        );
+        let options = StatusOptions {
+            untracked_files: Some("all".to_string()),
+            null_terminated: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            short(options),
+            " M a.txt\0 D b.txt\0A  c.txt\0?? new/nested/d.txt\0"
+        );
+
+        let options = StatusOptions {
+            porcelain: Some("v2".to_string()),
+            untracked_files: Some("no".to_string()),
+            pathspecs: vec!["a.txt".to_string(), "c.txt".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            status(context, options).unwrap(),
+            "1 .M N... 100644 100644 100644 ce013625030ba8dba906f756967f9e9ca394464a \
+            ce013625030ba8dba906f756967f9e9ca394464a a.txt\n\
+            1 A. N... 000000 100644 100644 0000000000000000000000000000000000000000 \
+            e69de29bb2d1d6434b8b29ae775ad8c2e48c5391 c.txt\n"
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use std::cmp::Ordering;
+
+use anyhow::{bail, Result};
+use clap::Args;
+
+use super::{commit::cleanup_message, commit_tree::join_messages};
+use crate::{
+    config::Config,
+    context::Context,
+    identity::{identity, Role},
+    objects::{
+        id::ObjectId,
+        object::{Contents, Object},
+        tag::TagContents,
+    },
+    refs,
+    revision::resolve_revision,
+    wildmatch::wildmatch,
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct TagOptions {
+    /// List the tags (matching any of the patterns, if given)
+    #[arg(short, long, conflicts_with_all = ["delete", "annotate", "force"])]
+    pub(crate) list: bool,
+
+    /// Delete the tags
+    #[arg(short, long, conflicts_with_all = ["annotate", "force"])]
+    pub(crate) delete: bool,
+
+    /// Create an annotated tag (implied by `-m` and `-F`)
+    #[arg(short, long)]
+    pub(crate) annotate: bool,
+
+    /// A paragraph of the tag message (can be given more than once)
+    #[arg(short, long = "message", value_name = "MESSAGE")]
+    pub(crate) messages: Vec<String>,
+
+    /// Read the tag message from the file ("-" for the standard input)
+    #[arg(short = 'F', long = "file", value_name = "FILE")]
+    pub(crate) files: Vec<String>,
+
+    /// Replace an existing tag
+    #[arg(short, long)]
+    pub(crate) force: bool,
+
+    /// Sort the listed tags by "refname", "version:refname" (or "v:refname"),
+    /// "creatordate" or "taggerdate" ("-<key>" for the reverse order)
+    #[arg(long, value_name = "KEY")]
+    pub(crate) sort: Option<String>,
+
+    /// `<tagname> [<object>]` to create a tag (of `HEAD` by default), the tags
+    /// to delete, or the patterns to list
+    #[arg(value_name = "ARGS")]
+    pub(crate) args: Vec<String>,
+}
+
+/// Creates, lists or deletes the tags (in `refs/tags/`). Returns whether all
+/// the tags to delete existed.
+pub(crate) fn tag(context: &Context, options: TagOptions) -> Result<bool> {
+    if options.delete {
+        return delete(context, &options.args);
+    }
+    let annotate = options.annotate || !options.messages.is_empty() || !options.files.is_empty();
+    if options.list || (options.args.is_empty() && !annotate) {
+        for name in list(context, &options.args, options.sort.as_deref())? {
+            println!("{name}");
+        }
+        return Ok(true);
+    }
+
+    let (name, object) = match options.args.as_slice() {
+        [name] => (name, "HEAD"),
+        [name, object] => (name, object.as_str()),
+        _ => bail!("too many arguments"),
+    };
+    let ref_name = format!("refs/tags/{name}");
+    if !refs::is_valid_name(&ref_name) {
+        bail!("'{name}' is not a valid tag name.");
+    }
+    let old = refs::resolve_ref(context, &ref_name)?.1;
+    if old.is_some() && !options.force {
+        bail!("tag '{name}' already exists");
+    }
+
+    let mut hash = resolve_revision(context, object)?;
+    if annotate {
+        if options.messages.is_empty() && options.files.is_empty() {
+            bail!("no tag message?");
+        }
+        let message = cleanup_message(&join_messages(&options.messages, &options.files)?);
+        let kind = context.database.read(&hash)?.kind();
+        let tagger = identity(&Config::load(context)?, Role::Committer)?;
+        let tag = TagContents::new(hash, kind, name.to_string(), tagger, message);
+        hash = context.database.write(&Object::new_tag(tag))?;
+    }
+    let expected = old.unwrap_or(ObjectId::null(context.hash_algorithm()));
+    refs::update_ref(context, &ref_name, &hash, Some(&expected), false)?;
+    if let Some(old) = old.filter(|old| *old != hash) {
+        let old = context.database.abbreviate(&old, 7)?;
+        println!("Updated tag '{name}' (was {old})");
+    }
+    Ok(true)
+}
+
+/// The names of the tags matching any of the patterns (or all of them), in
+/// the order of the sort key.
+fn list(context: &Context, patterns: &[String], sort: Option<&str>) -> Result<Vec<String>> {
+    let mut tags = Vec::new();
+    for (name, hash) in refs::list_refs(context, "refs/tags/")? {
+        let name = name.trim_start_matches("refs/tags/").to_string();
+        if patterns.is_empty() || patterns.iter().any(|p| wildmatch(p, &name, false)) {
+            tags.push((name, hash));
+        }
+    }
+
+    let (key, reverse) = match sort {
+        Some(key) => match key.strip_prefix('-') {
+            Some(key) => (key, true),
+            None => (key, false),
+        },
+        None => ("refname", false),
+    };
+    match key {
+        "refname" => {}
+        "version:refname" | "v:refname" => tags.sort_by(|a, b| version_cmp(&a.0, &b.0)),
+        "creatordate" | "taggerdate" => {
+            let mut dates = Vec::new();
+            for (name, hash) in tags {
+                let date = match context.database.read(&hash)?.contents {
+                    Contents::Tag(tag) => tag.tagger.map(|tagger| tagger.timestamp),
+                    Contents::Commit(commit) if key == "creatordate" => {
+                        Some(commit.committer().timestamp)
+                    }
+                    _ => None,
+                };
+                dates.push((date, name, hash));
+            }
+            // The tags without a date come first, and the ties are sorted by
+            // name.
+            dates.sort_by_key(|a| a.0);
+            tags = dates
+                .into_iter()
+                .map(|(_, name, hash)| (name, hash))
+                .collect();
+        }
+        key => bail!("unsupported sort specification '{key}'"),
+    }
+    if reverse {
+        tags.reverse();
+    }
+    Ok(tags.into_iter().map(|(name, _)| name).collect())
+}
+
+/// Deletes the tags, and returns whether all of them existed.
+fn delete(context: &Context, names: &[String]) -> Result<bool> {
+    let mut found = true;
+    for name in names {
+        let ref_name = format!("refs/tags/{name}");
+        let Some(hash) = refs::resolve_ref(context, &ref_name)?.1 else {
+            eprintln!("error: tag '{name}' not found.");
+            found = false;
+            continue;
+        };
+        refs::delete_ref(context, &ref_name, Some(&hash), false)?;
+        let hash = context.database.abbreviate(&hash, 7)?;
+        println!("Deleted tag '{name}' (was {hash})");
+    }
+    Ok(found)
+}
+
+/// Compares the names like versions: the sequences of digits are compared as
+/// numbers (e.g. `v1.9` < `v1.10`).
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let digits = |s: &[u8]| s.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (x, y) = (&a[..digits(a)], &b[..digits(b)]);
+                let trim = |s: &[u8]| s.iter().position(|&c| c != b'0').map_or(0, |i| s.len() - i);
+                let ordering = trim(x).cmp(&trim(y)).then_with(|| {
+                    let (x, y) = (&x[x.len() - trim(x)..], &y[y.len() - trim(y)..]);
+                    x.cmp(y)
+                });
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                (a, b) = (&a[x.len()..], &b[y.len()..]);
+            }
+            (Some(x), Some(y)) if x != y => return x.cmp(y),
+            _ => (a, b) = (&a[1..], &b[1..]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{list, tag, version_cmp, TagOptions};
+    use crate::{
+        context::tests::TestContext, objects::object::Contents, refs, revision::resolve_revision,
+        revwalk::tests::commit,
+    };
+
+    #[test]
+    fn create_list_delete() {
+        let context = TestContext::init().with_identity();
+        let context = &context.context;
+        let first = commit(context, ("a", "1"), &[], 100);
+        let second = commit(context, ("a", "2"), &[&first], 200);
+        refs::update_ref(context, "HEAD", &second, None, true).unwrap();
+
+        let run = |args: &[&str], options: TagOptions| {
+            let args = args.iter().map(|arg| arg.to_string()).collect();
+            tag(context, TagOptions { args, ..options })
+        };
+        run(&["v1.9", &first.to_string()], TagOptions::default()).unwrap();
+        run(&["v1.10"], TagOptions::default()).unwrap();
+        assert!(run(&["v1.10"], TagOptions::default()).is_err());
+        assert!(run(&["a..b"], TagOptions::default()).is_err());
+        let force = TagOptions {
+            force: true,
+            ..Default::default()
+        };
+        run(&["v1.10", &first.to_string()], force).unwrap();
+        assert_eq!(resolve_revision(context, "v1.10").unwrap(), first);
+
+        let annotated = TagOptions {
+            messages: vec!["Release  ".to_string(), "Notes".to_string()],
+            ..Default::default()
+        };
+        run(&["v2.0"], annotated).unwrap();
+        let hash = resolve_revision(context, "v2.0").unwrap();
+        let Contents::Tag(tag) = context.database.read(&hash).unwrap().contents else {
+            panic!("v2.0 is not an annotated tag");
+        };
+        assert_eq!((tag.object, tag.tag.as_str()), (second, "v2.0"));
+        assert_eq!(tag.message, b"Release\n\nNotes\n");
+        assert_eq!(resolve_revision(context, "v2.0^{}").unwrap(), second);
+
+        let annotate = TagOptions {
+            annotate: true,
+            ..Default::default()
+        };
+        assert!(run(&["v3.0"], annotate).is_err());
+
+        let tags = |patterns: &[&str], sort| {
+            let patterns: Vec<_> = patterns.iter().map(|p| p.to_string()).collect();
+            list(context, &patterns, sort).unwrap()
+        };
+        assert_eq!(tags(&[], None), ["v1.10", "v1.9", "v2.0"]);
+        assert_eq!(tags(&["v1*"], None), ["v1.10", "v1.9"]);
+        assert_eq!(tags(&[], Some("-v:refname")), ["v2.0", "v1.10", "v1.9"]);
+        assert!(list(context, &[], Some("size")).is_err());
+
+        let delete = TagOptions {
+            delete: true,
+            ..Default::default()
+        };
+        assert!(!run(&["v1.9", "missing"], delete).unwrap());
+        assert_eq!(tags(&[], None), ["v1.10", "v2.0"]);
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(version_cmp("v1.9", "v1.10"), Ordering::Less);
+        assert_eq!(version_cmp("v1.010", "v1.9"), Ordering::Greater);
+        assert_eq!(version_cmp("v1.0", "v1.0-rc1"), Ordering::Less);
+        assert_eq!(version_cmp("a", "b"), Ordering::Less);
+        assert_eq!(version_cmp("v2", "v2"), Ordering::Equal);
+    }
+}
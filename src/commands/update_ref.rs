@@ -0,0 +1,125 @@
+use anyhow::{bail, Result};
+use clap::Args;
+
+use crate::{context::Context, objects::id::ObjectId, refs, revision::resolve_revision};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct UpdateRefOptions {
+    /// Delete the ref (after verifying that it's at <NEW_VALUE>, if given)
+    #[arg(short = 'd')]
+    pub(crate) delete: bool,
+
+    /// Update the symbolic ref itself, instead of the ref it points to
+    #[arg(long)]
+    pub(crate) no_deref: bool,
+
+    /// The ref to update (e.g. HEAD or refs/heads/main)
+    #[arg(name = "REF")]
+    pub(crate) reference: String,
+
+    /// The object the ref should point to
+    pub(crate) new_value: Option<String>,
+
+    /// Only update the ref if it currently points to this object (the zero
+    /// hash, or an empty string, if it must not exist yet)
+    pub(crate) old_value: Option<String>,
+}
+
+/// Updates the ref to point to the new object, or deletes it.
+pub(crate) fn update_ref(context: &Context, options: UpdateRefOptions) -> Result<()> {
+    let deref = !options.no_deref;
+    if options.delete {
+        if options.old_value.is_some() {
+            bail!("usage: git update-ref -d <refname> [<old-val>]");
+        }
+        let old = resolve_old(context, options.new_value.as_deref())?;
+        return Ok(refs::delete_ref(
+            context,
+            &options.reference,
+            old.as_ref(),
+            deref,
+        )?);
+    }
+
+    let Some(new) = options.new_value else {
+        bail!("usage: git update-ref <refname> <new-val> [<old-val>]");
+    };
+    if is_null(&new) {
+        // Same as deleting the ref.
+        let old = resolve_old(context, options.old_value.as_deref())?;
+        return Ok(refs::delete_ref(
+            context,
+            &options.reference,
+            old.as_ref(),
+            deref,
+        )?);
+    }
+    let new = resolve_revision(context, &new)?;
+    let old = resolve_old(context, options.old_value.as_deref())?;
+    Ok(refs::update_ref(
+        context,
+        &options.reference,
+        &new,
+        old.as_ref(),
+        deref,
+    )?)
+}
+
+/// The expected old value keeps the zero hash (and the empty string), which
+/// mean that the ref must not exist.
+fn resolve_old(context: &Context, old: Option<&str>) -> Result<Option<ObjectId>> {
+    match old {
+        Some(old) if is_null(old) => Ok(Some(ObjectId::null(context.hash_algorithm()))),
+        Some(old) => Ok(Some(resolve_revision(context, old)?)),
+        None => Ok(None),
+    }
+}
+
+/// Whether the value is the zero hash (of any algorithm), or empty.
+fn is_null(value: &str) -> bool {
+    value.is_empty() || value.parse::<ObjectId>().is_ok_and(|id| id.is_null())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{update_ref, UpdateRefOptions};
+    use crate::{context::tests::TestContext, objects::object::Object};
+
+    #[test]
+    fn update_and_delete() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let first = context.database.write(&Object::new_blob(b"1\n")).unwrap();
+        let second = context.database.write(&Object::new_blob(b"2\n")).unwrap();
+        let main = context.git_dir.join("refs/heads/main");
+
+        let options = UpdateRefOptions {
+            reference: "HEAD".to_string(),
+            new_value: Some(format!("{first:.7}")),
+            old_value: Some("".to_string()),
+            ..Default::default()
+        };
+        update_ref(context, options).unwrap();
+        assert_eq!(fs::read_to_string(&main).unwrap(), format!("{first}\n"));
+
+        let options = UpdateRefOptions {
+            reference: "refs/heads/main".to_string(),
+            new_value: Some(second.to_string()),
+            old_value: Some(second.to_string()),
+            ..Default::default()
+        };
+        assert!(update_ref(context, options).is_err());
+        assert_eq!(fs::read_to_string(&main).unwrap(), format!("{first}\n"));
+
+        let options = UpdateRefOptions {
+            delete: true,
+            reference: "refs/heads/main".to_string(),
+            new_value: Some(first.to_string()),
+            ..Default::default()
+        };
+        update_ref(context, options).unwrap();
+        assert!(!main.exists());
+    }
+}
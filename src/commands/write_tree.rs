@@ -0,0 +1,173 @@
+use std::str;
+
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+
+use crate::{
+    context::Context,
+    index::{Index, IndexEntry},
+    objects::{
+        id::ObjectId,
+        mode::FileMode,
+        object::Object,
+        tree::{TreeContents, TreeRowItem},
+    },
+};
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct WriteTreeOptions {
+    /// Allow objects (referenced by the index) missing from the object database
+    #[arg(long)]
+    pub(crate) missing_ok: bool,
+
+    /// Write the tree object for the subdirectory <PREFIX> (e.g. "src/")
+    #[arg(long)]
+    pub(crate) prefix: Option<String>,
+}
+
+/// Creates a tree object (and its subtrees) from the current index, and returns
+/// its hash.
+pub(crate) fn write_tree(context: &Context, options: WriteTreeOptions) -> Result<ObjectId> {
+    let index = Index::read(context)?;
+    write_index_tree(context, &index, &options)
+}
+
+pub(crate) fn write_index_tree(
+    context: &Context,
+    index: &Index,
+    options: &WriteTreeOptions,
+) -> Result<ObjectId> {
+    if let Some(entry) = index.entries.iter().find(|entry| entry.stage() != 0) {
+        bail!("{}: unmerged ({})", entry.path_str(), entry.hash);
+    }
+
+    let prefix = match options.prefix.as_deref() {
+        None | Some("") | Some("/") => String::new(),
+        Some(prefix) => format!("{}/", prefix.trim_end_matches('/')),
+    };
+    let entries: Vec<_> = index
+        .entries
+        .iter()
+        .filter(|entry| entry.path.starts_with(prefix.as_bytes()) && !entry.is_intent_to_add())
+        .collect();
+    if entries.is_empty() && !prefix.is_empty() {
+        bail!("prefix {prefix} not found");
+    }
+    write_entries(context, &entries, &prefix, options.missing_ok)
+}
+
+/// Writes the tree for the `entries` (sorted by path) in the directory
+/// `prefix`, after writing the subtrees.
+fn write_entries(
+    context: &Context,
+    entries: &[&IndexEntry],
+    prefix: &str,
+    missing_ok: bool,
+) -> Result<ObjectId> {
+    let mut lines = Vec::new();
+    let mut i = 0;
+    while i < entries.len() {
+        let entry = entries[i];
+        // The names in trees are strings.
+        let path = str::from_utf8(&entry.path[prefix.len()..])
+            .map_err(|_| anyhow!("invalid path '{}'", entry.path_str()))?;
+        match path.split_once('/') {
+            Some((dir, _)) => {
+                // Since the entries are sorted, the ones in this directory are
+                // next to each other.
+                let dir_prefix = format!("{prefix}{dir}/");
+                let count = entries[i..]
+                    .iter()
+                    .take_while(|entry| entry.path.starts_with(dir_prefix.as_bytes()))
+                    .count();
+                let hash = write_entries(context, &entries[i..i + count], &dir_prefix, missing_ok)?;
+                lines.push(TreeRowItem::new(FileMode::Tree, hash, dir));
+                i += count;
+            }
+            None => {
+                let mode = FileMode::from_bits(entry.mode)?;
+                // Submodule commits (gitlinks) aren't in this repository.
+                if !missing_ok
+                    && mode != FileMode::Gitlink
+                    && !context.database.exists(&entry.hash)?
+                {
+                    bail!(
+                        "invalid object {mode} {} for '{}'",
+                        entry.hash,
+                        entry.path_str()
+                    );
+                }
+                lines.push(TreeRowItem::new(mode, entry.hash, path));
+                i += 1;
+            }
+        }
+    }
+    Ok(context
+        .database
+        .write(&Object::new_tree(TreeContents { lines }))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_index_tree, WriteTreeOptions};
+    use crate::{
+        context::tests::TestContext,
+        index::{Index, IndexEntry},
+        objects::object::Object,
+    };
+
+    fn index() -> Index {
+        let entry = |mode, path: &str| IndexEntry {
+            mode,
+            hash: "ce013625030ba8dba906f756967f9e9ca394464a".parse().unwrap(),
+            path: path.into(),
+            ..Default::default()
+        };
+        Index {
+            entries: vec![
+                entry(0o100644, "a.txt"),
+                entry(0o100755, "b"),
+                entry(0o100644, "src.rs"),
+                entry(0o100644, "src/x"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn write_tree() {
+        let context = TestContext::in_memory();
+        let context = &context.context;
+        context
+            .database
+            .write(&Object::new_blob(b"hello\n"))
+            .unwrap();
+
+        let options = WriteTreeOptions::default();
+        let hash = write_index_tree(context, &index(), &options).unwrap();
+        assert_eq!(hash, "b67207be6104838c2e10caa80cfdb2c330fe3f45");
+
+        let options = WriteTreeOptions {
+            prefix: Some("src/".to_string()),
+            ..Default::default()
+        };
+        let hash = write_index_tree(context, &index(), &options).unwrap();
+        assert_eq!(hash, "e31a96220fbfbe7601ecc086a36b96dc27a8867e");
+    }
+
+    #[test]
+    fn write_tree_missing() {
+        let context = TestContext::in_memory();
+        let context = &context.context;
+
+        let options = WriteTreeOptions::default();
+        assert!(write_index_tree(context, &index(), &options).is_err());
+
+        let options = WriteTreeOptions {
+            missing_ok: true,
+            ..Default::default()
+        };
+        let hash = write_index_tree(context, &index(), &options).unwrap();
+        assert_eq!(hash, "b67207be6104838c2e10caa80cfdb2c330fe3f45");
+    }
+}
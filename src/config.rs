@@ -0,0 +1,249 @@
+use std::{env, fs, iter, path::PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::context::Context;
+
+/// Configuration variables, read from the global (`~/.gitconfig`) and the
+/// repository (`.git/config`) config files.
+///
+/// ```txt
+/// [section]
+///     key = value
+/// [section "subsection"]
+///     key = "quoted value" ; comment
+/// ```
+#[derive(Default)]
+pub(crate) struct Config {
+    /// `(section[.subsection].key, value)`, in the order they were read. The
+    /// value is `None` for keys without a value (implicitly `true`).
+    entries: Vec<(String, Option<String>)>,
+}
+
+impl Config {
+    /// Reads the config files that exist. Values in the later files (the
+    /// repository) take precedence over the earlier ones (global).
+    pub(crate) fn load(context: &Context) -> Result<Self> {
+        let mut paths = Vec::new();
+        if let Some(home) = env::var_os("HOME") {
+            let home = PathBuf::from(home);
+            let xdg_config = match env::var_os("XDG_CONFIG_HOME") {
+                Some(dir) => PathBuf::from(dir),
+                None => home.join(".config"),
+            };
+            paths.push(xdg_config.join("git").join("config"));
+            paths.push(home.join(".gitconfig"));
+        }
+        paths.push(context.git_dir.join("config"));
+
+        let mut config = Self::default();
+        for path in paths {
+            if path.is_file() {
+                config.parse(&fs::read_to_string(&path)?)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// The last value of the variable (e.g. `user.name`).
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        let key = normalize_key(key);
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value.as_deref().unwrap_or("true"))
+    }
+
+    /// The last value of the boolean variable: `true`, `yes`, `on` or `1` (or
+    /// no value) for true, and `false`, `no`, `off`, `0` or an empty value
+    /// for false.
+    pub(crate) fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        let Some(value) = self.get(key) else {
+            return Ok(None);
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(Some(true)),
+            "false" | "no" | "off" | "0" | "" => Ok(Some(false)),
+            _ => bail!("bad boolean config value '{value}' for '{key}'"),
+        }
+    }
+
+    /// Parses the contents of a config file, and adds its variables.
+    pub(crate) fn parse(&mut self, body: &str) -> Result<()> {
+        let mut section = None;
+        let mut lines = body.lines();
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                section = Some(parse_section(line)?);
+                continue;
+            }
+            let Some(section) = &section else {
+                bail!("Invalid config (key outside a section): {line}");
+            };
+
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value)),
+                None => (line, None),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-') {
+                bail!("Invalid config key: {name}");
+            }
+            let value = match value {
+                Some(value) => Some(parse_value(value, &mut lines)?),
+                None => None,
+            };
+            let key = format!("{section}.{}", name.to_lowercase());
+            self.entries.push((key, value));
+        }
+        Ok(())
+    }
+}
+
+/// Section and key names are case-insensitive, but subsections aren't.
+fn normalize_key(key: &str) -> String {
+    let (section, rest) = key.split_once('.').unwrap_or((key, ""));
+    let (subsection, name) = match rest.rsplit_once('.') {
+        Some((subsection, name)) => (Some(subsection), name),
+        None => (None, rest),
+    };
+    match subsection {
+        Some(subsection) => format!(
+            "{}.{subsection}.{}",
+            section.to_lowercase(),
+            name.to_lowercase()
+        ),
+        None => format!("{}.{}", section.to_lowercase(), name.to_lowercase()),
+    }
+}
+
+/// Parses `[section]`, `[section "subsection"]` or the deprecated
+/// `[section.subsection]`.
+fn parse_section(line: &str) -> Result<String> {
+    let end = line
+        .find(']')
+        .ok_or(anyhow!("Invalid config section: {line}"))?;
+    let header = &line[1..end];
+    match header.split_once(' ') {
+        Some((section, subsection)) => {
+            let subsection = subsection.trim();
+            if !subsection.starts_with('"') || !subsection.ends_with('"') || subsection.len() < 2 {
+                bail!("Invalid config section: {line}");
+            }
+            let subsection = subsection[1..subsection.len() - 1]
+                .replace("\\\"", "\"")
+                .replace("\\\\", "\\");
+            Ok(format!("{}.{subsection}", section.to_lowercase()))
+        }
+        None => Ok(match header.split_once('.') {
+            Some((section, subsection)) => {
+                format!("{}.{}", section.to_lowercase(), subsection.to_lowercase())
+            }
+            None => header.to_lowercase(),
+        }),
+    }
+}
+
+/// Parses a value: comments are removed, escape sequences are replaced, and a
+/// trailing backslash continues the value on the next line. Like git, unquoted
+/// whitespace is trimmed, and replaced by spaces within the value.
+fn parse_value<'a>(value: &str, lines: &mut impl Iterator<Item = &'a str>) -> Result<String> {
+    let mut result = String::new();
+    let mut quoted = false;
+    let mut spaces = 0;
+    let mut chars: Vec<_> = value.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        if c.is_whitespace() && !quoted {
+            if !result.is_empty() {
+                spaces += 1;
+            }
+            continue;
+        }
+        if (c == '#' || c == ';') && !quoted {
+            break;
+        }
+        if c == '\\' && i == chars.len() {
+            match lines.next() {
+                Some(line) => {
+                    chars = line.chars().collect();
+                    i = 0;
+                    continue;
+                }
+                None => break,
+            }
+        }
+        result.extend(iter::repeat_n(' ', spaces));
+        spaces = 0;
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => {
+                let escaped = chars[i];
+                i += 1;
+                result.push(match escaped {
+                    'n' => '\n',
+                    't' => '\t',
+                    'b' => '\x08',
+                    '\\' | '"' => escaped,
+                    _ => bail!("Invalid escape sequence in config: \\{escaped}"),
+                });
+            }
+            c => result.push(c),
+        }
+    }
+    if quoted {
+        bail!("Invalid config value (unterminated quote): {value}");
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn parse() {
+        let mut config = Config::default();
+        config
+            .parse(
+                "# comment\n\
+                [core]\n\
+                \tbare = false\n\
+                \tfilemode\n\
+                [User]\n\
+                \tName = A U Thor  ; comment\n\
+                \temail = \"author@example.com\"\n\
+                [remote \"Origin\"]\n\
+                \turl = https://example.com/repo.git # comment\n\
+                \tfetch = \"+refs/heads/*:refs/remotes/origin/*\"\n\
+                [alias]\n\
+                \tlg = log \\\n\
+                \t--oneline\n",
+            )
+            .unwrap();
+        config
+            .parse("[user]\n\temail = other@example.com\n")
+            .unwrap();
+
+        assert_eq!(config.get("core.bare"), Some("false"));
+        assert_eq!(config.get("core.fileMode"), Some("true"));
+        assert_eq!(config.get("user.name"), Some("A U Thor"));
+        assert_eq!(config.get("user.email"), Some("other@example.com"));
+        assert_eq!(
+            config.get("remote.Origin.url"),
+            Some("https://example.com/repo.git")
+        );
+        assert_eq!(config.get("remote.origin.url"), None);
+        assert_eq!(config.get("alias.lg"), Some("log  --oneline"));
+        assert_eq!(config.get("user.missing"), None);
+        assert_eq!(config.get_bool("core.bare").unwrap(), Some(false));
+        assert_eq!(config.get_bool("core.filemode").unwrap(), Some(true));
+        assert!(config.get_bool("user.name").is_err());
+    }
+}
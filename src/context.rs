@@ -1,35 +1,73 @@
 use std::path::PathBuf;
 
+use anyhow::Result;
+
+use crate::objects::{database::CompositeDatabase, hash::HashAlgorithm, ObjectDatabase};
+
 pub struct Context {
     pub repo_root: PathBuf,
     // Though this git_dir doesn't have to be inside the repo root, here we assume git_dir to
     // always be "<repo_root>/.git"
     pub git_dir: PathBuf,
+    pub(crate) database: Box<dyn ObjectDatabase>,
 }
 
 impl Context {
-    pub(crate) fn new(repo_root: PathBuf) -> Self {
+    /// Opens the repository, with the object format of its config.
+    pub(crate) fn new(repo_root: PathBuf) -> Result<Self> {
         let git_dir = repo_root.join(".git");
-        Self { repo_root, git_dir }
+        let algorithm = HashAlgorithm::from_repository(&git_dir)?;
+        let database = CompositeDatabase::open(&git_dir.join("objects"), algorithm);
+        Ok(Self::with_database(repo_root, Box::new(database)))
     }
-    pub(crate) fn object_dir(&self, hash: &str) -> PathBuf {
-        self.git_dir.join("objects").join(&hash[..2])
+
+    pub(crate) fn with_database(repo_root: PathBuf, database: Box<dyn ObjectDatabase>) -> Self {
+        let git_dir = repo_root.join(".git");
+        Self {
+            repo_root,
+            git_dir,
+            database,
+        }
     }
 
-    pub(crate) fn object_path(&self, hash: &str) -> PathBuf {
-        self.object_dir(hash).join(&hash[2..])
+    /// The hash function naming the objects of the repository.
+    pub(crate) fn hash_algorithm(&self) -> HashAlgorithm {
+        self.database.hash_algorithm()
     }
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
+    use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
     use tempfile::TempDir;
 
     use super::Context;
-    use crate::commands::{self, InitOptions};
+    use crate::{
+        commands::{self, InitOptions},
+        objects::{database::MemoryDatabase, id::ObjectId},
+    };
+
+    impl Context {
+        pub fn objects_dir(&self) -> PathBuf {
+            self.git_dir.join("objects")
+        }
+
+        pub fn object_path(&self, id: &ObjectId) -> PathBuf {
+            let hex = id.to_string();
+            self.objects_dir().join(&hex[..2]).join(&hex[2..])
+        }
+
+        pub fn pack_dir(&self) -> PathBuf {
+            self.objects_dir().join("pack")
+        }
+    }
+
+    /// The config of the identity used by the tests (see `with_identity`).
+    pub const IDENTITY: &str = "[user]\n\tname = A U Thor\n\temail = author@example.com\n";
 
     pub struct TestContext {
-        _temp_dir: TempDir,
+        _temp_dir: Option<TempDir>,
         pub context: Context,
     }
 
@@ -38,13 +76,23 @@ pub(crate) mod tests {
         /// This directory will be deleted when the value is dropped.
         pub fn no_init() -> Self {
             let temp_dir = TempDir::new().unwrap();
-            let context = Context::new(temp_dir.path().to_path_buf());
+            let context = Context::new(temp_dir.path().to_path_buf()).unwrap();
             Self {
-                _temp_dir: temp_dir,
+                _temp_dir: Some(temp_dir),
                 context,
             }
         }
 
+        /// Creates a context whose objects are only kept in memory. Nothing is
+        /// written to the file system.
+        pub fn in_memory() -> Self {
+            let database = Box::<MemoryDatabase>::default();
+            Self {
+                _temp_dir: None,
+                context: Context::with_database("/nonexistent".into(), database),
+            }
+        }
+
         pub fn init() -> Self {
             let context = Self::no_init();
             let options = InitOptions {
@@ -54,5 +102,18 @@ pub(crate) mod tests {
             commands::init::init(options).unwrap();
             context
         }
+
+        /// Sets the identity (`A U Thor <author@example.com>`) in the config,
+        /// for the commands creating commits or tags.
+        pub fn with_identity(self) -> Self {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.context.git_dir.join("config"))
+                .unwrap()
+                .write_all(IDENTITY.as_bytes())
+                .unwrap();
+            self
+        }
     }
 }
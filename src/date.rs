@@ -0,0 +1,354 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset as _, TimeZone};
+
+/// The offset of a timezone from UTC, in minutes (e.g. `-330` for `-0530`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Offset(pub i32);
+
+impl Offset {
+    /// Parses an offset written like `+0530` or `-0800`.
+    pub(crate) fn parse(offset: &str) -> Option<Self> {
+        let sign = match offset.as_bytes().first()? {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return None,
+        };
+        let digits = &offset[1..];
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let hours: i32 = digits[..2].parse().ok()?;
+        let minutes: i32 = digits[2..].parse().ok()?;
+        Some(Self(sign * (hours * 60 + minutes)))
+    }
+
+    /// The offset of the local timezone at the timestamp.
+    pub(crate) fn local(timestamp: i64) -> Self {
+        match Local.timestamp_opt(timestamp, 0).earliest() {
+            Some(date) => Self(date.offset().fix().local_minus_utc() / 60),
+            None => Self(0),
+        }
+    }
+
+    fn fixed(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.0 * 60).unwrap_or(FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+impl Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { '-' } else { '+' };
+        let minutes = self.0.abs();
+        write!(f, "{sign}{:02}{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+/// The formats of `--date`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum DateFormat {
+    /// `Tue Nov 14 23:13:20 2023 +0100`
+    #[default]
+    Default,
+    /// Like the default format, in the local timezone (without the offset).
+    Local,
+    /// `2023-11-14 23:13:20 +0100`
+    Iso,
+    /// `2023-11-14T23:13:20+01:00`
+    IsoStrict,
+    /// `Tue, 14 Nov 2023 23:13:20 +0100`
+    Rfc,
+    /// `2023-11-14`
+    Short,
+    /// `1700000000 +0100`
+    Raw,
+    /// `1700000000`
+    Unix,
+    /// `2 hours ago`
+    Relative,
+}
+
+impl DateFormat {
+    /// The names accepted by `--date`.
+    pub(crate) const NAMES: [&'static str; 12] = [
+        "default",
+        "local",
+        "iso",
+        "iso8601",
+        "iso-strict",
+        "iso8601-strict",
+        "rfc",
+        "rfc2822",
+        "short",
+        "raw",
+        "unix",
+        "relative",
+    ];
+}
+
+impl FromStr for DateFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        Ok(match format {
+            "default" => Self::Default,
+            "local" => Self::Local,
+            "iso" | "iso8601" => Self::Iso,
+            "iso-strict" | "iso8601-strict" => Self::IsoStrict,
+            "rfc" | "rfc2822" => Self::Rfc,
+            "short" => Self::Short,
+            "raw" => Self::Raw,
+            "unix" => Self::Unix,
+            "relative" => Self::Relative,
+            _ => bail!("unknown date format {format}"),
+        })
+    }
+}
+
+/// Formats the timestamp, in the timezone of the offset.
+pub(crate) fn format_date(timestamp: i64, offset: Offset, format: DateFormat) -> String {
+    let Some(utc) = DateTime::from_timestamp(timestamp, 0) else {
+        return format!("{timestamp} {offset}");
+    };
+    let date = utc.with_timezone(&offset.fixed());
+    match format {
+        DateFormat::Default => format!("{} {offset}", date.format("%a %b %-d %H:%M:%S %Y")),
+        DateFormat::Local => utc
+            .with_timezone(&Local)
+            .format("%a %b %-d %H:%M:%S %Y")
+            .to_string(),
+        DateFormat::Iso => format!("{} {offset}", date.format("%Y-%m-%d %H:%M:%S")),
+        DateFormat::IsoStrict => date.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        DateFormat::Rfc => format!("{} {offset}", date.format("%a, %-d %b %Y %H:%M:%S")),
+        DateFormat::Short => date.format("%Y-%m-%d").to_string(),
+        DateFormat::Raw => format!("{timestamp} {offset}"),
+        DateFormat::Unix => timestamp.to_string(),
+        DateFormat::Relative => format_relative(timestamp, now()),
+    }
+}
+
+/// Formats the timestamp relative to `now`, like `3 hours ago` or `2 years, 1
+/// month ago`.
+fn format_relative(timestamp: i64, now: i64) -> String {
+    let plural = |count: i64, unit: &str| match count {
+        1 => format!("{count} {unit}"),
+        _ => format!("{count} {unit}s"),
+    };
+    if timestamp > now {
+        return "in the future".to_string();
+    }
+    let seconds = now - timestamp;
+    if seconds < 90 {
+        return format!("{} ago", plural(seconds, "second"));
+    }
+    let minutes = (seconds + 30) / 60;
+    if minutes < 90 {
+        return format!("{} ago", plural(minutes, "minute"));
+    }
+    let hours = (minutes + 30) / 60;
+    if hours < 36 {
+        return format!("{} ago", plural(hours, "hour"));
+    }
+    let days = (hours + 12) / 24;
+    if days < 14 {
+        return format!("{} ago", plural(days, "day"));
+    }
+    if days < 70 {
+        return format!("{} ago", plural((days + 3) / 7, "week"));
+    }
+    if days < 365 {
+        return format!("{} ago", plural((days + 15) / 30, "month"));
+    }
+    if days < 1825 {
+        let total_months = (days * 12 * 2 + 365) / (365 * 2);
+        let (years, months) = (total_months / 12, total_months % 12);
+        return match months {
+            0 => format!("{} ago", plural(years, "year")),
+            _ => format!("{}, {} ago", plural(years, "year"), plural(months, "month")),
+        };
+    }
+    format!("{} ago", plural((days + 183) / 365, "year"))
+}
+
+/// The current timestamp.
+pub(crate) fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+/// Parses a date given in `GIT_AUTHOR_DATE` (or `GIT_COMMITTER_DATE`):
+///
+/// - git's internal format: `[@]<timestamp> <offset>` (e.g. `1700000000 +0100`)
+/// - RFC 2822: `Tue, 14 Nov 2023 23:13:20 +0100`
+/// - ISO 8601: `2023-11-14 23:13:20 +0100` or `2023-11-14T23:13:20+01:00`
+/// - git's default format: `Tue Nov 14 23:13:20 2023 +0100`
+///
+/// The dates without an offset are in the local timezone.
+pub(crate) fn parse_date(date: &str) -> Result<(i64, Offset)> {
+    let invalid = || anyhow!("invalid date format: {date}");
+    let date = date.trim();
+    let internal = date.split_once(' ').and_then(|(timestamp, offset)| {
+        let timestamp = timestamp.strip_prefix('@').unwrap_or(timestamp);
+        Some((timestamp.parse().ok()?, Offset::parse(offset)?))
+    });
+    if let Some(internal) = internal {
+        return Ok(internal);
+    }
+    if let Some(Ok(timestamp)) = date.strip_prefix('@').map(str::parse) {
+        return Ok((timestamp, Offset(0)));
+    }
+
+    let with_offset = |date: DateTime<FixedOffset>| {
+        let offset = Offset(date.offset().local_minus_utc() / 60);
+        (date.timestamp(), offset)
+    };
+    if let Ok(date) = DateTime::parse_from_rfc2822(date) {
+        return Ok(with_offset(date));
+    }
+    let date = match date.strip_suffix('Z') {
+        Some(date) => format!("{date}+0000"),
+        None => date.to_string(),
+    };
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f%z",
+        "%Y-%m-%dT%H:%M:%S%.f%:z",
+        "%Y-%m-%d %H:%M:%S%.f %z",
+        "%Y-%m-%d %H:%M:%S%.f%:z",
+        "%a %b %e %H:%M:%S %Y %z",
+    ] {
+        if let Ok(date) = DateTime::parse_from_str(&date, format) {
+            return Ok(with_offset(date));
+        }
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%a %b %e %H:%M:%S %Y",
+    ] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(&date, format) {
+            let timestamp = local_timestamp(date)?;
+            return Ok((timestamp, Offset::local(timestamp)));
+        }
+    }
+    Err(invalid())
+}
+
+/// Parses a date given to `--since`/`--until` into a timestamp, relative to
+/// `now`:
+///
+/// - the formats of [`parse_date`]
+/// - `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` (in the local timezone)
+/// - `<n> <unit>[s] [ago]` (e.g. `2 weeks ago`), `now`, `yesterday`
+pub(crate) fn parse_approxidate(date: &str, now: i64) -> Result<i64> {
+    let invalid = || anyhow!("invalid date: {date}");
+    let date = date.trim();
+    match date {
+        "now" => return Ok(now),
+        "yesterday" => return Ok(now - 24 * 60 * 60),
+        _ => {}
+    }
+    if let Ok((timestamp, _)) = parse_date(date) {
+        return Ok(timestamp);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return local_timestamp(date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?);
+    }
+    if let Ok(date) = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M") {
+        return local_timestamp(date);
+    }
+
+    let mut words = date.split_whitespace();
+    let (Some(count), Some(unit)) = (words.next(), words.next()) else {
+        return Err(invalid());
+    };
+    if !matches!(words.next(), None | Some("ago")) {
+        return Err(invalid());
+    }
+    let count: i64 = count.parse().map_err(|_| invalid())?;
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        "month" => 30 * 24 * 60 * 60,
+        "year" => 365 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    Ok(now - count * seconds)
+}
+
+fn local_timestamp(date: NaiveDateTime) -> Result<i64> {
+    match Local.from_local_datetime(&date).earliest() {
+        Some(date) => Ok(date.timestamp()),
+        None => bail!("invalid date: {date}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_date, format_relative, parse_approxidate, parse_date, DateFormat, Offset};
+
+    #[test]
+    fn dates() {
+        let offset = Offset::parse("+0100").unwrap();
+        let format = |format| format_date(1700000000, offset, format);
+        assert_eq!(
+            format(DateFormat::Default),
+            "Tue Nov 14 23:13:20 2023 +0100"
+        );
+        assert_eq!(format(DateFormat::Iso), "2023-11-14 23:13:20 +0100");
+        assert_eq!(format(DateFormat::IsoStrict), "2023-11-14T23:13:20+01:00");
+        assert_eq!(format(DateFormat::Rfc), "Tue, 14 Nov 2023 23:13:20 +0100");
+        assert_eq!(format(DateFormat::Short), "2023-11-14");
+        assert_eq!(format(DateFormat::Raw), "1700000000 +0100");
+        assert_eq!(format(DateFormat::Unix), "1700000000");
+        assert_eq!(
+            format_date(-86400, Offset(-330), DateFormat::Iso),
+            "1969-12-30 18:30:00 -0530"
+        );
+        assert_eq!(Offset::parse("-0530"), Some(Offset(-330)));
+        assert_eq!(Offset(-330).to_string(), "-0530");
+        assert_eq!(Offset::parse("0530"), None);
+
+        let now = 1700000000;
+        assert_eq!(format_relative(now - 1, now), "1 second ago");
+        assert_eq!(format_relative(now - 7200, now), "2 hours ago");
+        assert_eq!(format_relative(now - 20 * 86400, now), "3 weeks ago");
+        assert_eq!(
+            format_relative(now - 400 * 86400, now),
+            "1 year, 1 month ago"
+        );
+        assert_eq!(format_relative(now - 3650 * 86400, now), "10 years ago");
+        assert_eq!(format_relative(now + 10, now), "in the future");
+    }
+
+    #[test]
+    fn parse() {
+        let date = |date| parse_date(date).unwrap();
+        let expected = (1700000000, Offset(60));
+        assert_eq!(date("1700000000 +0100"), expected);
+        assert_eq!(date("@1700000000 +0100"), expected);
+        assert_eq!(date("Tue, 14 Nov 2023 23:13:20 +0100"), expected);
+        assert_eq!(date("2023-11-14 23:13:20 +0100"), expected);
+        assert_eq!(date("2023-11-14T23:13:20+01:00"), expected);
+        assert_eq!(date("2023-11-14T22:13:20Z"), (1700000000, Offset(0)));
+        assert_eq!(date("Tue Nov 14 23:13:20 2023 +0100"), expected);
+        assert_eq!(date("@-100 -0800"), (-100, Offset(-480)));
+        assert_eq!(date("@5000000000 +0000").0, 5000000000);
+        assert!(parse_date("yesterday").is_err());
+
+        let now = 1700000000;
+        assert_eq!(parse_approxidate("@1600000000", now).unwrap(), 1600000000);
+        assert_eq!(parse_approxidate("2 days ago", now).unwrap(), now - 172800);
+        assert_eq!(parse_approxidate("1 hour", now).unwrap(), now - 3600);
+        assert!(parse_approxidate("2023-11-14", now).is_ok());
+        assert!(parse_approxidate("someday", now).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use std::{fmt, io, path::PathBuf};
+
+use crate::objects::id::{ObjectId, ObjectIdPrefix};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The errors of the library that callers may want to handle. The rest are
+/// kept as `Other`, with a message for the user. More variants may be added,
+/// so matching on them needs a wildcard arm.
+///
+/// The messages are the ones git shows (the command line prefixes them with
+/// `fatal: `).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("not a git repository (or any of the parent directories): .git")]
+    NotARepository,
+
+    /// No object has this name (full or abbreviated).
+    #[error("Not a valid object name {0}")]
+    NotFound(String),
+
+    /// More than one object starts with the prefix.
+    #[error("short object ID {prefix} is ambiguous")]
+    Ambiguous {
+        prefix: ObjectIdPrefix,
+        candidates: Vec<ObjectId>,
+    },
+
+    /// The object is stored, but it can't be read or parsed.
+    #[error("object {oid} is corrupt: {reason}")]
+    Corrupt { oid: ObjectId, reason: String },
+
+    /// The revision doesn't name an object (or its syntax is invalid).
+    #[error("ambiguous argument '{0}': unknown revision or path not in the working tree")]
+    UnknownRevision(String),
+
+    #[error("'{0}' is not a valid ref name")]
+    InvalidRef(String),
+
+    /// The ref can't be updated: its current value isn't the expected one,
+    /// it's locked, or it conflicts with an existing ref.
+    #[error("cannot lock ref '{name}': {reason}")]
+    RefLock { name: String, reason: String },
+
+    /// The lock file (`<path>.lock`) already exists.
+    #[error(
+        "Unable to create '{}': File exists.\n\n\
+        Another git process seems to be running in this repository. If it\n\
+        crashed, remove the file manually to continue.",
+        .0.display()
+    )]
+    Lock(PathBuf),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl Error {
+    pub(crate) fn corrupt(oid: &ObjectId, reason: impl fmt::Display) -> Self {
+        Error::Corrupt {
+            oid: *oid,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Keeps the errors of the library that passed through `anyhow` (so that
+/// they can still be matched on). I/O errors are only unwrapped if no context
+/// was added to them, which would be lost otherwise.
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Error>() {
+            Ok(err) => err,
+            Err(err) if err.chain().count() == 1 => match err.downcast::<io::Error>() {
+                Ok(err) => Error::Io(err),
+                Err(err) => Error::Other(err),
+            },
+            Err(err) => Error::Other(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, ErrorKind};
+
+    use anyhow::anyhow;
+
+    use super::Error;
+
+    #[test]
+    fn keeps_errors_passed_through_anyhow() {
+        let err: anyhow::Error = Error::NotFound("HEAD".to_string()).into();
+        assert!(matches!(Error::from(err), Error::NotFound(name) if name == "HEAD"));
+
+        let err = Error::from(anyhow!("bad config"));
+        assert!(matches!(&err, Error::Other(_)));
+        assert_eq!(err.to_string(), "bad config");
+
+        let io = || io::Error::new(ErrorKind::NotFound, "missing");
+        assert!(matches!(
+            Error::from(anyhow::Error::from(io())),
+            Error::Io(_)
+        ));
+        let err = Error::from(anyhow::Error::from(io()).context("reading the config"));
+        assert!(matches!(&err, Error::Other(_)));
+        assert_eq!(format!("{err:#}"), "reading the config: missing");
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    attributes::{Attributes, State},
+    config::Config,
+    context::Context,
+};
+
+/// What to do with the line endings of a file, when it's added to the
+/// repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Crlf {
+    /// Keep them as is.
+    Binary,
+    /// Convert CRLF to LF.
+    Text,
+    /// Convert CRLF to LF, unless the file looks binary.
+    Auto,
+}
+
+/// The filters applied to the contents of the files (driven by their
+/// attributes, and `core.autocrlf`) when they're added to the repository.
+pub(crate) struct Filters<'a> {
+    context: &'a Context,
+    attributes: Attributes,
+    config: Config,
+}
+
+impl<'a> Filters<'a> {
+    pub(crate) fn new(context: &'a Context) -> Result<Self> {
+        Ok(Self {
+            context,
+            attributes: Attributes::new(context)?,
+            config: Config::load(context)?,
+        })
+    }
+
+    /// Converts the contents of the file at the path (relative to the
+    /// repository root) to what's stored in the repository, like git does:
+    ///
+    /// 1. the `clean` command of its `filter` driver (`filter.<driver>.clean`)
+    ///    is run,
+    /// 2. CRLF line endings are converted to LF for text files (`text` and
+    ///    `eol` attributes, or `core.autocrlf`),
+    /// 3. `$Id: <anything>$` is replaced with `$Id$` with the `ident`
+    ///    attribute.
+    pub(crate) fn clean(&self, path: &str, contents: Vec<u8>) -> Result<Vec<u8>> {
+        let attributes = self.attributes.get(path)?;
+        let mut contents = contents;
+        if let Some(State::Value(driver)) = attributes.get("filter") {
+            contents = self.run_driver(driver, path, contents)?;
+        }
+        let convert = match self.crlf(&attributes)? {
+            Crlf::Binary => false,
+            Crlf::Text => true,
+            Crlf::Auto => !is_binary(&contents),
+        };
+        if convert {
+            contents = crlf_to_lf(&contents);
+        }
+        if attributes.get("ident") == Some(&State::Set) {
+            contents = collapse_ident(&contents);
+        }
+        Ok(contents)
+    }
+
+    /// The conversion of the line endings, decided by the `text` attribute
+    /// (or the legacy `crlf` one), `eol`, and then `core.autocrlf`.
+    fn crlf(&self, attributes: &HashMap<String, State>) -> Result<Crlf> {
+        let from_attribute = |name| match attributes.get(name) {
+            Some(State::Set) => Some(Crlf::Text),
+            Some(State::Unset) => Some(Crlf::Binary),
+            Some(State::Value(value)) if value == "input" => Some(Crlf::Text),
+            Some(State::Value(value)) if value == "auto" => Some(Crlf::Auto),
+            _ => None,
+        };
+        let crlf = from_attribute("text").or_else(|| from_attribute("crlf"));
+        let eol = matches!(attributes.get("eol"), Some(State::Value(eol)) if eol == "lf" || eol == "crlf");
+        Ok(match crlf {
+            Some(Crlf::Binary) => Crlf::Binary,
+            Some(crlf) => crlf,
+            None if eol => Crlf::Text,
+            None => match self.config.get("core.autocrlf") {
+                Some(value) if value.eq_ignore_ascii_case("input") => Crlf::Auto,
+                _ if self.config.get_bool("core.autocrlf")? == Some(true) => Crlf::Auto,
+                _ => Crlf::Binary,
+            },
+        })
+    }
+
+    /// Runs the `clean` command of the driver (with `%f` replaced by the
+    /// path), which gets the contents on its standard input. If the command
+    /// fails, the contents are kept as is, unless the driver is `required`.
+    fn run_driver(&self, driver: &str, path: &str, contents: Vec<u8>) -> Result<Vec<u8>> {
+        let required = self
+            .config
+            .get_bool(&format!("filter.{driver}.required"))?
+            .unwrap_or(false);
+        let Some(command) = self.config.get(&format!("filter.{driver}.clean")) else {
+            if required {
+                bail!("{path}: clean filter '{driver}' failed");
+            }
+            return Ok(contents);
+        };
+        let quoted = format!("'{}'", path.replace('\'', "'\\''"));
+        let command = command.replace("%f", &quoted);
+        match run_command(self.context, &command, &contents) {
+            Ok(output) => Ok(output),
+            Err(_) if required => bail!("{path}: clean filter '{driver}' failed"),
+            Err(err) => {
+                eprintln!("error: {err}");
+                Ok(contents)
+            }
+        }
+    }
+}
+
+/// Runs the shell command in the working tree, with the input on its standard
+/// input, and returns its standard output.
+fn run_command(context: &Context, command: &str, input: &[u8]) -> Result<Vec<u8>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(&context.repo_root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|_| anyhow!("cannot fork to run external filter '{command}'"))?;
+    let mut stdin = child.stdin.take().expect("The standard input is piped");
+    // The input is written from another thread, so that the command doesn't
+    // block on a full output pipe.
+    let output = thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(input));
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        bail!("external filter '{command}' failed");
+    }
+    Ok(output.stdout)
+}
+
+/// Whether the contents look binary, like git decides it: with NUL bytes,
+/// lone CRs, or more than 1/128 of non-printable characters.
+fn is_binary(contents: &[u8]) -> bool {
+    let (mut printable, mut non_printable) = (0, 0);
+    for (i, &c) in contents.iter().enumerate() {
+        match c {
+            b'\r' if contents.get(i + 1) != Some(&b'\n') => return true,
+            0 => return true,
+            b'\r' | b'\n' | b'\t' | b'\x08' | b'\x1b' | b'\x0c' => printable += 1,
+            // A trailing ^Z (from DOS) is ignored.
+            b'\x1a' if i == contents.len() - 1 => {}
+            c if c < 32 || c == 127 => non_printable += 1,
+            _ => printable += 1,
+        }
+    }
+    printable >> 7 < non_printable
+}
+
+/// Removes the CRs before LFs.
+fn crlf_to_lf(contents: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(contents.len());
+    for (i, &c) in contents.iter().enumerate() {
+        if c != b'\r' || contents.get(i + 1) != Some(&b'\n') {
+            converted.push(c);
+        }
+    }
+    converted
+}
+
+/// Replaces the expanded `$Id: <anything>$` keywords (on a single line) with
+/// `$Id$`.
+fn collapse_ident(contents: &[u8]) -> Vec<u8> {
+    let mut collapsed = Vec::with_capacity(contents.len());
+    let mut rest = contents;
+    while let Some(dollar) = rest.iter().position(|&c| c == b'$') {
+        collapsed.extend_from_slice(&rest[..=dollar]);
+        rest = &rest[dollar + 1..];
+        let Some(keyword) = rest.strip_prefix(b"Id:") else {
+            continue;
+        };
+        match keyword.iter().position(|&c| c == b'$' || c == b'\n') {
+            Some(end) if keyword[end] == b'$' => {
+                collapsed.extend_from_slice(b"Id$");
+                rest = &keyword[end + 1..];
+            }
+            _ => {}
+        }
+    }
+    collapsed.extend_from_slice(rest);
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{collapse_ident, is_binary, Filters};
+    use crate::context::tests::TestContext;
+
+    #[test]
+    fn clean() {
+        let context = TestContext::init();
+        let context = &context.context;
+        fs::write(
+            context.repo_root.join(".gitattributes"),
+            "*.txt text\n*.auto text=auto\n*.bin -text\n*.c ident\n*.up filter=upper\n",
+        )
+        .unwrap();
+        fs::write(
+            context.git_dir.join("config"),
+            "[filter \"upper\"]\n\tclean = tr a-z A-Z\n",
+        )
+        .unwrap();
+
+        let filters = Filters::new(context).unwrap();
+        let clean = |path, contents: &[u8]| filters.clean(path, contents.to_vec()).unwrap();
+        assert_eq!(clean("a.txt", b"a\r\nb\rc\r\n"), b"a\nb\rc\n");
+        assert_eq!(clean("a.auto", b"a\r\nb\r\n"), b"a\nb\n");
+        assert_eq!(clean("a.auto", b"a\r\nb\0\r\n"), b"a\r\nb\0\r\n");
+        assert_eq!(clean("a.bin", b"a\r\n"), b"a\r\n");
+        // `core.autocrlf` is false by default.
+        assert_eq!(clean("a.rs", b"a\r\n"), b"a\r\n");
+        assert_eq!(
+            clean("a.c", b"$Id: 1234 $\n$Id$ $Id:\n$"),
+            b"$Id$\n$Id$ $Id:\n$"
+        );
+        assert_eq!(clean("a.up", b"abc\n"), b"ABC\n");
+
+        assert!(is_binary(b"a\rb"));
+        assert!(!is_binary(b"a\r\nb\x1a"));
+        assert_eq!(collapse_ident(b"$$Id: x$"), b"$$Id$");
+    }
+}
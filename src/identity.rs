@@ -0,0 +1,101 @@
+use std::env;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    config::Config,
+    date::{now, parse_date, Offset},
+    objects::commit::Author,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Role {
+    Author,
+    Committer,
+}
+
+impl Role {
+    fn env_prefix(&self) -> &'static str {
+        match self {
+            Role::Author => "GIT_AUTHOR",
+            Role::Committer => "GIT_COMMITTER",
+        }
+    }
+}
+
+/// Identity (name, email and date) of the author or the committer of a new
+/// commit.
+///
+/// The name and email are taken from `GIT_AUTHOR_NAME`/`GIT_AUTHOR_EMAIL` (or
+/// `GIT_COMMITTER_*`), or else from `user.name`/`user.email` in the config. The
+/// date is taken from `GIT_AUTHOR_DATE` (or `GIT_COMMITTER_DATE`), or else is
+/// the current time.
+pub(crate) fn identity(config: &Config, role: Role) -> Result<Author> {
+    resolve_identity(config, role, &|name| env::var(name).ok())
+}
+
+fn resolve_identity(
+    config: &Config,
+    role: Role,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<Author> {
+    let prefix = role.env_prefix();
+    let name = env(&format!("{prefix}_NAME")).or(config.get("user.name").map(str::to_owned));
+    let email = env(&format!("{prefix}_EMAIL"))
+        .or(config.get("user.email").map(str::to_owned))
+        .or(env("EMAIL"));
+    let (Some(name), Some(email)) = (name, email) else {
+        bail!(
+            "{role:?} identity unknown\n\n\
+            Please set your identity with:\n\n  \
+            git config --global user.email \"you@example.com\"\n  \
+            git config --global user.name \"Your Name\""
+        );
+    };
+    if name.is_empty() {
+        bail!("empty ident name (for <{email}>) not allowed");
+    }
+
+    let (timestamp, offset) = match env(&format!("{prefix}_DATE")) {
+        Some(date) => parse_date(&date)?,
+        None => (now(), Offset::local(now())),
+    };
+    Ok(Author {
+        name,
+        email,
+        timestamp,
+        offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{resolve_identity, Role};
+    use crate::{config::Config, context::tests::IDENTITY};
+
+    #[test]
+    fn identity() {
+        let mut config = Config::default();
+        config.parse(IDENTITY).unwrap();
+        let env = HashMap::from([
+            ("GIT_COMMITTER_NAME", "C O Mitter"),
+            ("GIT_COMMITTER_DATE", "@1700000000 +0530"),
+        ]);
+        let env = |name: &str| env.get(name).map(|value| value.to_string());
+
+        let author = resolve_identity(&config, Role::Author, &env).unwrap();
+        assert_eq!(author.name, "A U Thor");
+        assert_eq!(author.email, "author@example.com");
+
+        let committer = resolve_identity(&config, Role::Committer, &env).unwrap();
+        assert_eq!(
+            committer.to_string(),
+            "C O Mitter <author@example.com> 1700000000 +0530"
+        );
+
+        let config = Config::default();
+        assert!(resolve_identity(&config, Role::Author, &env).is_err());
+    }
+}
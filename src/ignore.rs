@@ -0,0 +1,306 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::Result;
+
+use crate::{config::Config, context::Context, wildmatch::wildmatch};
+
+/// Decides which untracked files are ignored, with the patterns of:
+///
+/// 1. the `.gitignore` files in the working tree,
+/// 2. `.git/info/exclude`,
+/// 3. the file set by `core.excludesFile` (by default `~/.config/git/ignore`).
+///
+/// The patterns of a `.gitignore` file apply to the paths in its directory,
+/// and take precedence over the ones in the parent directories (and the
+/// sources after them). Within a file, the last matching pattern decides
+/// whether the path is ignored.
+pub(crate) struct Ignore {
+    repo_root: PathBuf,
+    /// Patterns of the `.gitignore` file in each directory ("" for the root),
+    /// loaded on demand.
+    patterns: RefCell<HashMap<String, Rc<Vec<Pattern>>>>,
+    /// Patterns of `info/exclude`, then of `core.excludesFile`.
+    excludes: Vec<Vec<Pattern>>,
+}
+
+/// A line of a `.gitignore` (or exclude) file.
+#[derive(Clone, Debug)]
+pub(crate) struct Pattern {
+    /// The file containing the pattern, as shown by `check-ignore -v` (e.g.
+    /// `src/.gitignore` or `.git/info/exclude`).
+    pub(crate) source: Rc<str>,
+    pub(crate) line_number: usize,
+    /// The line as written (e.g. `!/build/`).
+    pub(crate) line: String,
+    pattern: String,
+    /// Directory of the `.gitignore` file ("" for the root).
+    base: String,
+    /// `!pattern`: re-includes the paths excluded by a previous pattern.
+    pub(crate) negated: bool,
+    /// `pattern/`: only matches directories.
+    dir_only: bool,
+    /// Patterns with a `/` (other than at the end) are matched against the
+    /// path relative to the base, and the others against the file name.
+    anchored: bool,
+}
+
+impl Pattern {
+    pub(crate) fn parse(
+        line: &str,
+        base: &str,
+        source: &Rc<str>,
+        line_number: usize,
+    ) -> Option<Self> {
+        // Trailing spaces are ignored, unless they are escaped.
+        let mut line = line.trim_end_matches(['\r', '\n']);
+        while line.ends_with(' ') && !line.ends_with("\\ ") {
+            line = &line[..line.len() - 1];
+        }
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let full_line = line;
+        // `\!` and `\#` are matched literally by `wildmatch`.
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        if line.is_empty() {
+            return None;
+        }
+        let anchored = line.contains('/');
+        Some(Self {
+            source: source.clone(),
+            line_number,
+            line: full_line.to_string(),
+            pattern: line.strip_prefix('/').unwrap_or(line).to_string(),
+            base: base.to_string(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn parse_file(path: &Path, base: &str, source: &str) -> Vec<Self> {
+        let source: Rc<str> = source.into();
+        match fs::read_to_string(path) {
+            Ok(contents) => contents
+                .lines()
+                .enumerate()
+                .filter_map(|(i, line)| Self::parse(line, base, &source, i + 1))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub(crate) fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let relative = match self.base.as_str() {
+            "" => path,
+            base => match path.strip_prefix(base).and_then(|p| p.strip_prefix('/')) {
+                Some(relative) => relative,
+                None => return false,
+            },
+        };
+        if self.anchored {
+            wildmatch(&self.pattern, relative, true)
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            wildmatch(&self.pattern, name, true)
+        }
+    }
+}
+
+impl Ignore {
+    pub(crate) fn new(context: &Context) -> Result<Self> {
+        let info_exclude = context.git_dir.join("info").join("exclude");
+        let source = match info_exclude.strip_prefix(&context.repo_root) {
+            Ok(relative) => relative.to_string_lossy().into_owned(),
+            Err(_) => info_exclude.to_string_lossy().into_owned(),
+        };
+        let mut excludes = vec![Pattern::parse_file(&info_exclude, "", &source)];
+        if let Some(path) = user_file(&Config::load(context)?, "core.excludesFile", "ignore") {
+            let source = path.to_string_lossy().into_owned();
+            excludes.push(Pattern::parse_file(&path, "", &source));
+        }
+        Ok(Self {
+            repo_root: context.repo_root.clone(),
+            patterns: RefCell::default(),
+            excludes,
+        })
+    }
+
+    /// Whether the path (relative to the repository root) is ignored. The
+    /// parent directories aren't checked: a path in an ignored directory is
+    /// only ignored if it matches a pattern itself.
+    pub(crate) fn is_ignored(&self, path: &str, is_dir: bool) -> Result<bool> {
+        Ok(self
+            .find_match(path, is_dir)?
+            .is_some_and(|pattern| !pattern.negated))
+    }
+
+    /// Whether the path, or any of its parent directories, is ignored.
+    pub(crate) fn is_path_ignored(&self, path: &str, is_dir: bool) -> Result<bool> {
+        Ok(self
+            .find_path_match(path, is_dir)?
+            .is_some_and(|pattern| !pattern.negated))
+    }
+
+    /// The pattern deciding whether the path, or one of its parent directories,
+    /// is ignored: the files in an ignored directory can't be re-included.
+    pub(crate) fn find_path_match(&self, path: &str, is_dir: bool) -> Result<Option<Pattern>> {
+        let mut dirs: Vec<_> = path.match_indices('/').map(|(i, _)| &path[..i]).collect();
+        dirs.retain(|dir| !dir.is_empty());
+        for dir in dirs {
+            if let Some(pattern) = self.find_match(dir, true)? {
+                if !pattern.negated {
+                    return Ok(Some(pattern));
+                }
+            }
+        }
+        self.find_match(path, is_dir)
+    }
+
+    /// The last pattern matching the path (itself), with the highest
+    /// precedence.
+    pub(crate) fn find_match(&self, path: &str, is_dir: bool) -> Result<Option<Pattern>> {
+        let mut dir = path;
+        while !dir.is_empty() {
+            dir = dir.rsplit_once('/').map_or("", |(parent, _)| parent);
+            let patterns = self.load(dir)?;
+            if let Some(pattern) = patterns.iter().rev().find(|p| p.matches(path, is_dir)) {
+                return Ok(Some(pattern.clone()));
+            }
+        }
+        for patterns in self.excludes.iter() {
+            if let Some(pattern) = patterns.iter().rev().find(|p| p.matches(path, is_dir)) {
+                return Ok(Some(pattern.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The patterns of the `.gitignore` file in the directory.
+    fn load(&self, dir: &str) -> Result<Rc<Vec<Pattern>>> {
+        if let Some(patterns) = self.patterns.borrow().get(dir) {
+            return Ok(patterns.clone());
+        }
+        let path = self.repo_root.join(dir).join(".gitignore");
+        let source = match dir {
+            "" => ".gitignore".to_string(),
+            dir => format!("{dir}/.gitignore"),
+        };
+        let patterns = Rc::new(Pattern::parse_file(&path, dir, &source));
+        self.patterns
+            .borrow_mut()
+            .insert(dir.to_string(), patterns.clone());
+        Ok(patterns)
+    }
+}
+
+/// The path set by the config variable (e.g. `core.excludesFile`, with `~/`
+/// expanded), or else `$XDG_CONFIG_HOME/git/<name>` (or
+/// `~/.config/git/<name>`).
+pub(crate) fn user_file(config: &Config, key: &str, name: &str) -> Option<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    if let Some(path) = config.get(key) {
+        return match (path.strip_prefix("~/"), home) {
+            (Some(path), Some(home)) => Some(home.join(path)),
+            _ => Some(PathBuf::from(path)),
+        };
+    }
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("git").join(name)),
+        _ => home.map(|home| home.join(".config").join("git").join(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::Ignore;
+    use crate::context::tests::TestContext;
+
+    #[test]
+    fn gitignore() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::create_dir_all(root.join("src/generated")).unwrap();
+        fs::write(
+            root.join(".gitignore"),
+            "# comment\n*.log\n!keep.log\n/target\nbuild/\ndocs/*.html\n\\#*\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/.gitignore"), "!debug.log\ngenerated\n").unwrap();
+
+        let ignore = Ignore::new(context).unwrap();
+        let ignored = |path, is_dir| ignore.is_ignored(path, is_dir).unwrap();
+        assert!(ignored("error.log", false));
+        assert!(ignored("a/b/error.log", false));
+        assert!(!ignored("keep.log", false));
+        assert!(ignored("target", true));
+        assert!(!ignored("src/target", true));
+        assert!(ignored("build", true));
+        assert!(!ignored("build", false));
+        assert!(ignored("docs/index.html", false));
+        assert!(!ignored("docs/api/index.html", false));
+        assert!(!ignored("src/debug.log", false));
+        assert!(ignored("src/error.log", false));
+        assert!(ignored("#backup", false));
+        assert!(ignored("src/generated", true));
+        assert!(!ignored("src/generated/lib.rs", false));
+        assert!(ignore
+            .is_path_ignored("src/generated/lib.rs", false)
+            .unwrap());
+    }
+
+    #[test]
+    fn excludes() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let root = &context.repo_root;
+        fs::create_dir_all(context.git_dir.join("info")).unwrap();
+        fs::write(context.git_dir.join("info/exclude"), "*.tmp\nsecret\n").unwrap();
+        fs::write(root.join(".gitignore"), "!keep.tmp\nbuild/**/out\n").unwrap();
+        fs::write(
+            context.git_dir.join("config"),
+            format!(
+                "[core]\n\texcludesFile = {}\n",
+                root.join("global").display()
+            ),
+        )
+        .unwrap();
+        fs::write(root.join("global"), "*.swp\n").unwrap();
+
+        let ignore = Ignore::new(context).unwrap();
+        let ignored = |path| ignore.is_ignored(path, false).unwrap();
+        assert!(ignored("a.tmp"));
+        assert!(!ignored("keep.tmp"));
+        assert!(ignored("src/secret"));
+        assert!(ignored("a.swp"));
+        assert!(ignored("build/out"));
+        assert!(ignored("build/a/b/out"));
+
+        let pattern = ignore.find_match("keep.tmp", false).unwrap().unwrap();
+        assert_eq!(
+            (&*pattern.source, pattern.line_number, pattern.line.as_str()),
+            (".gitignore", 1, "!keep.tmp")
+        );
+        let pattern = ignore.find_match("x.tmp", false).unwrap().unwrap();
+        assert_eq!(&*pattern.source, ".git/info/exclude");
+    }
+}
@@ -0,0 +1,240 @@
+use std::str;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::objects::id::ObjectId;
+
+/// The extensions following the entries of the index: `<signature><size><data>`.
+///
+/// Extensions with an uppercase signature are optional, and the unknown ones are
+/// dropped (like git). The others are required to understand the index.
+#[derive(Default)]
+pub(crate) struct Extensions {
+    /// The trees of the indexed directories (`TREE`).
+    pub(crate) cache_tree: Option<CacheTree>,
+    /// The conflicts that have been resolved (`REUC`), to recreate them.
+    pub(crate) resolve_undo: Option<Vec<ResolveUndo>>,
+    /// The untracked cache (`UNTR`), which is kept as is.
+    pub(crate) untracked_cache: Option<Vec<u8>>,
+}
+
+/// The tree object of a directory in the index, which avoids recomputing the
+/// trees of the unchanged directories:
+///
+/// ```txt
+/// <path>\0<entry count> <subtree count>\n<hash><subtrees>
+/// ```
+#[derive(Debug)]
+pub(crate) struct CacheTree {
+    /// Name of the directory (empty for the root).
+    pub(crate) name: Vec<u8>,
+    /// Number of index entries in the directory, or -1 if it's been
+    /// invalidated (in which case there is no hash).
+    pub(crate) entry_count: i32,
+    pub(crate) hash: Option<ObjectId>,
+    pub(crate) subtrees: Vec<CacheTree>,
+}
+
+/// The stages (base, ours, theirs) of a path before its conflict was resolved.
+/// A mode of 0 means that the stage was missing.
+///
+/// ```txt
+/// <path>\0<octal mode>\0<octal mode>\0<octal mode>\0<hash of each stage>
+/// ```
+#[derive(Debug)]
+pub(crate) struct ResolveUndo {
+    pub(crate) path: Vec<u8>,
+    pub(crate) modes: [u32; 3],
+    pub(crate) hashes: [Option<ObjectId>; 3],
+}
+
+impl Extensions {
+    /// Parses the extensions, where the hashes are `hash_len` bytes long.
+    pub(crate) fn parse(mut body: &[u8], hash_len: usize) -> Result<Self> {
+        let mut extensions = Self::default();
+        while !body.is_empty() {
+            if body.len() < 8 {
+                bail!("Corrupt index (truncated extension)");
+            }
+            let signature = &body[..4];
+            let size = u32::from_be_bytes(body[4..8].try_into().unwrap()) as usize;
+            let data = body
+                .get(8..8 + size)
+                .ok_or(anyhow!("Corrupt index (truncated extension)"))?;
+            match signature {
+                b"TREE" => {
+                    let mut i = 0;
+                    extensions.cache_tree = Some(CacheTree::parse(data, &mut i, hash_len)?);
+                }
+                b"REUC" => extensions.resolve_undo = Some(ResolveUndo::parse_all(data, hash_len)?),
+                b"UNTR" => extensions.untracked_cache = Some(data.to_vec()),
+                [b'A'..=b'Z', ..] => {}
+                _ => bail!(
+                    "Unsupported index extension: {}",
+                    String::from_utf8_lossy(signature)
+                ),
+            }
+            body = &body[8 + size..];
+        }
+        Ok(extensions)
+    }
+
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut extension = |signature: &[u8], data: Vec<u8>| {
+            body.extend(signature);
+            body.extend((data.len() as u32).to_be_bytes());
+            body.extend(data);
+        };
+        if let Some(tree) = &self.cache_tree {
+            let mut data = Vec::new();
+            tree.serialize(&mut data);
+            extension(b"TREE", data);
+        }
+        if let Some(entries) = &self.resolve_undo {
+            extension(
+                b"REUC",
+                entries.iter().flat_map(|e| e.serialize()).collect(),
+            );
+        }
+        if let Some(data) = &self.untracked_cache {
+            extension(b"UNTR", data.clone());
+        }
+        body
+    }
+}
+
+impl CacheTree {
+    /// Invalidates the trees of the directories containing the path (relative
+    /// to this tree).
+    pub(crate) fn invalidate(&mut self, path: &[u8]) {
+        self.entry_count = -1;
+        self.hash = None;
+        if let Some(slash) = path.iter().position(|c| *c == b'/') {
+            let (dir, rest) = (&path[..slash], &path[slash + 1..]);
+            if let Some(subtree) = self.subtrees.iter_mut().find(|tree| tree.name == dir) {
+                subtree.invalidate(rest);
+            }
+        }
+    }
+
+    fn parse(body: &[u8], i: &mut usize, hash_len: usize) -> Result<Self> {
+        let corrupt = || anyhow!("Corrupt index (invalid cache tree)");
+        let name = read_until(body, i, 0).ok_or_else(corrupt)?.to_vec();
+        let entry_count = read_until(body, i, b' ')
+            .and_then(parse_number)
+            .ok_or_else(corrupt)?;
+        let subtree_count: usize = read_until(body, i, b'\n')
+            .and_then(parse_number)
+            .ok_or_else(corrupt)?;
+        let mut hash = None;
+        if entry_count >= 0 {
+            let bytes = body.get(*i..*i + hash_len).ok_or_else(corrupt)?;
+            hash = Some(ObjectId::from_bytes(bytes)?);
+            *i += hash_len;
+        }
+        let subtrees = (0..subtree_count)
+            .map(|_| Self::parse(body, i, hash_len))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            name,
+            entry_count,
+            hash,
+            subtrees,
+        })
+    }
+
+    fn serialize(&self, body: &mut Vec<u8>) {
+        body.extend(&self.name);
+        body.push(0);
+        body.extend(format!("{} {}\n", self.entry_count, self.subtrees.len()).as_bytes());
+        if let Some(hash) = &self.hash {
+            body.extend(hash.as_bytes());
+        }
+        for subtree in self.subtrees.iter() {
+            subtree.serialize(body);
+        }
+    }
+}
+
+impl ResolveUndo {
+    fn parse_all(body: &[u8], hash_len: usize) -> Result<Vec<Self>> {
+        let corrupt = || anyhow!("Corrupt index (invalid resolve-undo)");
+        let mut entries = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let path = read_until(body, &mut i, 0).ok_or_else(corrupt)?.to_vec();
+            let mut modes = [0; 3];
+            for mode in modes.iter_mut() {
+                *mode = read_until(body, &mut i, 0)
+                    .and_then(|mode| u32::from_str_radix(str::from_utf8(mode).ok()?, 8).ok())
+                    .ok_or_else(corrupt)?;
+            }
+            let mut hashes = [None, None, None];
+            for (hash, mode) in hashes.iter_mut().zip(modes) {
+                if mode != 0 {
+                    let bytes = body.get(i..i + hash_len).ok_or_else(corrupt)?;
+                    *hash = Some(ObjectId::from_bytes(bytes)?);
+                    i += hash_len;
+                }
+            }
+            entries.push(Self {
+                path,
+                modes,
+                hashes,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut body = self.path.clone();
+        body.push(0);
+        for mode in self.modes {
+            body.extend(format!("{mode:o}\0").as_bytes());
+        }
+        for hash in self.hashes.iter().flatten() {
+            body.extend(hash.as_bytes());
+        }
+        body
+    }
+}
+
+/// Reads the bytes up to the delimiter, and moves past the delimiter.
+fn read_until<'a>(body: &'a [u8], i: &mut usize, delimiter: u8) -> Option<&'a [u8]> {
+    let len = body.get(*i..)?.iter().position(|c| *c == delimiter)?;
+    let value = &body[*i..*i + len];
+    *i += len + 1;
+    Some(value)
+}
+
+fn parse_number<T: str::FromStr>(value: &[u8]) -> Option<T> {
+    str::from_utf8(value).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Extensions;
+
+    #[test]
+    fn resolve_undo_round_trip() {
+        let hash = [0xab; 20];
+        let mut data = b"conflict.txt\x00100644\x00100644\x000\x00".to_vec();
+        data.extend(hash);
+        data.extend(hash);
+        let mut body = b"REUC".to_vec();
+        body.extend((data.len() as u32).to_be_bytes());
+        body.extend(&data);
+        // Unknown optional extensions are dropped.
+        body.extend(b"ZZZZ\0\0\0\x01x");
+
+        let extensions = Extensions::parse(&body, 20).unwrap();
+        let entries = extensions.resolve_undo.as_ref().unwrap();
+        assert_eq!(entries[0].path, b"conflict.txt");
+        assert_eq!(entries[0].modes, [0o100644, 0o100644, 0]);
+        assert!(entries[0].hashes[2].is_none());
+        assert_eq!(extensions.serialize(), body[..body.len() - 9]);
+
+        assert!(Extensions::parse(b"link\0\0\0\0", 20).is_err());
+    }
+}
@@ -0,0 +1,636 @@
+mod extensions;
+
+use std::{
+    borrow::Cow,
+    fs::{self, Metadata},
+    os::unix::fs::MetadataExt,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    context::Context,
+    lockfile::LockFile,
+    objects::{hash::HashAlgorithm, id::ObjectId},
+    worktree::file_mode,
+};
+pub(crate) use extensions::Extensions;
+
+const SIGNATURE: &[u8] = b"DIRC";
+
+/// Length of the stat data and the mode of an entry, which are followed by the
+/// hash and the flags.
+const STAT_LEN: usize = 40;
+
+/// Flag for the entries that are assumed to be unchanged in the working tree.
+const ASSUME_VALID_FLAG: u16 = 0x8000;
+
+/// Flag for the entries followed by extended flags (version 3 and above).
+const EXTENDED_FLAG: u16 = 0x4000;
+
+/// Bits of the flags storing the merge stage.
+const STAGE_MASK: u16 = 0x3000;
+
+/// Bits of the flags storing the length of the path (or `0xfff`, if it's
+/// longer).
+const NAME_MASK: u16 = 0x0fff;
+
+/// Length of the smallest entry: the stat data, a SHA-1 hash, the flags and the
+/// null byte ending the path (version 4 entries aren't padded).
+const MIN_ENTRY_LEN: usize = STAT_LEN + 20 + 2 + 1;
+
+/// Longest encoding of a varint: 7 bits per byte, for 64 bits.
+const MAX_VARINT_LEN: usize = 10;
+
+/// Extended flag for the entries added with `add --intent-to-add`.
+const INTENT_TO_ADD_FLAG: u16 = 0x2000;
+
+/// The staging area (`.git/index`): the list of files in the next commit.
+///
+/// Layout:
+/// ```txt
+/// DIRC<version><entry count><entries><extensions><checksum>
+/// ```
+///
+/// The hashes of the entries and the checksum use the hash function of the
+/// repository.
+pub struct Index {
+    /// Format version: 2, 3 (extended flags) or 4 (compressed paths).
+    pub version: u32,
+    /// Entries, sorted by path (and then by stage).
+    pub entries: Vec<IndexEntry>,
+    pub(crate) extensions: Extensions,
+    pub hash_algorithm: HashAlgorithm,
+}
+
+/// The stat data of a file in the working tree, when it was last hashed. This
+/// allows detecting changes to the file without hashing it again.
+///
+/// Values are truncated to 32 bits, like git.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct StatData {
+    pub(crate) ctime_seconds: u32,
+    pub(crate) ctime_nanoseconds: u32,
+    pub(crate) mtime_seconds: u32,
+    pub(crate) mtime_nanoseconds: u32,
+    pub(crate) dev: u32,
+    pub(crate) ino: u32,
+    pub(crate) uid: u32,
+    pub(crate) gid: u32,
+    pub(crate) size: u32,
+}
+
+impl From<&Metadata> for StatData {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            ctime_seconds: metadata.ctime() as u32,
+            ctime_nanoseconds: metadata.ctime_nsec() as u32,
+            mtime_seconds: metadata.mtime() as u32,
+            mtime_nanoseconds: metadata.mtime_nsec() as u32,
+            dev: metadata.dev() as u32,
+            ino: metadata.ino() as u32,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.size() as u32,
+        }
+    }
+}
+
+/// A file in the index.
+#[derive(Clone, Debug, Default)]
+pub struct IndexEntry {
+    pub(crate) stat: StatData,
+    pub mode: u32,
+    pub hash: ObjectId,
+    /// `<1-bit assume-valid><1-bit extended><2-bit stage><12-bit name length>`
+    pub(crate) flags: u16,
+    /// Only present (in version 3 and above) if the extended flag is set.
+    pub(crate) extended_flags: u16,
+    /// The path, as bytes: git doesn't require paths to be valid UTF-8.
+    pub path: Vec<u8>,
+}
+
+impl IndexEntry {
+    /// The path, with the invalid UTF-8 bytes replaced (e.g. to show it, or
+    /// to match it against a pathspec).
+    pub fn path_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.path)
+    }
+
+    /// Merge stage: 0 for regular entries, and 1-3 (base, ours, theirs) for
+    /// conflicts.
+    pub fn stage(&self) -> u16 {
+        (self.flags & STAGE_MASK) >> 12
+    }
+
+    /// Whether the path has been recorded (with `add -N`), but its contents
+    /// haven't been staged yet.
+    pub fn is_intent_to_add(&self) -> bool {
+        self.extended_flags & INTENT_TO_ADD_FLAG != 0
+    }
+
+    /// Whether the file in the working tree is unchanged, judging from its
+    /// stat data and mode. Files modified after (or in the same second as) the
+    /// index was written may have changed without their stat data changing,
+    /// so they are never assumed to be unchanged: see [`Index::mtime`].
+    pub(crate) fn is_unchanged(
+        &self,
+        metadata: &Metadata,
+        index_mtime: Option<(u32, u32)>,
+    ) -> bool {
+        let racy = index_mtime
+            .is_none_or(|mtime| (self.stat.mtime_seconds, self.stat.mtime_nanoseconds) >= mtime);
+        self.stat == StatData::from(metadata) && self.mode == file_mode(metadata) && !racy
+    }
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Self {
+            version: 2,
+            entries: Vec::new(),
+            extensions: Extensions::default(),
+            hash_algorithm: HashAlgorithm::default(),
+        }
+    }
+}
+
+impl Index {
+    /// Reads the index of the repository. A missing index is the same as an
+    /// empty one.
+    pub(crate) fn read(context: &Context) -> Result<Self> {
+        let path = context.git_dir.join("index");
+        if !path.exists() {
+            return Ok(Self {
+                hash_algorithm: context.hash_algorithm(),
+                ..Self::default()
+            });
+        }
+        Self::parse(&fs::read(path)?, context.hash_algorithm())
+    }
+
+    /// The modification time of the index file (seconds and nanoseconds), or
+    /// `None` if there is no index yet.
+    pub(crate) fn mtime(context: &Context) -> Option<(u32, u32)> {
+        let metadata = fs::metadata(context.git_dir.join("index")).ok()?;
+        let stat = StatData::from(&metadata);
+        Some((stat.mtime_seconds, stat.mtime_nanoseconds))
+    }
+
+    /// Replaces the index of the repository, through `index.lock`.
+    pub(crate) fn write(&self, context: &Context) -> Result<()> {
+        let mut lock = LockFile::acquire(&context.git_dir.join("index"))?;
+        lock.write(&self.serialize()?)?;
+        Ok(lock.commit()?)
+    }
+
+    /// The entry of the path at stage 0.
+    pub fn entry(&self, path: &[u8]) -> Option<&IndexEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.path == path && entry.stage() == 0)
+    }
+
+    /// Adds (or replaces) the entry. The other stages of the path are removed,
+    /// along with the entries conflicting with it: files in place of its
+    /// parent directories, or files in the directory if the path was one.
+    pub(crate) fn add(&mut self, entry: IndexEntry) {
+        let is_in = |path: &[u8], dir: &[u8]| {
+            path.strip_prefix(dir)
+                .is_some_and(|rest| rest.starts_with(b"/"))
+        };
+        let conflicts: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|e| {
+                e.path == entry.path || is_in(&e.path, &entry.path) || is_in(&entry.path, &e.path)
+            })
+            .map(|e| e.path.clone())
+            .collect();
+        for path in conflicts {
+            self.remove(&path);
+        }
+        self.invalidate(&entry.path);
+        let key = (&entry.path, entry.stage());
+        let position = self.entries.partition_point(|e| (&e.path, e.stage()) < key);
+        self.entries.insert(position, entry);
+    }
+
+    /// Removes all the stages of the path, and returns whether it was present.
+    pub(crate) fn remove(&mut self, path: &[u8]) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.path != path);
+        if self.entries.len() == len {
+            return false;
+        }
+        self.invalidate(path);
+        true
+    }
+
+    /// Invalidates the cached trees of the directories containing the path, and
+    /// drops the untracked cache, which isn't updated.
+    fn invalidate(&mut self, path: &[u8]) {
+        if let Some(tree) = &mut self.extensions.cache_tree {
+            tree.invalidate(path);
+        }
+        self.extensions.untracked_cache = None;
+    }
+
+    pub(crate) fn parse(body: &[u8], hash_algorithm: HashAlgorithm) -> Result<Self> {
+        let hash_len = hash_algorithm.hash_len();
+        if body.len() < 12 + hash_len || !body.starts_with(SIGNATURE) {
+            bail!("Corrupt index (invalid signature)");
+        }
+        let (body, checksum) = body.split_at(body.len() - hash_len);
+        // The checksum is all zeros if `index.skipHash` is set.
+        if checksum.iter().any(|b| *b != 0) && hash_algorithm.digest(body) != *checksum {
+            bail!("Corrupt index (checksum mismatch)");
+        }
+        let version = read_u32(body, 4);
+        if !(2..=4).contains(&version) {
+            bail!("Unsupported index version: {version}");
+        }
+        let count = read_u32(body, 8);
+
+        // The count isn't trusted to allocate more than the body can hold.
+        let capacity = (count as usize).min(body.len() / MIN_ENTRY_LEN);
+        let mut entries: Vec<IndexEntry> = Vec::with_capacity(capacity);
+        let mut i = 12;
+        for _ in 0..count {
+            let previous_path = entries.last().map_or(&[][..], |entry| &entry.path);
+            let (entry, len) = parse_entry(&body[i..], version, previous_path, hash_len)?;
+            entries.push(entry);
+            i += len;
+        }
+        let extensions = Extensions::parse(&body[i..], hash_len)?;
+        Ok(Self {
+            version,
+            entries,
+            extensions,
+            hash_algorithm,
+        })
+    }
+
+    /// Serializes the index. Version 2 is upgraded to 3 if any entry has
+    /// extended flags.
+    pub(crate) fn serialize(&self) -> Result<Vec<u8>> {
+        let mut version = self.version;
+        if version == 2 && self.entries.iter().any(|entry| entry.extended_flags != 0) {
+            version = 3;
+        }
+
+        let mut body = SIGNATURE.to_vec();
+        body.extend(version.to_be_bytes());
+        body.extend((self.entries.len() as u32).to_be_bytes());
+        let mut previous_path = &[][..];
+        for entry in self.entries.iter() {
+            body.extend(serialize_entry(entry, version, previous_path)?);
+            previous_path = &entry.path;
+        }
+        body.extend(self.extensions.serialize());
+        let checksum = self.hash_algorithm.digest(&body);
+        body.extend(checksum);
+        Ok(body)
+    }
+}
+
+/// Parses the entry at the start of `body`, and returns it with its length
+/// (including the padding).
+///
+/// In version 4, the path is stored as the number of bytes to remove from the
+/// end of the previous path, followed by the bytes to append to it, and
+/// entries aren't padded.
+fn parse_entry(
+    body: &[u8],
+    version: u32,
+    previous_path: &[u8],
+    hash_len: usize,
+) -> Result<(IndexEntry, usize)> {
+    let corrupt = || anyhow!("Corrupt index (truncated entry)");
+    let flags_start = STAT_LEN + hash_len;
+    if body.len() < flags_start + 2 {
+        return Err(corrupt());
+    }
+    let flags = u16::from_be_bytes([body[flags_start], body[flags_start + 1]]);
+    let mut i = flags_start + 2;
+    let mut extended_flags = 0;
+    if flags & EXTENDED_FLAG != 0 {
+        if version < 3 {
+            bail!("Corrupt index (extended flags in version {version})");
+        }
+        extended_flags = u16::from_be_bytes(body.get(i..i + 2).ok_or_else(corrupt)?.try_into()?);
+        i += 2;
+    }
+
+    let mut path = Vec::new();
+    if version == 4 {
+        let removed = read_varint(body, &mut i).ok_or_else(corrupt)?;
+        let prefix = previous_path
+            .len()
+            .checked_sub(removed)
+            .map(|kept| &previous_path[..kept])
+            .ok_or(anyhow!("Corrupt index (invalid path prefix)"))?;
+        path.extend(prefix);
+    }
+    let name_len = body
+        .get(i..)
+        .ok_or_else(corrupt)?
+        .iter()
+        .position(|c| *c == 0)
+        .ok_or_else(corrupt)?;
+    path.extend(&body[i..i + name_len]);
+    let len = if version == 4 {
+        i + name_len + 1
+    } else {
+        // Entries are padded with 1-8 null bytes, to a multiple of 8 bytes.
+        (i + name_len + 8) / 8 * 8
+    };
+    if body.len() < len {
+        return Err(corrupt());
+    }
+
+    let entry = IndexEntry {
+        stat: StatData {
+            ctime_seconds: read_u32(body, 0),
+            ctime_nanoseconds: read_u32(body, 4),
+            mtime_seconds: read_u32(body, 8),
+            mtime_nanoseconds: read_u32(body, 12),
+            dev: read_u32(body, 16),
+            ino: read_u32(body, 20),
+            uid: read_u32(body, 28),
+            gid: read_u32(body, 32),
+            size: read_u32(body, 36),
+        },
+        mode: read_u32(body, 24),
+        hash: ObjectId::from_bytes(&body[STAT_LEN..flags_start])?,
+        flags,
+        extended_flags,
+        path,
+    };
+    Ok((entry, len))
+}
+
+fn serialize_entry(entry: &IndexEntry, version: u32, previous_path: &[u8]) -> Result<Vec<u8>> {
+    let stat = &entry.stat;
+    let mut body =
+        Vec::with_capacity(STAT_LEN + entry.hash.as_bytes().len() + entry.path.len() + 10);
+    for value in [
+        stat.ctime_seconds,
+        stat.ctime_nanoseconds,
+        stat.mtime_seconds,
+        stat.mtime_nanoseconds,
+        stat.dev,
+        stat.ino,
+        entry.mode,
+        stat.uid,
+        stat.gid,
+        stat.size,
+    ] {
+        body.extend(value.to_be_bytes());
+    }
+    body.extend(entry.hash.as_bytes());
+
+    // The extended bit and the name length are derived from the entry.
+    let mut flags = entry.flags & (ASSUME_VALID_FLAG | STAGE_MASK);
+    flags |= entry.path.len().min(NAME_MASK as usize) as u16;
+    if entry.extended_flags != 0 {
+        flags |= EXTENDED_FLAG;
+    }
+    body.extend(flags.to_be_bytes());
+    if entry.extended_flags != 0 {
+        body.extend(entry.extended_flags.to_be_bytes());
+    }
+
+    if version == 4 {
+        let common = previous_path
+            .iter()
+            .zip(entry.path.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        write_varint(&mut body, previous_path.len() - common);
+        body.extend(&entry.path[common..]);
+        body.push(0);
+    } else {
+        body.extend(&entry.path);
+        body.resize((body.len() + 8) / 8 * 8, 0);
+    }
+    Ok(body)
+}
+
+fn read_u32(body: &[u8], start: usize) -> u32 {
+    u32::from_be_bytes(body[start..start + 4].try_into().unwrap())
+}
+
+/// Reads a big-endian base-128 varint, where each continuation also adds 1
+/// (the same encoding as the offsets of `OFS_DELTA` pack entries). Returns
+/// `None` if it's truncated or overflows.
+fn read_varint(body: &[u8], i: &mut usize) -> Option<usize> {
+    let mut byte = *body.get(*i)?;
+    *i += 1;
+    let mut value = (byte & 0x7f) as usize;
+    let mut len = 1;
+    while byte & 0x80 != 0 {
+        if len == MAX_VARINT_LEN {
+            return None;
+        }
+        byte = *body.get(*i)?;
+        *i += 1;
+        len += 1;
+        value = value.checked_add(1)?.checked_mul(1 << 7)? | (byte & 0x7f) as usize;
+    }
+    Some(value)
+}
+
+fn write_varint(body: &mut Vec<u8>, mut value: usize) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        value -= 1;
+        bytes.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    bytes.reverse();
+    body.extend(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use sha1::{Digest, Sha1};
+
+    use super::{read_varint, write_varint, Index, IndexEntry, StatData, MAX_VARINT_LEN};
+    use crate::{
+        context::tests::TestContext,
+        objects::hash::{hex_to_bytes, HashAlgorithm},
+    };
+
+    const HASH: &str = "ce013625030ba8dba906f756967f9e9ca394464a";
+
+    fn entry(mode: u32, path: &[u8], extended_flags: Option<u16>) -> Vec<u8> {
+        let mut entry = Vec::new();
+        for value in 1..=6u32 {
+            entry.extend(value.to_be_bytes());
+        }
+        entry.extend(mode.to_be_bytes());
+        entry.extend([0; 8]);
+        entry.extend(42u32.to_be_bytes());
+        entry.extend(hex_to_bytes(HASH).unwrap());
+        let extended = extended_flags.map_or(0, |_| 0x4000);
+        entry.extend((extended | path.len() as u16).to_be_bytes());
+        if let Some(flags) = extended_flags {
+            entry.extend(flags.to_be_bytes());
+        }
+        entry.extend(path);
+        entry.resize((entry.len() + 8) / 8 * 8, 0);
+        entry
+    }
+
+    fn with_checksum(mut body: Vec<u8>) -> Vec<u8> {
+        let checksum = Sha1::digest(&body);
+        body.extend(checksum);
+        body
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut body = b"DIRC".to_vec();
+        body.extend(3u32.to_be_bytes());
+        body.extend(3u32.to_be_bytes());
+        body.extend(entry(0o100644, b"a.txt", None));
+        body.extend(entry(0o100644, b"new.txt", Some(0x2000)));
+        body.extend(entry(0o100755, b"src/run.sh", None));
+        // A cache tree with an invalidated root, and a valid `src` subtree.
+        let tree = [
+            &b"\0-1 1\nsrc\x001 0\n"[..],
+            &hex_to_bytes("b67207be6104838c2e10caa80cfdb2c330fe3f45").unwrap(),
+        ]
+        .concat();
+        body.extend(b"TREE");
+        body.extend((tree.len() as u32).to_be_bytes());
+        body.extend(tree);
+        with_checksum(body)
+    }
+
+    #[test]
+    fn parse() {
+        let index = Index::parse(&sample(), HashAlgorithm::Sha1).unwrap();
+        let paths: Vec<_> = index.entries.iter().map(|e| e.path_str()).collect();
+        assert_eq!(paths, ["a.txt", "new.txt", "src/run.sh"]);
+        assert_eq!(index.entries[2].mode, 0o100755);
+        assert_eq!(index.entries[2].hash, HASH);
+        assert!(index.entries[1].is_intent_to_add());
+        assert!(!index.entries[0].is_intent_to_add());
+        assert_eq!(
+            index.entries[0].stat,
+            StatData {
+                ctime_seconds: 1,
+                ctime_nanoseconds: 2,
+                mtime_seconds: 3,
+                mtime_nanoseconds: 4,
+                dev: 5,
+                ino: 6,
+                uid: 0,
+                gid: 0,
+                size: 42,
+            }
+        );
+        let tree = index.extensions.cache_tree.as_ref().unwrap();
+        assert_eq!(tree.entry_count, -1);
+        assert_eq!(tree.subtrees[0].name, b"src");
+
+        // Round trip, byte for byte.
+        assert_eq!(index.serialize().unwrap(), sample());
+
+        let mut corrupt = sample();
+        corrupt[20] ^= 1;
+        assert!(Index::parse(&corrupt, HashAlgorithm::Sha1).is_err());
+    }
+
+    #[test]
+    fn non_utf8_paths() {
+        let mut body = b"DIRC".to_vec();
+        body.extend(2u32.to_be_bytes());
+        body.extend(1u32.to_be_bytes());
+        body.extend(entry(0o100644, b"caf\xe9.txt", None));
+        let body = with_checksum(body);
+        let index = Index::parse(&body, HashAlgorithm::Sha1).unwrap();
+        assert_eq!(index.entries[0].path, b"caf\xe9.txt");
+        assert_eq!(index.entries[0].path_str(), "caf\u{fffd}.txt");
+        assert_eq!(index.serialize().unwrap(), body);
+
+        // The count isn't trusted: the entries are missing.
+        let mut body = b"DIRC".to_vec();
+        body.extend(2u32.to_be_bytes());
+        body.extend(u32::MAX.to_be_bytes());
+        assert!(Index::parse(&with_checksum(body), HashAlgorithm::Sha1).is_err());
+    }
+
+    #[test]
+    fn path_compression() {
+        let mut index = Index::parse(&sample(), HashAlgorithm::Sha1).unwrap();
+        index.entries.push(IndexEntry {
+            mode: 0o100644,
+            hash: HASH.parse().unwrap(),
+            path: b"src/run.sh.bak".to_vec(),
+            ..Default::default()
+        });
+        let uncompressed = index.serialize().unwrap();
+        index.version = 4;
+        let body = index.serialize().unwrap();
+        assert!(body.len() < uncompressed.len());
+        let parsed = Index::parse(&body, HashAlgorithm::Sha1).unwrap();
+        assert_eq!(parsed.version, 4);
+        let paths: Vec<_> = parsed.entries.iter().map(|e| e.path_str()).collect();
+        assert_eq!(paths, ["a.txt", "new.txt", "src/run.sh", "src/run.sh.bak"]);
+        assert_eq!(parsed.serialize().unwrap(), body);
+    }
+
+    #[test]
+    fn varint() {
+        let mut body = Vec::new();
+        write_varint(&mut body, usize::MAX);
+        assert!(body.len() <= MAX_VARINT_LEN);
+        assert_eq!(read_varint(&body, &mut 0), Some(usize::MAX));
+
+        // Reaches `usize::MAX` before the last byte.
+        let mut body = vec![0x80];
+        body.extend([0xfe; 8]);
+        body.extend([0xff, 0x00]);
+        assert_eq!(read_varint(&body, &mut 0), None);
+        assert_eq!(read_varint(&[0x80; 20], &mut 0), None);
+        assert_eq!(read_varint(&[0x80], &mut 0), None);
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut index = Index::parse(&sample(), HashAlgorithm::Sha1).unwrap();
+        index.extensions.untracked_cache = Some(b"untracked".to_vec());
+        assert!(!index.remove(b"missing.txt"));
+        assert!(index.extensions.untracked_cache.is_some());
+
+        assert!(index.remove(b"a.txt"));
+        assert!(index.extensions.untracked_cache.is_none());
+        let tree = index.extensions.cache_tree.as_ref().unwrap();
+        assert_eq!(tree.subtrees[0].entry_count, 1);
+
+        // A file in place of the `src` directory.
+        index.extensions.untracked_cache = Some(b"untracked".to_vec());
+        let mut entry = index.entries[0].clone();
+        entry.path = b"src".to_vec();
+        index.add(entry);
+        let paths: Vec<_> = index.entries.iter().map(|e| e.path_str()).collect();
+        assert_eq!(paths, ["new.txt", "src"]);
+        assert!(index.extensions.untracked_cache.is_none());
+        let tree = index.extensions.cache_tree.as_ref().unwrap();
+        assert_eq!(tree.subtrees[0].entry_count, -1);
+    }
+
+    #[test]
+    fn write() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let index = Index::parse(&sample(), HashAlgorithm::Sha1).unwrap();
+        index.write(context).unwrap();
+        assert!(!context.git_dir.join("index.lock").exists());
+        let index = Index::read(context).unwrap();
+        assert_eq!(index.entries.len(), 3);
+    }
+}
@@ -1,35 +1,114 @@
+mod attributes;
+mod config;
 mod context;
+mod date;
+mod error;
+mod filters;
+mod identity;
+mod ignore;
+mod index;
+mod lockfile;
+mod pathspec;
+mod refs;
+mod repository;
+mod revision;
+mod revwalk;
 mod utils;
+mod wildmatch;
+mod worktree;
 
-use std::env;
+use std::{
+    env,
+    process::{self, ExitCode},
+};
 
-use anyhow::{anyhow, Ok, Result};
 use cli::Cli;
-use context::Context;
-use utils::find_repo_root;
 
 pub(crate) mod cli;
 pub(crate) mod commands;
 pub(crate) mod objects;
 
-pub fn run() -> Result<()> {
-    let cli = cli::parse();
-    let context = match cli {
-        Cli::Init(_) => Context::new(env::current_dir()?),
-        _ => {
-            let cwd = env::current_dir()?;
-            let root = find_repo_root(cwd).ok_or(anyhow!("not a git repository"))?;
-            Context::new(root)
+pub use date::Offset;
+pub use error::{Error, Result};
+pub use index::{Index, IndexEntry};
+pub use objects::{
+    blob::BlobContents,
+    commit::{Author, CommitContents, Header},
+    hash::HashAlgorithm,
+    id::{ObjectId, ObjectIdPrefix},
+    kind::ObjectKind,
+    mode::FileMode,
+    object::{Contents, Object},
+    tag::TagContents,
+    tree::{TreeContents, TreeRowItem},
+};
+pub use repository::Repository;
+pub use revwalk::RevWalk;
+
+/// Runs the command line. Errors are shown like git does (`fatal:
+/// <message>`), with the status 128.
+pub fn run() -> ExitCode {
+    match execute() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("fatal: {err:#}");
+            ExitCode::from(128)
         }
+    }
+}
+
+fn execute() -> anyhow::Result<()> {
+    let cli = cli::parse();
+    let repository = match cli {
+        Cli::Init(options) => return commands::init(options),
+        _ => Repository::discover(env::current_dir()?)?,
     };
+    let context = repository.context();
     match cli {
-        Cli::Init(options) => commands::init(options)?,
-        Cli::CatFile(options) => commands::cat_file(&context, options.into())?,
+        Cli::Init(_) => unreachable!("Initialized above"),
+        Cli::CatFile(options) => commands::cat_file(context, options.into())?,
         Cli::HashObject(options) => {
-            let hash = commands::hash_object(&context, options)?;
+            for hash in commands::hash_object(context, options)? {
+                println!("{hash}");
+            }
+        }
+        Cli::LsTree(options) => print!("{}", commands::ls_tree(context, options)?),
+        Cli::PackObjects(options) => commands::pack_objects(context, options)?,
+        Cli::WriteTree(options) => {
+            let hash = commands::write_tree(context, options)?;
             println!("{hash}");
         }
-        Cli::LsTree(options) => commands::ls_tree(context, options)?,
+        Cli::CommitTree(options) => {
+            let hash = commands::commit_tree(context, options)?;
+            println!("{hash}");
+        }
+        Cli::UpdateRef(options) => commands::update_ref(context, options)?,
+        Cli::Add(options) => commands::add(context, options)?,
+        Cli::Rm(options) => commands::rm(context, options)?,
+        Cli::Commit(options) => commands::commit(context, options)?,
+        Cli::Status(options) => print!("{}", commands::status(context, options)?),
+        Cli::CheckIgnore(options) => {
+            if !commands::check_ignore(context, options)? {
+                process::exit(1);
+            }
+        }
+        Cli::Clean(options) => commands::clean(context, options)?,
+        Cli::Log(options) => commands::log(context, options)?,
+        Cli::RevList(options) => commands::rev_list(context, options)?,
+        Cli::Tag(options) => {
+            if !commands::tag(context, options)? {
+                process::exit(1);
+            }
+        }
+        Cli::LsFiles(options) => print!("{}", commands::ls_files(context, options)?),
+        Cli::RevParse(options) => {
+            let quiet = options.quiet;
+            let lines = commands::rev_parse(context, options);
+            if quiet && lines.is_err() {
+                process::exit(1);
+            }
+            lines?.iter().for_each(|line| println!("{line}"));
+        }
     };
     Ok(())
 }
@@ -0,0 +1,74 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+
+use crate::error::{Error, Result};
+
+/// An exclusive lock on a file, held by creating `<path>.lock`.
+///
+/// The new contents are written to the lock file, which then replaces the file
+/// when the lock is committed. The lock file is removed if the lock is dropped
+/// without being committed, leaving the file untouched.
+pub(crate) struct LockFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<File>,
+}
+
+impl LockFile {
+    pub(crate) fn acquire(path: &Path) -> Result<Self> {
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        let file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                return Err(Error::Lock(lock_path))
+            }
+            Err(err) => {
+                return Err(anyhow!("Unable to create '{}': {err}", lock_path.display()).into())
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            lock_path,
+            file: Some(file),
+        })
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        if let Some(file) = &mut self.file {
+            file.write_all(bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the file with the contents written to the lock file.
+    pub(crate) fn commit(mut self) -> Result<()> {
+        if let Some(file) = &self.file {
+            file.sync_all()?;
+        }
+        self.file = None;
+        if let Err(err) = fs::rename(&self.lock_path, &self.path) {
+            let _ = fs::remove_file(&self.lock_path);
+            return Err(anyhow!("Unable to write '{}': {err}", self.path.display()).into());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
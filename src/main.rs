@@ -1,6 +1,5 @@
-use anyhow::Result;
+use std::process::ExitCode;
 
-
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     git::run()
 }
@@ -1,15 +1,10 @@
 use std::str::{self, Utf8Error};
 
-pub struct BlobContents(pub(crate) Vec<u8>);
+pub struct BlobContents(pub Vec<u8>);
 
 impl BlobContents {
     pub fn new(contents: &[u8]) -> Self {
-        let mut body = Vec::new();
-        body.extend(b"blob ");
-        body.extend(contents.len().to_string().as_bytes());
-        body.push(0);
-        body.extend(contents);
-        Self(body)
+        Self(contents.to_vec())
     }
 
     pub fn try_string(&self) -> std::result::Result<&str, Utf8Error> {
@@ -1,121 +1,276 @@
-use anyhow::{anyhow, bail, Result};
 use std::{
+    borrow::Cow,
     fmt::{self, Display},
     str,
 };
 
+use anyhow::{anyhow, bail, Result};
+use encoding_rs::Encoding;
+
+use super::id::ObjectId;
+use crate::date::Offset;
+
+/// A commit. The typed fields are parsed from the headers, and the commit is
+/// serialized from the headers and the raw message, so that a parsed commit is
+/// written back byte for byte.
+///
+/// Commits are only created by `parse` and `CommitBuilder`, which keep the
+/// typed fields and the headers in sync, so the fields are read-only.
 pub struct CommitContents {
-    pub raw: String,
-    pub tree: String,
-    pub parents: Vec<String>,
-    pub author: Author,
-    pub committer: Option<Author>,
-    pub gpgsig: Option<String>,
-    pub message: String,
+    tree: ObjectId,
+    parents: Vec<ObjectId>,
+    author: Author,
+    committer: Option<Author>,
+    /// All the headers (including `tree`, `parent`, `author` and `committer`,
+    /// and e.g. `encoding`, `mergetag` or `gpgsig`), in order.
+    headers: Vec<Header>,
+    /// The message, in the commit's `encoding` (UTF-8 by default).
+    raw_message: Vec<u8>,
 }
 
 impl CommitContents {
     pub fn parse(body: &[u8]) -> Result<Self> {
-        let raw = str::from_utf8(body)?;
-        let (metadata, message) = raw
-            .split_once("\n\n")
-            .ok_or(anyhow!("Missing commit message"))?;
-        let mut lines = metadata.lines();
-
-        let tree = lines.next().ok_or(anyhow!("Expected tree hash"))?;
-        if !tree.starts_with("tree ") {
-            bail!("Expected tree hash");
-        }
-        let tree = tree[5..].to_owned();
+        let (headers, message) = parse_headers(body)?;
+        let mut values = headers.iter().peekable();
+        let id = |header: &Header| {
+            header
+                .text()
+                .parse::<ObjectId>()
+                .map_err(|_| anyhow!("Invalid commit ({})", header.name))
+        };
 
+        let tree = match values.next() {
+            Some(header) if header.name == "tree" => id(header)?,
+            _ => bail!("Expected tree hash"),
+        };
         // This will be blank for the initial commits.
         let mut parents = Vec::new();
-        let mut line = lines.next().ok_or(anyhow!("Expected parent or author"))?;
-        while line.starts_with("parent ") {
-            parents.push(line[7..].to_owned());
-            line = lines.next().expect("Expected parent or author");
-        }
-
-        if !line.starts_with("author ") {
-            bail!("Expected author");
+        while let Some(header) = values.next_if(|header| header.name == "parent") {
+            parents.push(id(header)?);
         }
-        let author = Author::parse(&line[7..])?;
-
-        let committer = match lines
-            .next()
-            .filter(|line| line.starts_with("committer "))
-            .map(|line| Author::parse(&line[10..]))
-        {
-            Some(Ok(committer)) => Some(committer),
-            Some(Err(_)) => bail!("Invalid commit (committer)"),
-            None => None,
-        };
-
-        let gpgsig = match lines.next() {
-            Some(line) => {
-                if !line.starts_with("gpgsig") || !line.contains("--BEGIN PGP SIGNATURE--") {
-                    bail!("Invalid commit (gpgsig)");
-                }
-                let mut gpgsig = line[7..].to_string();
-                let mut ends = false;
-                let err = lines
-                    .map(|line| {
-                        if !line.starts_with(' ') {
-                            return Some("Unexpected lines");
-                        }
-                        gpgsig.push('\n');
-                        gpgsig.push_str(line);
-                        let last_line = line.contains("--END PGP SIGNATURE--");
-                        ends = ends || last_line;
-                        None
-                    })
-                    .find_map(|e| e);
-                if !ends || err.is_some() {
-                    bail!("Invalid commit (gpgsig)");
-                }
-                Some(gpgsig)
-            }
-            None => None,
+        // The raw headers are kept, so a malformed identity doesn't make the
+        // commit unreadable.
+        let author = match values.next() {
+            Some(header) if header.name == "author" => Author::parse_lenient(&header.text()),
+            _ => bail!("Expected author"),
         };
+        let committer = values
+            .next_if(|header| header.name == "committer")
+            .map(|header| Author::parse_lenient(&header.text()));
 
         Ok(Self {
-            raw: raw.to_owned(),
             tree,
             parents,
             author,
             committer,
-            gpgsig,
-            message: message.to_owned(),
+            headers,
+            raw_message: message.to_vec(),
         })
     }
+
+    /// Serializes the commit, with one header per line (and the continuation
+    /// lines starting with a space), followed by a blank line and the message.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for header in self.headers.iter() {
+            header.write(&mut body);
+        }
+        body.push(b'\n');
+        body.extend_from_slice(&self.raw_message);
+        body
+    }
+
+    pub fn tree(&self) -> &ObjectId {
+        &self.tree
+    }
+
+    /// The parents (none for a root commit, several for a merge).
+    pub fn parents(&self) -> &[ObjectId] {
+        &self.parents
+    }
+
+    pub fn author(&self) -> &Author {
+        &self.author
+    }
+
+    /// The committer, or the author if the commit has no `committer` header.
+    pub fn committer(&self) -> &Author {
+        self.committer.as_ref().unwrap_or(&self.author)
+    }
+
+    pub fn headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    /// The message, in the commit's `encoding` (see `message`).
+    pub fn raw_message(&self) -> &[u8] {
+        &self.raw_message
+    }
+
+    /// The value of the first header with the name (e.g. `gpgsig`).
+    pub fn header(&self, name: &str) -> Option<&Header> {
+        self.headers.iter().find(|header| header.name == name)
+    }
+
+    /// The message, decoded from the commit's `encoding`. Invalid bytes (or
+    /// unknown encodings) are decoded as in UTF-8, with replacement characters.
+    pub fn message(&self) -> Cow<'_, str> {
+        let encoding = self
+            .header("encoding")
+            .and_then(|header| Encoding::for_label(header.value.trim_ascii()));
+        match encoding {
+            Some(encoding) => encoding.decode_without_bom_handling(&self.raw_message).0,
+            None => String::from_utf8_lossy(&self.raw_message),
+        }
+    }
 }
 
-impl Display for CommitContents {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_fmt(format_args!("tree {}\n", self.tree))?;
-        for parent in self.parents.iter() {
-            f.write_fmt(format_args!("parent {}\n", parent))?;
+/// A header of a commit (or a tag), e.g. `parent <hash>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub name: String,
+    /// The value, with its continuation lines joined by newlines (without
+    /// their leading space).
+    pub value: Vec<u8>,
+}
+
+impl Header {
+    pub fn new(name: &str, value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.into(),
+        }
+    }
+
+    /// The value, with the invalid UTF-8 bytes replaced.
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.value)
+    }
+
+    pub(crate) fn write(&self, body: &mut Vec<u8>) {
+        body.extend_from_slice(self.name.as_bytes());
+        body.push(b' ');
+        for (i, line) in self.value.split(|&c| c == b'\n').enumerate() {
+            if i > 0 {
+                body.extend_from_slice(b"\n ");
+            }
+            body.extend_from_slice(line);
         }
-        f.write_fmt(format_args!("author {}\n", self.author))?;
-        if let Some(committer) = &self.committer {
-            f.write_fmt(format_args!("committer {}\n", committer))?;
+        body.push(b'\n');
+    }
+}
+
+/// Splits the body of a commit (or a tag) into its headers and its message
+/// (after the first blank line).
+pub(crate) fn parse_headers(body: &[u8]) -> Result<(Vec<Header>, &[u8])> {
+    let mut headers: Vec<Header> = Vec::new();
+    let mut rest = body;
+    loop {
+        let end = rest
+            .iter()
+            .position(|&c| c == b'\n')
+            .ok_or(anyhow!("Missing commit message"))?;
+        let line = &rest[..end];
+        rest = &rest[end + 1..];
+        if line.is_empty() {
+            return Ok((headers, rest));
         }
-        if let Some(gpgsig) = &self.gpgsig {
-            f.write_fmt(format_args!("gpgsig {}\n", gpgsig))?;
+        if let Some(continuation) = line.strip_prefix(b" ") {
+            let header = headers
+                .last_mut()
+                .ok_or(anyhow!("Invalid header (continuation line)"))?;
+            header.value.push(b'\n');
+            header.value.extend_from_slice(continuation);
+            continue;
         }
-        f.write_fmt(format_args!("\n{}", self.message))
+        let space = line
+            .iter()
+            .position(|&c| c == b' ')
+            .ok_or(anyhow!("Invalid header (missing value)"))?;
+        let name = str::from_utf8(&line[..space]).map_err(|_| anyhow!("Invalid header name"))?;
+        headers.push(Header::new(name, &line[space + 1..]));
     }
 }
 
+/// Creates a new commit, e.g.
+///
+/// ```ignore
+/// let commit = CommitBuilder::new(tree)
+///     .parent(parent)
+///     .author(author)
+///     .message("Commit message\n".to_string())
+///     .build()?;
+/// ```
+pub struct CommitBuilder {
+    tree: ObjectId,
+    parents: Vec<ObjectId>,
+    author: Option<Author>,
+    committer: Option<Author>,
+    message: String,
+}
+
+impl CommitBuilder {
+    pub fn new(tree: ObjectId) -> Self {
+        Self {
+            tree,
+            parents: Vec::new(),
+            author: None,
+            committer: None,
+            message: String::new(),
+        }
+    }
+
+    pub fn parent(mut self, parent: ObjectId) -> Self {
+        self.parents.push(parent);
+        self
+    }
+
+    pub fn author(mut self, author: Author) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// The committer is the same as the author, unless it's set.
+    pub fn committer(mut self, committer: Author) -> Self {
+        self.committer = Some(committer);
+        self
+    }
+
+    pub fn message(mut self, message: String) -> Self {
+        self.message = message;
+        self
+    }
+
+    pub fn build(self) -> Result<CommitContents> {
+        let author = self.author.ok_or(anyhow!("Missing commit author"))?;
+        let committer = self.committer.unwrap_or_else(|| author.clone());
+        let mut headers = vec![Header::new("tree", self.tree.to_string())];
+        for parent in self.parents.iter() {
+            headers.push(Header::new("parent", parent.to_string()));
+        }
+        headers.push(Header::new("author", author.to_string()));
+        headers.push(Header::new("committer", committer.to_string()));
+        Ok(CommitContents {
+            tree: self.tree,
+            parents: self.parents,
+            author,
+            committer: Some(committer),
+            headers,
+            raw_message: self.message.into_bytes(),
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct Author {
     pub name: String,
     pub email: String,
-    pub timestamp: u32,
-    pub timezone: String,
+    pub timestamp: i64,
+    pub offset: Offset,
 }
 
 impl Author {
-    fn parse(line: &str) -> Result<Self> {
+    pub(crate) fn parse(line: &str) -> Result<Self> {
         let (name, remaining) = line
             .split_once(" <")
             .ok_or(anyhow!("Invalid author format"))?;
@@ -126,27 +281,193 @@ impl Author {
             .split_once(' ')
             .ok_or(anyhow!("Invalid author format"))?;
         let timestamp = timestamp
-            .parse::<u32>()
+            .parse::<i64>()
             .map_err(|_| anyhow!("Invalid author format (timestamp)"))?;
-        let parsed_timezone: i32 = timezone
-            .parse()
-            .map_err(|_| anyhow!("Invalid author format (timezone)"))?;
-        if !(-2400..2400).contains(&parsed_timezone) {
-            bail!("Invalid author format (timezone)");
-        }
+        let offset = Offset::parse(timezone).ok_or(anyhow!("Invalid author format (timezone)"))?;
         Ok(Self {
             name: name.to_string(),
             email: email.to_string(),
             timestamp,
-            timezone: timezone.to_string(),
+            offset,
         })
     }
+
+    /// Parses the identity of an existing object as git does: the name and
+    /// the email are empty if they can't be found, and an invalid date falls
+    /// back to the epoch (`0 +0000`).
+    pub(crate) fn parse_lenient(line: &str) -> Self {
+        if let Ok(author) = Self::parse(line) {
+            return author;
+        }
+        let (name, email, date) = line
+            .split_once('<')
+            .and_then(|(name, rest)| {
+                let (email, date) = rest.split_once('>')?;
+                Some((name.trim(), email, date))
+            })
+            .unwrap_or_default();
+        let mut date = date.split_whitespace();
+        let (timestamp, offset) = match date.next().map(str::parse::<i64>) {
+            Some(Ok(timestamp)) => (timestamp, date.next().and_then(Offset::parse)),
+            _ => (0, None),
+        };
+        Self {
+            name: name.to_string(),
+            email: email.to_string(),
+            timestamp,
+            offset: offset.unwrap_or(Offset(0)),
+        }
+    }
 }
+
 impl Display for Author {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_fmt(format_args!(
             "{} <{}> {} {}",
-            self.name, self.email, self.timestamp, self.timezone
+            self.name, self.email, self.timestamp, self.offset
         ))
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Author, CommitBuilder, CommitContents};
+    use crate::{
+        date::Offset,
+        objects::{hash::HashAlgorithm, kind::ObjectKind, object::Object},
+    };
+
+    /// The author of the commits (and tags) of the tests, in UTC.
+    pub(crate) fn author(timestamp: i64) -> Author {
+        Author {
+            name: "A U Thor".to_string(),
+            email: "author@example.com".to_string(),
+            timestamp,
+            offset: Offset(0),
+        }
+    }
+
+    #[test]
+    fn serialize() {
+        let body = "tree b67207be6104838c2e10caa80cfdb2c330fe3f45\n\
+            author A U Thor <author@example.com> 1700000000 +0530\n\
+            committer A U Thor <author@example.com> 1700000100 -0800\n\
+            \n\
+            Subject line\n\
+            \n\
+            Body paragraph.\n";
+        let commit = CommitContents::parse(body.as_bytes()).unwrap();
+        assert_eq!(commit.serialize(), body.as_bytes());
+
+        let object =
+            Object::from_raw(ObjectKind::Commit, body.as_bytes(), HashAlgorithm::Sha1).unwrap();
+        assert_eq!(
+            object.compute_hash(HashAlgorithm::Sha1),
+            "8eabb745da068292912426b350eb9e7b1f396c55"
+        );
+    }
+
+    #[test]
+    fn serialize_signed() {
+        let body = "tree b67207be6104838c2e10caa80cfdb2c330fe3f45\n\
+            parent 8eabb745da068292912426b350eb9e7b1f396c55\n\
+            author A U Thor <author@example.com> 1700000000 +0530\n\
+            committer A U Thor <author@example.com> 1700000100 -0800\n\
+            gpgsig -----BEGIN PGP SIGNATURE-----\n \n iQEzBAABCAAdFiEE\n -----END PGP SIGNATURE-----\n\
+            \n\
+            Signed\n";
+        let commit = CommitContents::parse(body.as_bytes()).unwrap();
+        assert_eq!(commit.parents().len(), 1);
+        assert_eq!(
+            commit.header("gpgsig").unwrap().text(),
+            "-----BEGIN PGP SIGNATURE-----\n\niQEzBAABCAAdFiEE\n-----END PGP SIGNATURE-----"
+        );
+        assert_eq!(commit.serialize(), body.as_bytes());
+    }
+
+    #[test]
+    fn serialize_unknown_headers() {
+        let mut body = b"tree b67207be6104838c2e10caa80cfdb2c330fe3f45\n\
+            author A U Thor <author@example.com> 1700000000 +0530\n\
+            committer C O Mitter <committer@example.com> 1700000100 -0800\n\
+            encoding ISO-8859-1\n\
+            mergetag object 8eabb745da068292912426b350eb9e7b1f396c55\n type commit\n \n tagged\n\
+            x-custom  two  spaces \n\
+            gpgsig-sha256 -----BEGIN SSH SIGNATURE-----\n U1NIU0lH\n -----END SSH SIGNATURE-----\n\
+            \n\
+            Caf"
+        .to_vec();
+        body.extend_from_slice(b"\xe9 cr\xe8me\n\n\xff\n");
+        let commit = CommitContents::parse(&body).unwrap();
+        assert_eq!(commit.committer().name, "C O Mitter");
+        let names: Vec<_> = commit.headers.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "tree",
+                "author",
+                "committer",
+                "encoding",
+                "mergetag",
+                "x-custom",
+                "gpgsig-sha256"
+            ]
+        );
+        assert_eq!(
+            commit.header("mergetag").unwrap().text(),
+            "object 8eabb745da068292912426b350eb9e7b1f396c55\ntype commit\n\ntagged"
+        );
+        assert_eq!(commit.header("x-custom").unwrap().text(), " two  spaces ");
+        assert_eq!(commit.message(), "Caf\u{e9} cr\u{e8}me\n\n\u{ff}\n");
+        assert_eq!(commit.serialize(), body);
+
+        assert!(CommitContents::parse(b"tree abc\nauthor A <a> 1 +0000\n").is_err());
+        assert!(CommitContents::parse(b" tree abc\n\nmessage").is_err());
+        assert!(CommitContents::parse(b"tree abc\nauthor A <a> 1 +0000\n\nmessage").is_err());
+    }
+
+    #[test]
+    fn malformed_identities() {
+        let body = "tree b67207be6104838c2e10caa80cfdb2c330fe3f45\n\
+            author A U Thor <author@example.com> yesterday\n\
+            committer C O Mitter\n\
+            \n\
+            Message\n";
+        let commit = CommitContents::parse(body.as_bytes()).unwrap();
+        let author = commit.author();
+        assert_eq!(
+            (author.name.as_str(), author.email.as_str()),
+            ("A U Thor", "author@example.com")
+        );
+        assert_eq!((author.timestamp, author.offset), (0, Offset(0)));
+        assert_eq!(commit.committer().name, "");
+        assert_eq!(commit.serialize(), body.as_bytes());
+
+        let author = Author::parse_lenient("A U Thor <author@example.com> 1700000000 +05");
+        assert_eq!((author.timestamp, author.offset), (1700000000, Offset(0)));
+    }
+
+    #[test]
+    fn build() {
+        let author = Author {
+            offset: Offset(330),
+            ..author(1700000000)
+        };
+        let commit =
+            CommitBuilder::new("b67207be6104838c2e10caa80cfdb2c330fe3f45".parse().unwrap())
+                .parent("8eabb745da068292912426b350eb9e7b1f396c55".parse().unwrap())
+                .author(author)
+                .message("Message\n".to_string())
+                .build()
+                .unwrap();
+        assert_eq!(
+            String::from_utf8(commit.serialize()).unwrap(),
+            "tree b67207be6104838c2e10caa80cfdb2c330fe3f45\n\
+            parent 8eabb745da068292912426b350eb9e7b1f396c55\n\
+            author A U Thor <author@example.com> 1700000000 +0530\n\
+            committer A U Thor <author@example.com> 1700000000 +0530\n\
+            \n\
+            Message\n"
+        );
+    }
+}
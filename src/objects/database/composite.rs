@@ -0,0 +1,162 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+
+use super::{LooseDatabase, ObjectDatabase, PackedDatabase};
+use crate::{
+    error::Result,
+    objects::{
+        hash::HashAlgorithm,
+        id::{ObjectId, ObjectIdPrefix},
+        kind::ObjectKind,
+        object::Object,
+    },
+};
+
+/// Alternates of alternates are followed only up to this depth (same as git).
+const MAX_ALTERNATES_DEPTH: usize = 5;
+
+/// Looks up objects in each of the databases in order, and writes them to the
+/// first one.
+pub(crate) struct CompositeDatabase {
+    databases: Vec<Box<dyn ObjectDatabase>>,
+    algorithm: HashAlgorithm,
+}
+
+impl CompositeDatabase {
+    pub(crate) fn new(databases: Vec<Box<dyn ObjectDatabase>>, algorithm: HashAlgorithm) -> Self {
+        Self {
+            databases,
+            algorithm,
+        }
+    }
+
+    /// The loose objects and packs of the objects directory, followed by those
+    /// of the alternate object directories listed in `info/alternates`.
+    pub(crate) fn open(objects_dir: &Path, algorithm: HashAlgorithm) -> Self {
+        let mut databases = Vec::new();
+        add_objects_dir(&mut databases, objects_dir.to_path_buf(), algorithm, 0);
+        Self::new(databases, algorithm)
+    }
+}
+
+fn add_objects_dir(
+    databases: &mut Vec<Box<dyn ObjectDatabase>>,
+    dir: PathBuf,
+    algorithm: HashAlgorithm,
+    depth: usize,
+) {
+    databases.push(Box::new(LooseDatabase::new(dir.clone(), algorithm)));
+    databases.push(Box::new(PackedDatabase::new(dir.join("pack"), algorithm)));
+    if depth >= MAX_ALTERNATES_DEPTH {
+        return;
+    }
+    // Missing (or unreadable) alternates are skipped, like git does.
+    let alternates = fs::read_to_string(dir.join("info").join("alternates")).unwrap_or_default();
+    for line in alternates.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Relative paths are relative to the objects directory.
+        let alternate = dir.join(line);
+        if alternate.is_dir() {
+            add_objects_dir(databases, alternate, algorithm, depth + 1);
+        }
+    }
+}
+
+impl ObjectDatabase for CompositeDatabase {
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read_raw(&self, id: &ObjectId) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        for database in self.databases.iter() {
+            if let Some(object) = database.read_raw(id)? {
+                return Ok(Some(object));
+            }
+        }
+        Ok(None)
+    }
+
+    fn write_raw(&self, kind: ObjectKind, body: &[u8]) -> Result<ObjectId> {
+        // Objects already present in any of the databases aren't duplicated.
+        let id = Object::hash_raw(self.algorithm, kind, body);
+        if self.exists(&id)? {
+            return Ok(id);
+        }
+        match self.databases.first() {
+            Some(database) => database.write_raw(kind, body),
+            None => Err(anyhow!("No object database to write to").into()),
+        }
+    }
+
+    fn exists(&self, id: &ObjectId) -> Result<bool> {
+        for database in self.databases.iter() {
+            if database.exists(id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn find_prefix(&self, prefix: &ObjectIdPrefix) -> Result<Vec<ObjectId>> {
+        let mut hashes = Vec::new();
+        for database in self.databases.iter() {
+            hashes.extend(database.find_prefix(prefix)?);
+        }
+        // The same object can be both loose and packed.
+        hashes.sort();
+        hashes.dedup();
+        Ok(hashes)
+    }
+
+    fn hashes(&self) -> Result<Vec<ObjectId>> {
+        let mut hashes = Vec::new();
+        for database in self.databases.iter() {
+            hashes.extend(database.hashes()?);
+        }
+        hashes.sort();
+        hashes.dedup();
+        Ok(hashes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::CompositeDatabase;
+    use crate::objects::{database::ObjectDatabase, hash::HashAlgorithm, object::Object};
+
+    #[test]
+    fn alternates() {
+        let temp_dir = TempDir::new().unwrap();
+        let shared = temp_dir.path().join("shared");
+        let objects = temp_dir.path().join("objects");
+        fs::create_dir_all(objects.join("info")).unwrap();
+        fs::write(objects.join("info").join("alternates"), "../shared\n").unwrap();
+        fs::create_dir_all(&shared).unwrap();
+
+        let hash = CompositeDatabase::open(&shared, HashAlgorithm::Sha1)
+            .write(&Object::new_blob(b"shared\n"))
+            .unwrap();
+
+        let database = CompositeDatabase::open(&objects, HashAlgorithm::Sha1);
+        assert!(database.exists(&hash).unwrap());
+        assert_eq!(database.read(&hash).unwrap().size, 7);
+
+        // New objects are written to the repository's own objects directory.
+        let hash = database
+            .write(&Object::new_blob(b"own\n"))
+            .unwrap()
+            .to_string();
+        assert!(objects.join(&hash[..2]).join(&hash[2..]).is_file());
+    }
+}
@@ -0,0 +1,87 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::anyhow;
+
+use super::ObjectDatabase;
+use crate::{
+    error::Result,
+    objects::{
+        hash::HashAlgorithm,
+        id::{ObjectId, ObjectIdPrefix},
+        kind::ObjectKind,
+        object::Object,
+        ObjectFile,
+    },
+};
+
+/// Objects stored in individual (zlib compressed) files in the objects
+/// directory.
+pub(crate) struct LooseDatabase {
+    dir: PathBuf,
+    algorithm: HashAlgorithm,
+}
+
+impl LooseDatabase {
+    pub(crate) fn new(dir: PathBuf, algorithm: HashAlgorithm) -> Self {
+        Self { dir, algorithm }
+    }
+
+    /// Names of the objects in the `ab/` directory that start with `prefix`.
+    /// Other files (e.g. temporary ones) are ignored.
+    fn scan(&self, dir_name: &str, prefix: &str) -> Result<Vec<ObjectId>> {
+        let dir = self.dir.join(dir_name);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let files = fs::read_dir(&dir).map_err(|_| anyhow!("Error reading {}", dir.display()))?;
+        let mut hashes = Vec::new();
+        for file in files.flatten() {
+            if let Some(file_name) = file.file_name().to_str() {
+                if !file_name.starts_with(prefix) {
+                    continue;
+                }
+                if let Ok(id) = format!("{dir_name}{file_name}").parse() {
+                    hashes.push(id);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+}
+
+impl ObjectDatabase for LooseDatabase {
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read_raw(&self, id: &ObjectId) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        let file = ObjectFile::new(&self.dir, id);
+        if !file.exists() {
+            return Ok(None);
+        }
+        file.read_raw().map(Some)
+    }
+
+    fn write_raw(&self, kind: ObjectKind, body: &[u8]) -> Result<ObjectId> {
+        let id = Object::hash_raw(self.algorithm, kind, body);
+        ObjectFile::new(&self.dir, &id).save(kind, body)?;
+        Ok(id)
+    }
+
+    fn exists(&self, id: &ObjectId) -> Result<bool> {
+        Ok(ObjectFile::new(&self.dir, id).exists())
+    }
+
+    fn find_prefix(&self, prefix: &ObjectIdPrefix) -> Result<Vec<ObjectId>> {
+        let prefix = prefix.as_str();
+        self.scan(&prefix[..2], &prefix[2..])
+    }
+
+    fn hashes(&self) -> Result<Vec<ObjectId>> {
+        let mut hashes = Vec::new();
+        for first in 0..=255u8 {
+            hashes.extend(self.scan(&format!("{first:02x}"), "")?);
+        }
+        Ok(hashes)
+    }
+}
@@ -0,0 +1,91 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use super::ObjectDatabase;
+use crate::{
+    error::{Error, Result},
+    objects::{
+        hash::HashAlgorithm,
+        id::{ObjectId, ObjectIdPrefix},
+        kind::ObjectKind,
+        object::Object,
+    },
+};
+
+/// Objects kept in memory (serialized, but not compressed), which are lost once
+/// the database is dropped. Only used by the tests.
+#[derive(Default)]
+pub(crate) struct MemoryDatabase {
+    objects: RefCell<BTreeMap<ObjectId, Vec<u8>>>,
+    algorithm: HashAlgorithm,
+}
+
+impl ObjectDatabase for MemoryDatabase {
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read_raw(&self, id: &ObjectId) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        match self.objects.borrow().get(id) {
+            Some(body) => {
+                let (kind, contents) =
+                    Object::parse_header(body).map_err(|err| Error::corrupt(id, err))?;
+                Ok(Some((kind, contents.to_vec())))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn write_raw(&self, kind: ObjectKind, body: &[u8]) -> Result<ObjectId> {
+        let id = Object::hash_raw(self.algorithm, kind, body);
+        self.objects
+            .borrow_mut()
+            .insert(id, Object::serialize_raw(kind, body));
+        Ok(id)
+    }
+
+    fn exists(&self, id: &ObjectId) -> Result<bool> {
+        Ok(self.objects.borrow().contains_key(id))
+    }
+
+    fn find_prefix(&self, prefix: &ObjectIdPrefix) -> Result<Vec<ObjectId>> {
+        Ok(self
+            .objects
+            .borrow()
+            .keys()
+            .filter(|id| prefix.matches(id))
+            .copied()
+            .collect())
+    }
+
+    fn hashes(&self) -> Result<Vec<ObjectId>> {
+        Ok(self.objects.borrow().keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{context::tests::TestContext, error::Error, objects::object::Object};
+
+    #[test]
+    fn read_write() {
+        let context = TestContext::in_memory();
+        let database = &context.context.database;
+        let hash = database
+            .write(&Object::new_blob(b"This is a test file.\n"))
+            .unwrap();
+        assert_eq!(hash, "6de7b8c69d65923eb48b10a560f3d72939df256a");
+        assert!(database.exists(&hash).unwrap());
+        assert_eq!(database.find_hash(&"6de7".parse().unwrap()).unwrap(), hash);
+        assert_eq!(database.hashes().unwrap(), vec![hash]);
+        assert_eq!(database.read(&hash).unwrap().size, 21);
+        let missing = "6de7b8c69d65923eb48b10a560f3d72939df256b".parse().unwrap();
+        assert!(matches!(database.read(&missing), Err(Error::NotFound(_))));
+
+        let other = database.write(&Object::new_blob(b"23464\n")).unwrap();
+        let Err(Error::Ambiguous { candidates, .. }) = database.find_hash(&"6de7".parse().unwrap())
+        else {
+            panic!("6de7 should be ambiguous");
+        };
+        assert_eq!(candidates, [hash, other]);
+    }
+}
@@ -0,0 +1,84 @@
+mod composite;
+mod loose;
+#[cfg(test)]
+mod memory;
+mod packed;
+
+use crate::error::{Error, Result};
+
+use super::{
+    hash::HashAlgorithm,
+    id::{ObjectId, ObjectIdPrefix, MIN_PREFIX_LEN},
+    kind::ObjectKind,
+    object::Object,
+};
+
+pub(crate) use composite::CompositeDatabase;
+pub(crate) use loose::LooseDatabase;
+#[cfg(test)]
+pub(crate) use memory::MemoryDatabase;
+pub(crate) use packed::PackedDatabase;
+
+/// A store of objects, addressed by their hashes.
+pub(crate) trait ObjectDatabase {
+    /// The hash function naming the objects.
+    fn hash_algorithm(&self) -> HashAlgorithm;
+
+    /// Returns the type and contents of the object, if it's present.
+    fn read_raw(&self, id: &ObjectId) -> Result<Option<(ObjectKind, Vec<u8>)>>;
+
+    /// Stores the object, and returns its name.
+    fn write(&self, object: &Object) -> Result<ObjectId> {
+        self.write_raw(object.kind(), &object.body())
+    }
+
+    /// Stores the object of this type and contents as is (without validating
+    /// them), and returns its name.
+    fn write_raw(&self, kind: ObjectKind, body: &[u8]) -> Result<ObjectId>;
+
+    fn exists(&self, id: &ObjectId) -> Result<bool>;
+
+    /// All the object names that start with `prefix`.
+    fn find_prefix(&self, prefix: &ObjectIdPrefix) -> Result<Vec<ObjectId>>;
+
+    /// All the object names in the database.
+    fn hashes(&self) -> Result<Vec<ObjectId>>;
+
+    fn read(&self, id: &ObjectId) -> Result<Object> {
+        match self.read_raw(id)? {
+            Some((kind, body)) => Object::from_raw(kind, &body, self.hash_algorithm())
+                .map_err(|err| Error::corrupt(id, err)),
+            None => Err(Error::NotFound(id.to_string())),
+        }
+    }
+
+    /// Finds the full object name for the prefix.
+    fn find_hash(&self, prefix: &ObjectIdPrefix) -> Result<ObjectId> {
+        if prefix.len() > self.hash_algorithm().hex_len() {
+            return Err(Error::NotFound(prefix.to_string()));
+        }
+        let mut candidates = self.find_prefix(prefix)?;
+        candidates.sort();
+        candidates.dedup();
+        match candidates.len() {
+            0 => Err(Error::NotFound(prefix.to_string())),
+            1 => Ok(candidates.remove(0)),
+            _ => Err(Error::Ambiguous {
+                prefix: prefix.clone(),
+                candidates,
+            }),
+        }
+    }
+
+    /// The shortest prefix (of at least `min_len` characters) that uniquely
+    /// identifies the object.
+    fn abbreviate(&self, id: &ObjectId, min_len: usize) -> Result<String> {
+        let hex_len = self.hash_algorithm().hex_len();
+        for len in min_len.clamp(MIN_PREFIX_LEN, hex_len)..hex_len {
+            if self.find_prefix(&id.prefix(len))?.len() <= 1 {
+                return Ok(format!("{id:.len$}"));
+            }
+        }
+        Ok(id.to_string())
+    }
+}
@@ -0,0 +1,80 @@
+use std::{cell::OnceCell, path::PathBuf};
+
+use anyhow::anyhow;
+
+use super::ObjectDatabase;
+use crate::{
+    error::{Error, Result},
+    objects::{
+        hash::HashAlgorithm,
+        id::{ObjectId, ObjectIdPrefix},
+        kind::ObjectKind,
+        pack::Pack,
+    },
+};
+
+/// Objects stored in the packfiles of the `pack/` directory.
+///
+/// The packs are loaded when an object is first looked up.
+pub(crate) struct PackedDatabase {
+    dir: PathBuf,
+    algorithm: HashAlgorithm,
+    packs: OnceCell<Vec<Pack>>,
+}
+
+impl PackedDatabase {
+    pub(crate) fn new(dir: PathBuf, algorithm: HashAlgorithm) -> Self {
+        Self {
+            dir,
+            algorithm,
+            packs: OnceCell::new(),
+        }
+    }
+
+    fn packs(&self) -> anyhow::Result<&[Pack]> {
+        if let Some(packs) = self.packs.get() {
+            return Ok(packs);
+        }
+        let packs = Pack::load_all(&self.dir, self.algorithm)?;
+        Ok(self.packs.get_or_init(|| packs))
+    }
+}
+
+impl ObjectDatabase for PackedDatabase {
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read_raw(&self, id: &ObjectId) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        for pack in self.packs()? {
+            if let Some(object) = pack.read(id).map_err(|err| Error::corrupt(id, err))? {
+                return Ok(Some(object));
+            }
+        }
+        Ok(None)
+    }
+
+    fn write_raw(&self, _kind: ObjectKind, _body: &[u8]) -> Result<ObjectId> {
+        Err(anyhow!("Objects can't be added to an existing pack").into())
+    }
+
+    fn exists(&self, id: &ObjectId) -> Result<bool> {
+        Ok(self.packs()?.iter().any(|pack| pack.contains(id)))
+    }
+
+    fn find_prefix(&self, prefix: &ObjectIdPrefix) -> Result<Vec<ObjectId>> {
+        Ok(self
+            .packs()?
+            .iter()
+            .flat_map(|pack| pack.find_prefix(prefix))
+            .collect())
+    }
+
+    fn hashes(&self) -> Result<Vec<ObjectId>> {
+        Ok(self
+            .packs()?
+            .iter()
+            .flat_map(|pack| pack.hashes())
+            .collect())
+    }
+}
@@ -1,38 +1,46 @@
 use std::{
     fs::{self, File},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use anyhow::{bail, Result};
 
-use super::object::Object;
+use super::{id::ObjectId, kind::ObjectKind, object::Object};
 use crate::{
-    context::Context,
-    objects::{blob::BlobContents, object::Contents},
+    error::{self, Error},
     utils,
 };
 
+/// A loose object, stored in its own file (`<objects-dir>/ab/cdef...`).
 pub(crate) struct ObjectFile<'a> {
-    context: &'a Context,
-    hash: &'a str,
+    objects_dir: &'a Path,
+    id: &'a ObjectId,
 }
 
 impl<'a> ObjectFile<'a> {
-    pub(crate) fn new(context: &'a Context, hash: &'a str) -> Self {
-        // TODO: better handling of hash. Currently it's assumed to be validated
-        // before this function is called.
-        Self { context, hash }
+    pub(crate) fn new(objects_dir: &'a Path, id: &'a ObjectId) -> Self {
+        Self { objects_dir, id }
     }
 
-    pub fn save(&self, object: &Object) -> Result<()> {
-        assert_eq!(self.hash, object.compute_hash());
+    fn dir(&self) -> PathBuf {
+        self.objects_dir.join(format!("{:.2}", self.id))
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir().join(&self.id.to_string()[2..])
+    }
+
+    pub(crate) fn exists(&self) -> bool {
+        self.path().is_file()
+    }
 
-        let dir_path = self.context.object_dir(self.hash);
-        let fp = self.context.object_path(self.hash);
+    pub fn save(&self, kind: ObjectKind, body: &[u8]) -> Result<()> {
+        let dir_path = self.dir();
+        let fp = self.path();
 
         if !dir_path.exists() {
-            fs::create_dir(dir_path)?;
+            fs::create_dir_all(&dir_path)?;
         } else if !dir_path.is_dir() {
             bail!("File already exists instead of directory: {:?}", dir_path);
         }
@@ -43,11 +51,7 @@ impl<'a> ObjectFile<'a> {
             fs::remove_file(&fp)?;
         }
 
-        let body = match &object.contents {
-            Contents::Blob(BlobContents(blob)) => blob,
-            _ => todo!(),
-        };
-        let body = utils::zlib_encode(body)?;
+        let body = utils::zlib_encode(&Object::serialize_raw(kind, body))?;
         {
             let mut f = File::create(&fp)?;
             f.write_all(&body)?;
@@ -60,9 +64,12 @@ impl<'a> ObjectFile<'a> {
         Ok(())
     }
 
-    pub(crate) fn parse(&self) -> Result<Object> {
-        let body = fs::read(self.context.object_path(self.hash))?;
-        let body = utils::zlib_decode(&body)?;
-        Object::parse(&body)
+    /// Returns the type and contents of the object, without parsing them.
+    pub(crate) fn read_raw(&self) -> error::Result<(ObjectKind, Vec<u8>)> {
+        let body = fs::read(self.path())?;
+        let corrupt = |err| Error::corrupt(self.id, err);
+        let body = utils::zlib_decode(&body).map_err(corrupt)?;
+        let (kind, contents) = Object::parse_header(&body).map_err(corrupt)?;
+        Ok((kind, contents.to_vec()))
     }
 }
@@ -1,39 +1,79 @@
-use anyhow::{anyhow, bail, Result};
-use std::fs;
-
-use crate::context::Context;
-
-/// Finds the full object hash for the hash prefix.
-pub fn find_hash(context: &Context, hash: &str) -> Result<String> {
-    if hash.len() < 4 || hash.len() > 40 {
-        bail!("Invalid hash length");
-    }
-
-    let dir = context.git_dir.join("objects").join(&hash[..2]);
-    if !dir.exists() || !dir.is_dir() {
-        bail!("No object found for hash: {hash}");
-    }
-    if dir.join(&hash[2..]).exists() {
-        return Ok(hash.to_owned());
-    }
-
-    let mut full_hash = None;
-    let prefix = &hash[2..];
-    let files = fs::read_dir(&dir).map_err(|_| anyhow!("Error reading {}", dir.display()))?;
-    for file in files.flatten() {
-        let path = file.path();
-        let file_name = path.file_name().map(|f| f.to_str());
-        if let Some(Some(file_name)) = file_name {
-            if !file_name.starts_with(prefix) {
-                continue;
-            }
-            if full_hash.is_some() {
-                bail!("Ambiguous hash: {hash}");
-            }
-            full_hash = Some(format!("{}{}", &hash[..2], file_name));
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::config::Config;
+
+/// The hash function naming the objects of a repository (its "object
+/// format"): SHA-1 by default, or SHA-256 with `extensions.objectFormat`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// The object format of the repository. `extensions.objectFormat` is only
+    /// honored in repositories of format version 1 (like git, which ignores it
+    /// in version 0).
+    pub(crate) fn from_repository(git_dir: &Path) -> Result<Self> {
+        let path = git_dir.join("config");
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let mut config = Config::default();
+        config.parse(&fs::read_to_string(path)?)?;
+        match config.get("core.repositoryFormatVersion") {
+            Some(version) if version.trim().parse::<u32>().is_ok_and(|v| v >= 1) => config
+                .get("extensions.objectFormat")
+                .map_or(Ok(Self::default()), str::parse),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Length of the (binary) hashes.
+    pub(crate) const fn hash_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// Length of the hashes in hexadecimal.
+    pub(crate) const fn hex_len(self) -> usize {
+        self.hash_len() * 2
+    }
+
+    pub(crate) fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+            HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashAlgorithm::Sha1 => write!(f, "sha1"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => bail!("unknown hash algorithm '{s}'"),
         }
     }
-    full_hash.ok_or(anyhow!("No object found for hash: {hash}"))
 }
 
 /// Return the digest value as a string of hexadecimal digits
@@ -51,3 +91,40 @@ pub(crate) fn hex_digest(bytes: &[u8]) -> String {
         .collect::<Vec<_>>()
         .join("")
 }
+
+/// Parses a string of hexadecimal digits into bytes.
+pub(crate) fn hex_to_bytes(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        bail!("Invalid hex string: {hex}");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or(anyhow!("Invalid hex string: {hex}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hex_digest, HashAlgorithm};
+
+    #[test]
+    fn algorithms() {
+        let sha1 = hex_digest(&HashAlgorithm::Sha1.digest(b"blob 0\0"));
+        assert_eq!(sha1, "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+        let sha256 = hex_digest(&HashAlgorithm::Sha256.digest(b"blob 0\0"));
+        assert_eq!(
+            sha256,
+            "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813"
+        );
+        assert_eq!(sha256.len(), HashAlgorithm::Sha256.hex_len());
+        assert_eq!(
+            "SHA256".parse::<HashAlgorithm>().unwrap(),
+            HashAlgorithm::Sha256
+        );
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
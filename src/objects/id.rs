@@ -0,0 +1,202 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use super::hash::{hex_digest, hex_to_bytes, HashAlgorithm};
+
+/// Length of the longest hashes (SHA-256).
+const MAX_LEN: usize = 32;
+
+/// Shortest prefix that can name an object (same as git).
+pub(crate) const MIN_PREFIX_LEN: usize = 4;
+
+/// The name of an object: the (binary) hash of its type and contents, shown
+/// in hexadecimal.
+///
+/// Both SHA-1 and SHA-256 names fit in it, so it's `Copy`. The precision of the
+/// format abbreviates it, e.g. `format!("{id:.7}")`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId {
+    bytes: [u8; MAX_LEN],
+    len: u8,
+}
+
+impl ObjectId {
+    /// The name made of the bytes of a hash (of any supported algorithm).
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if ![HashAlgorithm::Sha1, HashAlgorithm::Sha256]
+            .iter()
+            .any(|algorithm| algorithm.hash_len() == bytes.len())
+        {
+            bail!("invalid object name length: {}", bytes.len());
+        }
+        let mut id = Self {
+            bytes: [0; MAX_LEN],
+            len: bytes.len() as u8,
+        };
+        id.bytes[..bytes.len()].copy_from_slice(bytes);
+        Ok(id)
+    }
+
+    /// The hash of the data.
+    pub(crate) fn hash(algorithm: HashAlgorithm, data: &[u8]) -> Self {
+        Self::from_bytes(&algorithm.digest(data)).expect("The digest has a valid length")
+    }
+
+    /// The all-zeros name, which stands for a missing object (e.g. a ref that
+    /// doesn't exist yet).
+    pub(crate) fn null(algorithm: HashAlgorithm) -> Self {
+        Self {
+            bytes: [0; MAX_LEN],
+            len: algorithm.hash_len() as u8,
+        }
+    }
+
+    pub(crate) fn is_null(&self) -> bool {
+        self.as_bytes().iter().all(|b| *b == 0)
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    /// The first `len` hexadecimal digits, clamped between `MIN_PREFIX_LEN`
+    /// and the full name.
+    pub(crate) fn prefix(&self, len: usize) -> ObjectIdPrefix {
+        let mut hex = self.to_string();
+        hex.truncate(len.max(MIN_PREFIX_LEN));
+        ObjectIdPrefix { hex }
+    }
+}
+
+/// The null name of the default algorithm.
+impl Default for ObjectId {
+    fn default() -> Self {
+        Self::null(HashAlgorithm::default())
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&hex_digest(self.as_bytes()))
+    }
+}
+
+/// Compares with the name in hexadecimal, in tests.
+#[cfg(test)]
+impl PartialEq<&str> for ObjectId {
+    fn eq(&self, other: &&str) -> bool {
+        hex_digest(self.as_bytes()) == *other
+    }
+}
+
+impl fmt::Debug for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ObjectId({self})")
+    }
+}
+
+/// Parses the full name, in hexadecimal.
+impl FromStr for ObjectId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match hex_to_bytes(s) {
+            Ok(bytes) => Self::from_bytes(&bytes).map_err(|_| anyhow!("invalid object name '{s}'")),
+            Err(_) => bail!("invalid object name '{s}'"),
+        }
+    }
+}
+
+/// The (possibly abbreviated) name of an object: from `MIN_PREFIX_LEN`
+/// hexadecimal digits, up to a full name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectIdPrefix {
+    /// Lowercase hexadecimal digits (possibly an odd number of them).
+    hex: String,
+}
+
+impl ObjectIdPrefix {
+    pub(crate) fn as_str(&self) -> &str {
+        &self.hex
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.hex.len()
+    }
+
+    pub(crate) fn matches(&self, id: &ObjectId) -> bool {
+        id.to_string().starts_with(&self.hex)
+    }
+
+    /// The first byte of the names it matches.
+    pub(crate) fn first_byte(&self) -> u8 {
+        u8::from_str_radix(&self.hex[..2], 16).expect("The prefix is validated")
+    }
+}
+
+impl fmt::Display for ObjectIdPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&self.hex)
+    }
+}
+
+impl FromStr for ObjectIdPrefix {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() < MIN_PREFIX_LEN
+            || s.len() > HashAlgorithm::Sha256.hex_len()
+            || !s.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            bail!("invalid object name prefix '{s}'");
+        }
+        Ok(Self {
+            hex: s.to_ascii_lowercase(),
+        })
+    }
+}
+
+impl From<ObjectId> for ObjectIdPrefix {
+    fn from(id: ObjectId) -> Self {
+        Self {
+            hex: id.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ObjectId, ObjectIdPrefix};
+    use crate::objects::hash::HashAlgorithm;
+
+    const HASH: &str = "ce013625030ba8dba906f756967f9e9ca394464a";
+
+    #[test]
+    fn parse_and_format() {
+        let id: ObjectId = HASH.parse().unwrap();
+        assert_eq!(id.to_string(), HASH);
+        assert_eq!(id.as_bytes().len(), 20);
+        assert_eq!(format!("{id:.7}"), "ce01362");
+        assert_eq!(HASH.to_uppercase().parse::<ObjectId>().unwrap(), id);
+        assert!(HASH[1..].parse::<ObjectId>().is_err());
+        assert!(HASH.replace('c', "x").parse::<ObjectId>().is_err());
+
+        let sha256 = ObjectId::hash(HashAlgorithm::Sha256, b"blob 0\0");
+        assert_eq!(sha256.to_string().parse::<ObjectId>().unwrap(), sha256);
+        assert!(ObjectId::null(HashAlgorithm::Sha256).is_null());
+        assert!(!id.is_null());
+        assert!(ObjectId::null(HashAlgorithm::Sha1) < id);
+
+        let prefix: ObjectIdPrefix = "CE013".parse().unwrap();
+        assert!(prefix.matches(&id));
+        assert_eq!(prefix, id.prefix(5));
+        assert_eq!(prefix.first_byte(), 0xce);
+        assert_eq!(id.prefix(1).to_string(), "ce01");
+        assert_eq!(id.prefix(1).first_byte(), 0xce);
+        assert_eq!(id.prefix(100).to_string(), HASH);
+        assert!(!"ce01362a".parse::<ObjectIdPrefix>().unwrap().matches(&id));
+        assert!("ce0".parse::<ObjectIdPrefix>().is_err());
+        assert!("ce0g".parse::<ObjectIdPrefix>().is_err());
+    }
+}
@@ -6,11 +6,12 @@ use std::{
 
 use ObjectKind::*;
 
-#[derive(Debug)]
-pub(crate) enum ObjectKind {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ObjectKind {
     Blob,
     Tree,
     Commit,
+    Tag,
 }
 
 impl fmt::Display for ObjectKind {
@@ -19,6 +20,7 @@ impl fmt::Display for ObjectKind {
             Blob => write!(f, "blob"),
             Tree => write!(f, "tree"),
             Commit => write!(f, "commit"),
+            Tag => write!(f, "tag"),
         }
     }
 }
@@ -38,6 +40,7 @@ impl<'a> TryFrom<&'a [u8]> for ObjectKind {
             b"blob" => Ok(Blob),
             b"tree" => Ok(Tree),
             b"commit" => Ok(Commit),
+            b"tag" => Ok(Tag),
             _ => match str::from_utf8(kind) {
                 Ok(kind) => bail!("Invalid object type: {kind}"),
                 Err(_) => bail!("Invalid object type"),
@@ -1,10 +1,16 @@
-mod blob;
-mod commit;
+pub(crate) mod blob;
+pub(crate) mod commit;
+pub(crate) mod database;
 mod file;
 pub(crate) mod hash;
-mod kind;
+pub(crate) mod id;
+pub(crate) mod kind;
+pub(crate) mod mode;
 pub(crate) mod object;
-mod tree;
+mod pack;
+pub(crate) mod tag;
+pub(crate) mod tree;
 
+pub(crate) use database::ObjectDatabase;
 pub(crate) use file::ObjectFile;
-pub(crate) use hash::find_hash;
+pub(crate) use pack::{write_pack, PackObject, PackOptions};
@@ -0,0 +1,92 @@
+use anyhow::{anyhow, bail, Error, Result};
+use std::{fmt, str::FromStr};
+
+use super::kind::ObjectKind;
+use FileMode::*;
+
+/// The mode of an entry in a tree (or in the index).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    Regular,
+    Executable,
+    Symlink,
+    Tree,
+    /// A commit of a submodule.
+    Gitlink,
+}
+
+impl FileMode {
+    /// Validates the mode. The permissions of regular files are normalized
+    /// like git does (e.g. `100664`, found in old repositories, is a regular
+    /// file), but the other non-canonical modes are rejected.
+    pub(crate) fn from_bits(bits: u32) -> Result<Self> {
+        let permissions = bits & 0o7777;
+        Ok(match bits & !0o7777 {
+            0o100000 if permissions & 0o100 != 0 => Executable,
+            0o100000 => Regular,
+            0o120000 if permissions == 0 => Symlink,
+            0o040000 if permissions == 0 => Tree,
+            0o160000 if permissions == 0 => Gitlink,
+            _ => bail!("invalid mode {bits:o}"),
+        })
+    }
+
+    pub(crate) const fn bits(self) -> u32 {
+        match self {
+            Regular => 0o100644,
+            Executable => 0o100755,
+            Symlink => 0o120000,
+            Tree => 0o040000,
+            Gitlink => 0o160000,
+        }
+    }
+
+    /// The type of the object the entry refers to.
+    pub(crate) fn kind(self) -> ObjectKind {
+        match self {
+            Regular | Executable | Symlink => ObjectKind::Blob,
+            Tree => ObjectKind::Tree,
+            Gitlink => ObjectKind::Commit,
+        }
+    }
+}
+
+/// The mode as shown by `ls-tree` (e.g. `100644` or `040000`).
+impl fmt::Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:06o}", self.bits())
+    }
+}
+
+/// Parses the octal mode (e.g. `100644` or `40000`).
+impl FromStr for FileMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bits = u32::from_str_radix(s, 8)
+            .ok()
+            .filter(|_| !s.starts_with('+'))
+            .ok_or_else(|| anyhow!("invalid mode {s}"))?;
+        FileMode::from_bits(bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileMode;
+
+    #[test]
+    fn parse() {
+        let parse = |mode: &str| mode.parse::<FileMode>().ok();
+        assert_eq!(parse("100644"), Some(FileMode::Regular));
+        assert_eq!(parse("100664"), Some(FileMode::Regular));
+        assert_eq!(parse("100755"), Some(FileMode::Executable));
+        assert_eq!(parse("120000"), Some(FileMode::Symlink));
+        assert_eq!(parse("40000"), Some(FileMode::Tree));
+        assert_eq!(parse("160000"), Some(FileMode::Gitlink));
+        assert_eq!(parse("120644"), None);
+        assert_eq!(parse("140000"), None);
+        assert_eq!(parse("10064x"), None);
+        assert_eq!(FileMode::Tree.to_string(), "040000");
+    }
+}
@@ -1,31 +1,35 @@
 use anyhow::{anyhow, bail, Result};
-use sha1::{Digest, Sha1};
 use std::str;
 
 use crate::objects::commit::CommitContents;
 
-use super::{blob::BlobContents, kind::ObjectKind, tree::TreeContents};
+use super::{
+    blob::BlobContents, hash::HashAlgorithm, id::ObjectId, kind::ObjectKind, tag::TagContents,
+    tree::TreeContents,
+};
 
-pub(crate) struct Object {
-    pub(crate) size: usize,
-    pub(crate) contents: Contents,
+pub struct Object {
+    pub size: usize,
+    pub contents: Contents,
 }
 
 // TODO: The size diff is too large. Should we use Box?
 #[allow(clippy::large_enum_variant)]
-pub(crate) enum Contents {
+pub enum Contents {
     Blob(BlobContents),
     Tree(TreeContents),
     Commit(CommitContents),
+    Tag(TagContents),
 }
 
 impl Contents {
-    fn parse(kind: ObjectKind, body: &[u8]) -> Result<Self> {
+    fn parse(kind: ObjectKind, body: &[u8], algorithm: HashAlgorithm) -> Result<Self> {
         use Contents::*;
         Ok(match kind {
             ObjectKind::Blob => Blob(BlobContents::new(body)),
-            ObjectKind::Tree => Tree(TreeContents::parse(body)?),
+            ObjectKind::Tree => Tree(TreeContents::parse(body, algorithm)?),
             ObjectKind::Commit => Commit(CommitContents::parse(body)?),
+            ObjectKind::Tag => Tag(TagContents::parse(body)?),
         })
     }
 }
@@ -38,7 +42,30 @@ impl Object {
         }
     }
 
-    pub(crate) fn parse(body: &[u8]) -> Result<Self> {
+    pub(crate) fn new_tree(tree: TreeContents) -> Self {
+        Self {
+            size: tree.serialize().len(),
+            contents: Contents::Tree(tree),
+        }
+    }
+
+    pub(crate) fn new_commit(commit: CommitContents) -> Self {
+        Self {
+            size: commit.serialize().len(),
+            contents: Contents::Commit(commit),
+        }
+    }
+
+    pub(crate) fn new_tag(tag: TagContents) -> Self {
+        Self {
+            size: tag.serialize().len(),
+            contents: Contents::Tag(tag),
+        }
+    }
+
+    /// Splits the serialized object into its type and contents, after
+    /// validating the size in the header.
+    pub(crate) fn parse_header(body: &[u8]) -> Result<(ObjectKind, &[u8])> {
         let space = body
             .iter()
             .position(|c| *c == b' ')
@@ -58,28 +85,57 @@ impl Object {
         if body.len() - content_start != size {
             bail!("Corrupt hash (Invalid size: {})", size);
         }
+        Ok((kind, &body[content_start..]))
+    }
+
+    /// Creates the object from its type and contents (without the header).
+    /// The algorithm is the one of the hashes it refers to.
+    pub(crate) fn from_raw(
+        kind: ObjectKind,
+        body: &[u8],
+        algorithm: HashAlgorithm,
+    ) -> Result<Self> {
         Ok(Object {
-            size,
-            contents: Contents::parse(kind, &body[content_start..])?,
+            size: body.len(),
+            contents: Contents::parse(kind, body, algorithm)?,
         })
     }
 
-    pub(crate) fn kind(&self) -> ObjectKind {
+    pub fn kind(&self) -> ObjectKind {
         use Contents::*;
         match self.contents {
             Blob(_) => ObjectKind::Blob,
             Tree(_) => ObjectKind::Tree,
             Commit(_) => ObjectKind::Commit,
+            Tag(_) => ObjectKind::Tag,
+        }
+    }
+
+    /// The contents of the object, without the header.
+    pub(crate) fn body(&self) -> Vec<u8> {
+        match &self.contents {
+            Contents::Blob(BlobContents(blob)) => blob.clone(),
+            Contents::Tree(tree) => tree.serialize(),
+            Contents::Commit(commit) => commit.serialize(),
+            Contents::Tag(tag) => tag.serialize(),
         }
     }
 
-    pub(crate) fn compute_hash(&self) -> String {
-        let mut hasher = Sha1::new();
-        hasher.update(match &self.contents {
-            Contents::Blob(BlobContents(blob)) => blob,
-            _ => todo!(),
-        });
-        let hash = hasher.finalize().to_vec();
-        super::hash::hex_digest(&hash)
+    /// The object of this type and contents, as it's stored: `<type>
+    /// <size>\0<contents>`.
+    pub(crate) fn serialize_raw(kind: ObjectKind, body: &[u8]) -> Vec<u8> {
+        let mut object = format!("{kind} {}\0", body.len()).into_bytes();
+        object.extend(body);
+        object
+    }
+
+    pub(crate) fn compute_hash(&self, algorithm: HashAlgorithm) -> ObjectId {
+        Object::hash_raw(algorithm, self.kind(), &self.body())
+    }
+
+    /// The hash of the object of this type and contents. Unlike
+    /// `compute_hash`, the contents don't have to be valid.
+    pub(crate) fn hash_raw(algorithm: HashAlgorithm, kind: ObjectKind, body: &[u8]) -> ObjectId {
+        ObjectId::hash(algorithm, &Object::serialize_raw(kind, body))
     }
 }
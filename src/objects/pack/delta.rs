@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+
+/// Size of the blocks of the base that are indexed to find matches.
+const BLOCK_SIZE: usize = 16;
+
+/// Largest range that can be copied from the base in a single instruction.
+const MAX_COPY: usize = 0xff_ffff;
+
+/// Largest number of bytes that can be inserted in a single instruction.
+const MAX_INSERT: usize = 0x7f;
+
+/// Reconstructs an object from its `base` and a `delta` against it.
+///
+/// The delta starts with the (varint encoded) sizes of the base and the
+/// result, followed by a sequence of instructions:
+/// - `1xxxxxxx`: copy a range from the base. The low 4 bits flag which bytes of
+///   the offset follow, and the next 3 bits flag the bytes of the size.
+/// - `0xxxxxxx`: insert the next `xxxxxxx` bytes from the delta itself.
+pub(crate) fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut i = 0;
+    let base_size = read_size(delta, &mut i)?;
+    if base_size != base.len() {
+        bail!("Corrupt delta (base size mismatch)");
+    }
+    let size = read_size(delta, &mut i)?;
+
+    let mut result = Vec::with_capacity(size);
+    while i < delta.len() {
+        let op = delta[i];
+        i += 1;
+        if op & 0x80 != 0 {
+            let mut offset = 0;
+            for bit in 0..4 {
+                if op & (1 << bit) != 0 {
+                    offset |=
+                        (*delta.get(i).ok_or(anyhow!("Corrupt delta"))? as usize) << (8 * bit);
+                    i += 1;
+                }
+            }
+            let mut len = 0;
+            for bit in 0..3 {
+                if op & (0x10 << bit) != 0 {
+                    len |= (*delta.get(i).ok_or(anyhow!("Corrupt delta"))? as usize) << (8 * bit);
+                    i += 1;
+                }
+            }
+            if len == 0 {
+                len = 0x10000;
+            }
+            let chunk = base
+                .get(offset..offset + len)
+                .ok_or(anyhow!("Corrupt delta (copy out of bounds)"))?;
+            result.extend_from_slice(chunk);
+        } else if op != 0 {
+            let chunk = delta
+                .get(i..i + op as usize)
+                .ok_or(anyhow!("Corrupt delta (insert out of bounds)"))?;
+            result.extend_from_slice(chunk);
+            i += op as usize;
+        } else {
+            bail!("Corrupt delta (reserved instruction)");
+        }
+    }
+
+    if result.len() != size {
+        bail!("Corrupt delta (result size mismatch)");
+    }
+    Ok(result)
+}
+
+/// Creates a delta to reconstruct `target` from `base` (see [`apply_delta`]).
+///
+/// Every block of the base is indexed, and the target is scanned for these
+/// blocks. Matches are extended as far as possible and emitted as copy
+/// instructions, while the bytes in between are inserted literally.
+pub(crate) fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_size(&mut delta, base.len());
+    write_size(&mut delta, target.len());
+
+    let mut blocks = HashMap::new();
+    for (offset, block) in base.windows(BLOCK_SIZE).enumerate() {
+        blocks.entry(block).or_insert(offset);
+    }
+
+    let mut insert_start = 0;
+    let mut i = 0;
+    while i + BLOCK_SIZE <= target.len() {
+        let Some(&offset) = blocks.get(&target[i..i + BLOCK_SIZE]) else {
+            i += 1;
+            continue;
+        };
+        let len = base[offset..]
+            .iter()
+            .zip(&target[i..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        write_insert(&mut delta, &target[insert_start..i]);
+        write_copy(&mut delta, offset, len);
+        i += len;
+        insert_start = i;
+    }
+    write_insert(&mut delta, &target[insert_start..]);
+    delta
+}
+
+fn write_copy(delta: &mut Vec<u8>, mut offset: usize, mut len: usize) {
+    while len > 0 {
+        let chunk = len.min(MAX_COPY);
+        let mut op = 0x80;
+        let mut args = Vec::new();
+        for bit in 0..4 {
+            let byte = (offset >> (8 * bit)) as u8;
+            if byte != 0 {
+                op |= 1 << bit;
+                args.push(byte);
+            }
+        }
+        for bit in 0..3 {
+            let byte = (chunk >> (8 * bit)) as u8;
+            if byte != 0 {
+                op |= 0x10 << bit;
+                args.push(byte);
+            }
+        }
+        delta.push(op);
+        delta.extend(args);
+        offset += chunk;
+        len -= chunk;
+    }
+}
+
+fn write_insert(delta: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(MAX_INSERT) {
+        delta.push(chunk.len() as u8);
+        delta.extend(chunk);
+    }
+}
+
+fn write_size(delta: &mut Vec<u8>, mut size: usize) {
+    while size >= 0x80 {
+        delta.push((size & 0x7f) as u8 | 0x80);
+        size >>= 7;
+    }
+    delta.push(size as u8);
+}
+
+/// Reads a little-endian base-128 varint, used for the sizes in a delta.
+fn read_size(delta: &[u8], i: &mut usize) -> Result<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        if shift > 57 {
+            bail!("Corrupt delta (invalid size)");
+        }
+        let byte = *delta.get(*i).ok_or(anyhow!("Corrupt delta (truncated)"))?;
+        *i += 1;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_delta, create_delta};
+
+    #[test]
+    fn apply() {
+        let base = b"Hello, world!\n";
+        let delta = [
+            14, 12, // base and result sizes
+            0x90, 7, // copy 7 bytes from offset 0: "Hello, "
+            3, b'a', b'l', b'l', // insert "all"
+            0x91, 12, 2, // copy 2 bytes from offset 12: "!\n"
+        ];
+        assert_eq!(apply_delta(base, &delta).unwrap(), b"Hello, all!\n");
+
+        // The base size doesn't fit in 64 bits.
+        let mut delta = vec![0xff; 10];
+        delta.extend([0x01, 0]);
+        assert!(apply_delta(base, &delta).is_err());
+    }
+
+    #[test]
+    fn create() {
+        let base: Vec<u8> = (0..1000)
+            .flat_map(|i| format!("line {i}\n").into_bytes())
+            .collect();
+        let mut target = base[..3000].to_vec();
+        target.extend(b"an inserted line\n");
+        target.extend(&base[4000..]);
+
+        let delta = create_delta(&base, &target);
+        assert!(delta.len() < 100);
+        assert_eq!(apply_delta(&base, &delta).unwrap(), target);
+
+        let delta = create_delta(b"short", b"unrelated content");
+        assert_eq!(apply_delta(b"short", &delta).unwrap(), b"unrelated content");
+    }
+}
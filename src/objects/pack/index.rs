@@ -0,0 +1,188 @@
+use anyhow::{bail, Result};
+
+use crate::objects::{
+    hash::HashAlgorithm,
+    id::{ObjectId, ObjectIdPrefix},
+};
+
+const MAGIC: &[u8] = b"\xfftOc";
+
+/// Index (`.idx`, version 2) of a packfile, mapping object names to offsets
+/// in the pack.
+///
+/// Layout:
+/// ```txt
+/// <magic><version><fan-out: 256 * u32><names: N * hash><crc32: N * u32>
+/// <offsets: N * u32><large offsets: M * u64><pack checksum><index checksum>
+/// ```
+///
+/// The names and the checksums use the hash function of the repository.
+pub(crate) struct PackIndex {
+    pub(crate) algorithm: HashAlgorithm,
+    fanout: [u32; 256],
+    names: Vec<ObjectId>,
+    offsets: Vec<u64>,
+    pub(crate) pack_checksum: Vec<u8>,
+}
+
+impl PackIndex {
+    pub(crate) fn parse(body: &[u8], algorithm: HashAlgorithm) -> Result<Self> {
+        let hash_len = algorithm.hash_len();
+        if body.len() < 8 + 256 * 4 + 2 * hash_len || !body.starts_with(MAGIC) {
+            bail!("Unsupported pack index (only version 2 is supported)");
+        }
+        let version = read_u32(body, 4);
+        if version != 2 {
+            bail!("Unsupported pack index version: {version}");
+        }
+
+        // Cumulative counts of the names by first byte, so the last one is
+        // the total.
+        let mut fanout = [0; 256];
+        for (i, count) in fanout.iter_mut().enumerate() {
+            *count = read_u32(body, 8 + i * 4);
+        }
+        if fanout.windows(2).any(|pair| pair[0] > pair[1]) {
+            bail!("Corrupt pack index (invalid fan-out table)");
+        }
+        let count = fanout[255] as usize;
+
+        let names_start = 8 + 256 * 4;
+        let crcs_start = names_start + count * hash_len;
+        let offsets_start = crcs_start + count * 4;
+        let large_offsets_start = offsets_start + count * 4;
+        if body.len() < large_offsets_start + 2 * hash_len {
+            bail!("Corrupt pack index (truncated)");
+        }
+
+        let names = (0..count)
+            .map(|i| {
+                let start = names_start + i * hash_len;
+                ObjectId::from_bytes(&body[start..start + hash_len])
+            })
+            .collect::<Result<_>>()?;
+        let offsets = (0..count)
+            .map(|i| {
+                let offset = read_u32(body, offsets_start + i * 4);
+                if offset & 0x8000_0000 == 0 {
+                    return Ok(offset as u64);
+                }
+                // The MSB flags an index into the table of 64-bit offsets.
+                let start = large_offsets_start + (offset & 0x7fff_ffff) as usize * 8;
+                if body.len() < start + 8 + 2 * hash_len {
+                    bail!("Corrupt pack index (invalid large offset)");
+                }
+                Ok(u64::from_be_bytes(
+                    body[start..start + 8].try_into().unwrap(),
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        let trailer = body.len() - 2 * hash_len;
+        Ok(Self {
+            algorithm,
+            fanout,
+            names,
+            offsets,
+            pack_checksum: body[trailer..trailer + hash_len].to_vec(),
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Range of positions of the names starting with the `first` byte.
+    fn bucket(&self, first: u8) -> (usize, usize) {
+        let end = self.fanout[first as usize] as usize;
+        let start = match first {
+            0 => 0,
+            _ => self.fanout[first as usize - 1] as usize,
+        };
+        (start, end)
+    }
+
+    /// Position of the object in the index.
+    pub(crate) fn position(&self, id: &ObjectId) -> Option<usize> {
+        let (start, end) = self.bucket(id.as_bytes()[0]);
+        self.names[start..end]
+            .binary_search(id)
+            .ok()
+            .map(|i| start + i)
+    }
+
+    /// Offset of the object in the packfile.
+    pub(crate) fn offset(&self, id: &ObjectId) -> Option<u64> {
+        self.position(id).map(|i| self.offsets[i])
+    }
+
+    pub(crate) fn hashes(&self) -> Vec<ObjectId> {
+        self.names.clone()
+    }
+
+    /// All the object names in the index that start with the prefix.
+    pub(crate) fn find_prefix(&self, prefix: &ObjectIdPrefix) -> Vec<ObjectId> {
+        let (start, end) = self.bucket(prefix.first_byte());
+        self.names[start..end]
+            .iter()
+            .filter(|id| prefix.matches(id))
+            .copied()
+            .collect()
+    }
+}
+
+/// An object in a packfile being written, to be recorded in its index.
+pub(crate) struct IndexEntry {
+    pub(crate) name: ObjectId,
+    /// CRC32 of the (compressed) entry in the packfile.
+    pub(crate) crc32: u32,
+    pub(crate) offset: u64,
+}
+
+/// Serializes the index (version 2) for the packfile.
+pub(crate) fn write_index(
+    entries: &mut [IndexEntry],
+    pack_checksum: &[u8],
+    algorithm: HashAlgorithm,
+) -> Vec<u8> {
+    entries.sort_by_key(|entry| entry.name);
+
+    let mut body = MAGIC.to_vec();
+    body.extend(2u32.to_be_bytes());
+    let mut count = 0;
+    for first in 0..=255 {
+        count += entries[count..]
+            .iter()
+            .take_while(|entry| entry.name.as_bytes()[0] == first)
+            .count();
+        body.extend((count as u32).to_be_bytes());
+    }
+    for entry in entries.iter() {
+        body.extend(entry.name.as_bytes());
+    }
+    for entry in entries.iter() {
+        body.extend(entry.crc32.to_be_bytes());
+    }
+    let mut large_offsets = Vec::new();
+    for entry in entries.iter() {
+        let offset = match u32::try_from(entry.offset) {
+            Ok(offset) if offset & 0x8000_0000 == 0 => offset,
+            _ => {
+                large_offsets.push(entry.offset);
+                (large_offsets.len() - 1) as u32 | 0x8000_0000
+            }
+        };
+        body.extend(offset.to_be_bytes());
+    }
+    for offset in large_offsets {
+        body.extend(offset.to_be_bytes());
+    }
+    body.extend(pack_checksum);
+    let checksum = algorithm.digest(&body);
+    body.extend(checksum);
+    body
+}
+
+fn read_u32(body: &[u8], start: usize) -> u32 {
+    u32::from_be_bytes(body[start..start + 4].try_into().unwrap())
+}
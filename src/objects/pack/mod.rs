@@ -0,0 +1,416 @@
+mod delta;
+mod index;
+mod write;
+
+use std::{
+    cell::RefCell,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, Result};
+use flate2::read::ZlibDecoder;
+
+use super::{
+    hash::HashAlgorithm,
+    id::{ObjectId, ObjectIdPrefix},
+    kind::ObjectKind,
+};
+use delta::apply_delta;
+use index::PackIndex;
+pub(crate) use write::{write_pack, PackObject, PackOptions};
+
+const OBJ_COMMIT: u8 = 1;
+const OBJ_TREE: u8 = 2;
+const OBJ_BLOB: u8 = 3;
+const OBJ_TAG: u8 = 4;
+const OBJ_OFS_DELTA: u8 = 6;
+const OBJ_REF_DELTA: u8 = 7;
+
+/// Longest delta chain that will be followed before giving up.
+const MAX_DELTA_DEPTH: usize = 4096;
+
+/// Longest header of an entry (type, size and delta offset), excluding the
+/// name of the base object of `REF_DELTA` entries.
+const MAX_ENTRY_HEADER_LEN: usize = 32;
+
+/// A packfile (`.pack`) along with its index (`.idx`).
+///
+/// The packfile starts with a header (`PACK<version><object count>`), followed
+/// by the objects, and ends with the checksum of the preceding bytes. Each
+/// object starts with its type and (inflated) size, and then its zlib
+/// compressed data. Delta objects additionally store a reference to their base
+/// object, either as an offset in the same pack (`OFS_DELTA`) or as the object
+/// name (`REF_DELTA`).
+///
+/// Only the index is loaded: the entries of the packfile are read from the
+/// file when their objects are looked up.
+pub(crate) struct Pack {
+    index: PackIndex,
+    /// Shared by all the lookups, which seek to the entry they read.
+    file: RefCell<File>,
+    len: u64,
+}
+
+impl Pack {
+    /// Loads the pack index, and opens the packfile next to it.
+    pub(crate) fn open(idx_path: &Path, algorithm: HashAlgorithm) -> Result<Self> {
+        let index = PackIndex::parse(&fs::read(idx_path)?, algorithm)?;
+        let file = File::open(idx_path.with_extension("pack"))?;
+        Self::new(index, file)
+    }
+
+    /// Checks the header and the checksum of the packfile against the index.
+    fn new(index: PackIndex, mut file: File) -> Result<Self> {
+        let hash_len = index.algorithm.hash_len();
+        let len = file.metadata()?.len();
+        let mut header = [0; 12];
+        if len < (header.len() + hash_len) as u64 {
+            bail!("Corrupt packfile (invalid header)");
+        }
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+        if !header.starts_with(b"PACK") {
+            bail!("Corrupt packfile (invalid header)");
+        }
+        let version = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        if version != 2 && version != 3 {
+            bail!("Unsupported packfile version: {version}");
+        }
+        let count = u32::from_be_bytes(header[8..12].try_into().unwrap());
+        if count as usize != index.len() {
+            bail!("Corrupt packfile (object count doesn't match the index)");
+        }
+        let mut checksum = vec![0; hash_len];
+        file.seek(SeekFrom::Start(len - hash_len as u64))?;
+        file.read_exact(&mut checksum)?;
+        if checksum != index.pack_checksum {
+            bail!("Corrupt packfile (checksum doesn't match the index)");
+        }
+        Ok(Self {
+            index,
+            file: RefCell::new(file),
+            len,
+        })
+    }
+
+    /// Loads all the packs in the `pack/` directory.
+    pub(crate) fn load_all(dir: &Path, algorithm: HashAlgorithm) -> Result<Vec<Self>> {
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut packs = Vec::new();
+        for file in fs::read_dir(dir)?.flatten() {
+            let path = file.path();
+            if path.extension().is_some_and(|ext| ext == "idx") {
+                packs.push(Pack::open(&path, algorithm)?);
+            }
+        }
+        Ok(packs)
+    }
+
+    pub(crate) fn contains(&self, id: &ObjectId) -> bool {
+        self.index.position(id).is_some()
+    }
+
+    pub(crate) fn hashes(&self) -> Vec<ObjectId> {
+        self.index.hashes()
+    }
+
+    /// All the object names in the pack that start with the prefix.
+    pub(crate) fn find_prefix(&self, prefix: &ObjectIdPrefix) -> Vec<ObjectId> {
+        self.index.find_prefix(prefix)
+    }
+
+    /// Returns the type and the (undeltified) contents of the object, if it's
+    /// present in this pack.
+    pub(crate) fn read(&self, id: &ObjectId) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        match self.index.offset(id) {
+            Some(offset) => self.read_at(offset as usize).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read_at(&self, offset: usize) -> Result<(ObjectKind, Vec<u8>)> {
+        // Follow the chain of deltas until a base object is found, and then
+        // apply the deltas in the reverse order.
+        let mut deltas = Vec::new();
+        let mut offset = offset;
+        let (kind, mut body) = loop {
+            if deltas.len() > MAX_DELTA_DEPTH {
+                bail!("Corrupt packfile (delta chain too long)");
+            }
+            let entry = self.entry(offset)?;
+            let kind = match entry.kind {
+                OBJ_COMMIT => ObjectKind::Commit,
+                OBJ_TREE => ObjectKind::Tree,
+                OBJ_BLOB => ObjectKind::Blob,
+                OBJ_TAG => ObjectKind::Tag,
+                OBJ_OFS_DELTA | OBJ_REF_DELTA => {
+                    deltas.push(self.inflate(entry.data_offset, entry.size)?);
+                    offset = entry
+                        .base_offset
+                        .ok_or(anyhow!("Corrupt packfile (missing delta base)"))?;
+                    continue;
+                }
+                kind => bail!("Corrupt packfile (invalid object type {kind})"),
+            };
+            break (kind, self.inflate(entry.data_offset, entry.size)?);
+        };
+        for delta in deltas.iter().rev() {
+            body = apply_delta(&body, delta)?;
+        }
+        Ok((kind, body))
+    }
+
+    /// Parses the header of the object stored at `offset`.
+    fn entry(&self, offset: usize) -> Result<Entry> {
+        let corrupt = || anyhow!("Corrupt packfile (truncated object at {offset})");
+        let data = self.read_chunk(
+            offset,
+            MAX_ENTRY_HEADER_LEN + self.index.algorithm.hash_len(),
+        )?;
+        let mut i = 0;
+
+        // <1-bit continuation><3-bit type><4-bit size>, followed by the
+        // remaining bits of the size in (little-endian) groups of 7 bits.
+        let mut byte = *data.get(i).ok_or_else(corrupt)?;
+        i += 1;
+        let kind = (byte >> 4) & 0x7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            if shift > 57 {
+                bail!("Corrupt packfile (invalid size at {offset})");
+            }
+            byte = *data.get(i).ok_or_else(corrupt)?;
+            i += 1;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+
+        let base_offset = match kind {
+            OBJ_OFS_DELTA => {
+                // Big-endian groups of 7 bits, where each continuation also
+                // adds 1 (so that there is a single encoding for each offset).
+                let mut byte = *data.get(i).ok_or_else(corrupt)?;
+                i += 1;
+                let invalid = || anyhow!("Corrupt packfile (invalid delta base)");
+                let mut distance = (byte & 0x7f) as usize;
+                while byte & 0x80 != 0 {
+                    byte = *data.get(i).ok_or_else(corrupt)?;
+                    i += 1;
+                    distance = distance
+                        .checked_add(1)
+                        .and_then(|distance| distance.checked_mul(1 << 7))
+                        .ok_or_else(invalid)?
+                        | (byte & 0x7f) as usize;
+                }
+                Some(offset.checked_sub(distance).ok_or_else(invalid)?)
+            }
+            OBJ_REF_DELTA => {
+                let hash_len = self.index.algorithm.hash_len();
+                let name = data.get(i..i + hash_len).ok_or_else(corrupt)?;
+                i += hash_len;
+                let base_offset = self
+                    .index
+                    .offset(&ObjectId::from_bytes(name)?)
+                    .ok_or(anyhow!("Delta base not found in the packfile"))?;
+                Some(base_offset as usize)
+            }
+            _ => None,
+        };
+
+        Ok(Entry {
+            kind,
+            size,
+            base_offset,
+            data_offset: offset + i,
+        })
+    }
+
+    /// Reads up to `len` bytes at the offset (fewer at the end of the file).
+    fn read_chunk(&self, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let end = self.len.min((offset + len) as u64);
+        let mut chunk = vec![0; end.saturating_sub(offset as u64) as usize];
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.read_exact(&mut chunk)?;
+        Ok(chunk)
+    }
+
+    /// Inflates the compressed data at the offset, which must be `size`
+    /// bytes long. Only the compressed data is read from the file.
+    fn inflate(&self, offset: usize, size: usize) -> Result<Vec<u8>> {
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(offset as u64))?;
+        let mut body = Vec::new();
+        ZlibDecoder::new(&mut *file)
+            .take(size as u64 + 1)
+            .read_to_end(&mut body)?;
+        if body.len() != size {
+            bail!("Corrupt packfile (invalid size at {offset})");
+        }
+        Ok(body)
+    }
+}
+
+struct Entry {
+    kind: u8,
+    size: usize,
+    base_offset: Option<usize>,
+    data_offset: usize,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs, io::Write};
+
+    use sha1::{Digest, Sha1};
+
+    use super::{Pack, PackIndex, OBJ_BLOB, OBJ_OFS_DELTA, OBJ_REF_DELTA};
+    use crate::{
+        context::tests::TestContext,
+        objects::{
+            hash::HashAlgorithm, id::ObjectId, kind::ObjectKind, object::Object, ObjectFile,
+        },
+        utils,
+    };
+
+    /// Opens the pack, stored in a temporary file.
+    pub(crate) fn open_pack(pack: &[u8], idx: &[u8]) -> Pack {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(pack).unwrap();
+        Pack::new(PackIndex::parse(idx, HashAlgorithm::Sha1).unwrap(), file).unwrap()
+    }
+
+    fn blob_hash(contents: &[u8]) -> ObjectId {
+        Object::new_blob(contents).compute_hash(HashAlgorithm::Sha1)
+    }
+
+    /// Builds a packfile, and its index, with the (already encoded) entries.
+    fn build(entries: &[(ObjectId, Vec<u8>)]) -> (Vec<u8>, Vec<u8>) {
+        let mut pack = b"PACK".to_vec();
+        pack.extend(2u32.to_be_bytes());
+        pack.extend((entries.len() as u32).to_be_bytes());
+        let mut names = Vec::new();
+        for (hash, entry) in entries {
+            names.push((hash.as_bytes().to_vec(), pack.len() as u32));
+            pack.extend(entry);
+        }
+        let checksum = Sha1::digest(&pack).to_vec();
+        pack.extend(&checksum);
+
+        names.sort();
+        let mut idx = b"\xfftOc".to_vec();
+        idx.extend(2u32.to_be_bytes());
+        for byte in 0..=255u8 {
+            let count = names.iter().filter(|(name, _)| name[0] <= byte).count();
+            idx.extend((count as u32).to_be_bytes());
+        }
+        names.iter().for_each(|(name, _)| idx.extend(name));
+        names.iter().for_each(|_| idx.extend(0u32.to_be_bytes()));
+        names
+            .iter()
+            .for_each(|(_, offset)| idx.extend(offset.to_be_bytes()));
+        idx.extend(&checksum);
+        let idx_checksum = Sha1::digest(&idx).to_vec();
+        idx.extend(idx_checksum);
+        (pack, idx)
+    }
+
+    /// Encodes the type and size of an entry, followed by the `extra` header
+    /// bytes (delta base) and the compressed data.
+    fn entry(kind: u8, extra: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut size = data.len();
+        let mut header = vec![(kind << 4) | (size & 0x0f) as u8];
+        size >>= 4;
+        while size > 0 {
+            *header.last_mut().unwrap() |= 0x80;
+            header.push((size & 0x7f) as u8);
+            size >>= 7;
+        }
+        header.extend(extra);
+        header.extend(utils::zlib_encode(data).unwrap());
+        header
+    }
+
+    fn sample() -> (Vec<u8>, Vec<u8>) {
+        let base = entry(OBJ_BLOB, &[], b"Hello, world!\n");
+        // "Hello, " + "all" + "!\n"
+        let delta = [14, 12, 0x90, 7, 3, b'a', b'l', b'l', 0x91, 12, 2];
+        let ofs_delta = entry(OBJ_OFS_DELTA, &[base.len() as u8], &delta);
+        // "Hello, " + "Git" + "!\n", against the previous delta
+        let delta = [12, 12, 0x90, 7, 3, b'G', b'i', b't', 0x91, 10, 2];
+        let ref_delta = entry(
+            OBJ_REF_DELTA,
+            blob_hash(b"Hello, all!\n").as_bytes(),
+            &delta,
+        );
+        build(&[
+            (blob_hash(b"Hello, world!\n"), base),
+            (blob_hash(b"Hello, all!\n"), ofs_delta),
+            (blob_hash(b"Hello, Git!\n"), ref_delta),
+        ])
+    }
+
+    #[test]
+    fn read_deltas() {
+        let (data, idx) = sample();
+        let pack = open_pack(&data, &idx);
+        for contents in [&b"Hello, world!\n"[..], b"Hello, all!\n", b"Hello, Git!\n"] {
+            let (kind, body) = pack.read(&blob_hash(contents)).unwrap().unwrap();
+            assert_eq!(kind.to_string(), "blob");
+            assert_eq!(body, contents);
+        }
+        assert!(pack.read(&blob_hash(b"missing")).unwrap().is_none());
+    }
+
+    #[test]
+    fn corrupt() {
+        // The fan-out table must be non-decreasing.
+        let (_, mut idx) = sample();
+        idx[8..12].copy_from_slice(&5u32.to_be_bytes());
+        assert!(PackIndex::parse(&idx, HashAlgorithm::Sha1).is_err());
+
+        // The size of the entry doesn't fit in 64 bits.
+        let hash = blob_hash(b"x");
+        let mut header = vec![0x80 | (OBJ_BLOB << 4)];
+        header.extend([0xff; 10]);
+        header.push(0x01);
+        let (data, idx) = build(&[(hash, header)]);
+        assert!(open_pack(&data, &idx).read(&hash).is_err());
+
+        // The distance to the base of the delta overflows: it reaches
+        // `usize::MAX` before the last byte.
+        let mut distance = vec![0x80];
+        distance.extend([0xfe; 8]);
+        distance.extend([0xff, 0x00]);
+        let (data, idx) = build(&[(hash, entry(OBJ_OFS_DELTA, &distance, b""))]);
+        assert!(open_pack(&data, &idx).read(&hash).is_err());
+    }
+
+    #[test]
+    fn read_from_repository() {
+        let context = TestContext::init();
+        let context = &context.context;
+
+        let (data, idx) = sample();
+        fs::create_dir_all(context.pack_dir()).unwrap();
+        fs::write(context.pack_dir().join("pack-test.pack"), data).unwrap();
+        fs::write(context.pack_dir().join("pack-test.idx"), idx).unwrap();
+
+        // Also stored as a loose object.
+        let hash = blob_hash(b"Hello, Git!\n");
+        ObjectFile::new(&context.objects_dir(), &hash)
+            .save(ObjectKind::Blob, b"Hello, Git!\n")
+            .unwrap();
+        let database = &context.database;
+        assert_eq!(database.find_hash(&hash.prefix(6)).unwrap(), hash);
+        assert_eq!(database.find_prefix(&hash.prefix(6)).unwrap(), [hash]);
+        assert_eq!(database.abbreviate(&hash, 7).unwrap(), format!("{hash:.7}"));
+        let object = database.read(&hash).unwrap();
+        assert_eq!(object.size, 12);
+    }
+}
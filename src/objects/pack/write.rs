@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use flate2::Crc;
+
+use super::{
+    delta::create_delta,
+    index::{write_index, IndexEntry},
+    OBJ_BLOB, OBJ_COMMIT, OBJ_OFS_DELTA, OBJ_TAG, OBJ_TREE,
+};
+use crate::{
+    objects::{
+        hash::{hex_digest, HashAlgorithm},
+        id::ObjectId,
+        kind::ObjectKind,
+    },
+    utils,
+};
+
+/// An object to be written to a packfile.
+pub(crate) struct PackObject {
+    pub(crate) hash: ObjectId,
+    pub(crate) kind: ObjectKind,
+    pub(crate) body: Vec<u8>,
+}
+
+pub(crate) struct PackOptions {
+    /// Number of preceding objects that are tried as the delta base.
+    pub(crate) window: usize,
+    /// Maximum length of a delta chain.
+    pub(crate) depth: usize,
+    /// The hash function of the object names and the checksums.
+    pub(crate) algorithm: HashAlgorithm,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            window: 10,
+            depth: 50,
+            algorithm: HashAlgorithm::default(),
+        }
+    }
+}
+
+/// A packfile and its index.
+pub(crate) struct PackFiles {
+    pub(crate) pack: Vec<u8>,
+    pub(crate) index: Vec<u8>,
+    /// Checksum of the packfile, which is also used to name the files.
+    pub(crate) checksum: String,
+}
+
+/// Creates a packfile (version 2) with the objects.
+///
+/// The objects are sorted by type and size (largest first), and every object is
+/// compared against the preceding `window` objects of the same type. The base
+/// producing the smallest delta is used, if the delta is small enough to be
+/// worth it, and the object is stored as an `OFS_DELTA` entry.
+pub(crate) fn write_pack(objects: Vec<PackObject>, options: &PackOptions) -> Result<PackFiles> {
+    let mut seen = HashSet::new();
+    let mut objects: Vec<_> = objects
+        .into_iter()
+        .filter(|object| seen.insert(object.hash))
+        .collect();
+    objects.sort_by(|a, b| {
+        a.kind
+            .cmp(&b.kind)
+            .then_with(|| b.body.len().cmp(&a.body.len()))
+    });
+
+    let mut pack = b"PACK".to_vec();
+    pack.extend(2u32.to_be_bytes());
+    pack.extend((objects.len() as u32).to_be_bytes());
+
+    let mut depths = vec![0; objects.len()];
+    let mut offsets = vec![0; objects.len()];
+    let mut entries = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        let mut best: Option<(usize, Vec<u8>)> = None;
+        for base in i.saturating_sub(options.window)..i {
+            if objects[base].kind != object.kind || depths[base] >= options.depth {
+                continue;
+            }
+            let delta = create_delta(&objects[base].body, &object.body);
+            if delta.len() >= object.body.len() / 2 {
+                continue;
+            }
+            if best
+                .as_ref()
+                .is_none_or(|(_, best)| delta.len() < best.len())
+            {
+                best = Some((base, delta));
+            }
+        }
+
+        offsets[i] = pack.len();
+        let mut entry = match &best {
+            Some((base, delta)) => {
+                depths[i] = depths[*base] + 1;
+                let mut entry = entry_header(OBJ_OFS_DELTA, delta.len());
+                entry.extend(encode_offset(offsets[i] - offsets[*base]));
+                entry
+            }
+            None => entry_header(type_code(object.kind), object.body.len()),
+        };
+        let data = best.as_ref().map_or(&object.body, |(_, delta)| delta);
+        entry.extend(utils::zlib_encode(data)?);
+
+        let mut crc = Crc::new();
+        crc.update(&entry);
+        entries.push(IndexEntry {
+            name: object.hash,
+            crc32: crc.sum(),
+            offset: offsets[i] as u64,
+        });
+        pack.extend(entry);
+    }
+
+    let checksum = options.algorithm.digest(&pack);
+    pack.extend(&checksum);
+    let index = write_index(&mut entries, &checksum, options.algorithm);
+    Ok(PackFiles {
+        pack,
+        index,
+        checksum: hex_digest(&checksum),
+    })
+}
+
+fn type_code(kind: ObjectKind) -> u8 {
+    match kind {
+        ObjectKind::Commit => OBJ_COMMIT,
+        ObjectKind::Tree => OBJ_TREE,
+        ObjectKind::Blob => OBJ_BLOB,
+        ObjectKind::Tag => OBJ_TAG,
+    }
+}
+
+/// Encodes the type and size of the entry (see `Pack::entry`).
+fn entry_header(kind: u8, mut size: usize) -> Vec<u8> {
+    let mut header = vec![(kind << 4) | (size & 0x0f) as u8];
+    size >>= 4;
+    while size > 0 {
+        *header.last_mut().unwrap() |= 0x80;
+        header.push((size & 0x7f) as u8);
+        size >>= 7;
+    }
+    header
+}
+
+/// Encodes the distance to the base of an `OFS_DELTA` entry.
+fn encode_offset(mut distance: usize) -> Vec<u8> {
+    let mut bytes = vec![(distance & 0x7f) as u8];
+    distance >>= 7;
+    while distance > 0 {
+        distance -= 1;
+        bytes.push(0x80 | (distance & 0x7f) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_pack, PackObject, PackOptions};
+    use crate::objects::{
+        hash::HashAlgorithm, kind::ObjectKind, object::Object, pack::tests::open_pack,
+    };
+
+    fn blob(body: Vec<u8>) -> PackObject {
+        PackObject {
+            hash: Object::new_blob(&body).compute_hash(HashAlgorithm::Sha1),
+            kind: ObjectKind::Blob,
+            body,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let lines = |n: usize| -> Vec<u8> {
+            (0..n)
+                .flat_map(|i| format!("line {i}\n").into_bytes())
+                .collect()
+        };
+        let objects = || (1..=5).map(|i| blob(lines(i * 200))).collect::<Vec<_>>();
+        let hashes: Vec<_> = objects().into_iter().map(|o| o.hash).collect();
+
+        let files = write_pack(objects(), &PackOptions::default()).unwrap();
+        let pack = open_pack(&files.pack, &files.index);
+        for (i, hash) in hashes.iter().enumerate() {
+            let (kind, body) = pack.read(hash).unwrap().unwrap();
+            assert_eq!(kind, ObjectKind::Blob);
+            assert_eq!(body, lines((i + 1) * 200));
+        }
+
+        let no_deltas = PackOptions {
+            window: 0,
+            ..Default::default()
+        };
+        let undeltified = write_pack(objects(), &no_deltas).unwrap();
+        assert!(files.pack.len() < undeltified.pack.len() / 2);
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{anyhow, bail, Result};
+
+use super::{
+    commit::{parse_headers, Author, Header},
+    id::ObjectId,
+    kind::ObjectKind,
+};
+
+/// The start of the signature appended to the message of a signed tag.
+const SIGNATURE_STARTS: [&[u8]; 3] = [
+    b"-----BEGIN PGP SIGNATURE-----",
+    b"-----BEGIN PGP MESSAGE-----",
+    b"-----BEGIN SSH SIGNATURE-----",
+];
+
+/// An annotated tag. Like commits, the typed fields are parsed from the
+/// headers, and the tag is serialized from the headers, the message and the
+/// signature.
+pub struct TagContents {
+    /// The tagged object.
+    pub object: ObjectId,
+    /// The type of the tagged object.
+    pub kind: ObjectKind,
+    /// The name of the tag (e.g. `v1.0`).
+    pub tag: String,
+    /// Missing in some old tags.
+    pub tagger: Option<Author>,
+    /// All the headers (`object`, `type`, `tag`, `tagger` and any other one),
+    /// in order.
+    pub headers: Vec<Header>,
+    pub message: Vec<u8>,
+    /// The signature after the message, if the tag is signed.
+    pub signature: Option<Vec<u8>>,
+}
+
+impl TagContents {
+    pub fn parse(body: &[u8]) -> Result<Self> {
+        let (headers, message) = parse_headers(body)?;
+        let mut values = headers.iter();
+        let mut next = |name: &str| match values.next() {
+            Some(header) if header.name == name => {
+                String::from_utf8(header.value.clone()).map_err(|_| anyhow!("Invalid tag ({name})"))
+            }
+            _ => bail!("Invalid tag (expected {name})"),
+        };
+        let object = next("object")?
+            .parse()
+            .map_err(|_| anyhow!("Invalid tag (object)"))?;
+        let kind = next("type")?.parse()?;
+        let tag = next("tag")?;
+        let tagger = match headers.get(3) {
+            Some(header) if header.name == "tagger" => Some(Author::parse(&header.text())?),
+            _ => None,
+        };
+
+        let (message, signature) = match signature_start(message) {
+            Some(start) => (&message[..start], Some(message[start..].to_vec())),
+            None => (message, None),
+        };
+        Ok(Self {
+            object,
+            kind,
+            tag,
+            tagger,
+            headers,
+            message: message.to_vec(),
+            signature,
+        })
+    }
+
+    pub fn new(
+        object: ObjectId,
+        kind: ObjectKind,
+        tag: String,
+        tagger: Author,
+        message: String,
+    ) -> Self {
+        let headers = vec![
+            Header::new("object", object.to_string()),
+            Header::new("type", kind.to_string()),
+            Header::new("tag", tag.as_str()),
+            Header::new("tagger", tagger.to_string()),
+        ];
+        Self {
+            object,
+            kind,
+            tag,
+            tagger: Some(tagger),
+            headers,
+            message: message.into_bytes(),
+            signature: None,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for header in self.headers.iter() {
+            header.write(&mut body);
+        }
+        body.push(b'\n');
+        body.extend_from_slice(&self.message);
+        if let Some(signature) = &self.signature {
+            body.extend_from_slice(signature);
+        }
+        body
+    }
+}
+
+/// The offset of the signature in the message: a line starting one of the
+/// known signatures, up to the end.
+fn signature_start(message: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    while start < message.len() {
+        let line = &message[start..];
+        if SIGNATURE_STARTS.iter().any(|sig| line.starts_with(sig)) {
+            return Some(start);
+        }
+        start += line.iter().position(|&c| c == b'\n')? + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TagContents;
+    use crate::objects::{hash::HashAlgorithm, kind::ObjectKind, object::Object};
+
+    #[test]
+    fn parse_and_serialize() {
+        let body = "object 8eabb745da068292912426b350eb9e7b1f396c55\n\
+            type commit\n\
+            tag v1.0\n\
+            tagger A U Thor <author@example.com> 1700000000 +0530\n\
+            \n\
+            Release 1.0\n\
+            -----BEGIN PGP SIGNATURE-----\n\
+            \n\
+            iQEzBAABCAAdFiEE\n\
+            -----END PGP SIGNATURE-----\n";
+        let tag = TagContents::parse(body.as_bytes()).unwrap();
+        assert_eq!(tag.object, "8eabb745da068292912426b350eb9e7b1f396c55");
+        assert_eq!(tag.kind, ObjectKind::Commit);
+        assert_eq!(tag.tag, "v1.0");
+        assert_eq!(tag.tagger.as_ref().unwrap().timestamp, 1700000000);
+        assert_eq!(tag.message, b"Release 1.0\n");
+        assert!(tag
+            .signature
+            .as_ref()
+            .unwrap()
+            .ends_with(b"SIGNATURE-----\n"));
+        assert_eq!(tag.serialize(), body.as_bytes());
+
+        // Old tags may not have a tagger.
+        let body = "object 8eabb745da068292912426b350eb9e7b1f396c55\n\
+            type commit\n\
+            tag v0.1\n\
+            \n\
+            Old\n";
+        let object =
+            Object::from_raw(ObjectKind::Tag, body.as_bytes(), HashAlgorithm::Sha1).unwrap();
+        assert_eq!(object.body(), body.as_bytes());
+        assert!(TagContents::parse(b"type commit\nobject abc\ntag v1\n\n").is_err());
+    }
+}
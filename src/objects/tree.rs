@@ -4,14 +4,14 @@ use std::{
     str,
 };
 
-use super::{hash::hex_digest, kind::ObjectKind};
+use super::{hash::HashAlgorithm, id::ObjectId, kind::ObjectKind, mode::FileMode};
 
-pub(crate) struct TreeContents {
-    pub(crate) lines: Vec<TreeRowItem>,
+pub struct TreeContents {
+    pub lines: Vec<TreeRowItem>,
 }
 
 impl TreeContents {
-    pub(crate) fn parse(body: &[u8]) -> Result<Self> {
+    pub(crate) fn parse(body: &[u8], algorithm: HashAlgorithm) -> Result<Self> {
         let mut lines = Vec::new();
         let mut i = 0;
         while i < body.len() {
@@ -19,7 +19,11 @@ impl TreeContents {
                 .iter()
                 .position(|c| *c == b' ')
                 .ok_or(anyhow!("Corrupt file"))?;
-            let perms = str::from_utf8(&body[i..space])?;
+            let raw_mode = &body[i..space];
+            let mode = str::from_utf8(raw_mode)
+                .ok()
+                .and_then(|mode| mode.parse().ok())
+                .ok_or(anyhow!("Corrupt tree (invalid mode)"))?;
             i = space + 1;
 
             let null = i + body[i..]
@@ -29,13 +33,38 @@ impl TreeContents {
             let name = str::from_utf8(&body[i..null])?;
             i = null + 1;
 
-            let hash = hex_digest(&body[i..i + 20]);
-            i += 20;
+            let hash = body
+                .get(i..i + algorithm.hash_len())
+                .ok_or(anyhow!("Corrupt file"))
+                .and_then(ObjectId::from_bytes)?;
+            i += algorithm.hash_len();
 
-            lines.push(TreeRowItem::new(perms, hash, name));
+            lines.push(TreeRowItem {
+                mode,
+                raw_mode: raw_mode.to_vec(),
+                hash,
+                name: name.to_string(),
+            });
         }
         Ok(Self { lines })
     }
+
+    /// Serializes the tree as `<mode> <name>\0<binary hash>` for every entry,
+    /// in git's canonical order.
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by_cached_key(|line| line.sort_key());
+
+        let mut body = Vec::new();
+        for line in lines {
+            body.extend(&line.raw_mode);
+            body.push(b' ');
+            body.extend(line.name.as_bytes());
+            body.push(0);
+            body.extend(line.hash.as_bytes());
+        }
+        body
+    }
 }
 
 impl Display for TreeContents {
@@ -51,27 +80,45 @@ impl Display for TreeContents {
     }
 }
 
-pub(crate) struct TreeRowItem {
-    pub(crate) perms: String,
-    pub(crate) kind: ObjectKind,
-    pub(crate) hash: String,
-    pub(crate) name: String,
+pub struct TreeRowItem {
+    mode: FileMode,
+    /// The mode as written in the tree, which may not be canonical (e.g.
+    /// `100664` or `040000` in old repositories).
+    raw_mode: Vec<u8>,
+    pub hash: ObjectId,
+    pub name: String,
 }
 
 impl TreeRowItem {
-    fn new(perms: &str, hash: String, name: &str) -> Self {
-        let perms = format!("{:0>6}", perms);
+    pub(crate) fn new(mode: FileMode, hash: ObjectId, name: &str) -> Self {
         Self {
-            kind: if perms.starts_with('1') {
-                ObjectKind::Blob
-            } else {
-                ObjectKind::Tree
-            },
-            perms,
+            mode,
+            // Modes are stored without the leading zeros (e.g. "40000").
+            raw_mode: format!("{:o}", mode.bits()).into_bytes(),
             hash,
             name: name.to_string(),
         }
     }
+
+    /// The mode, normalized (e.g. `100664` is a regular file).
+    pub fn mode(&self) -> FileMode {
+        self.mode
+    }
+
+    /// The type of the object (a commit for the submodules).
+    pub fn kind(&self) -> ObjectKind {
+        self.mode.kind()
+    }
+
+    /// Entries are sorted by name, where the names of trees are compared as if
+    /// they end with a "/".
+    fn sort_key(&self) -> Vec<u8> {
+        let mut key = self.name.as_bytes().to_vec();
+        if let FileMode::Tree = self.mode {
+            key.push(b'/');
+        }
+        key
+    }
 }
 
 impl Display for TreeRowItem {
@@ -79,7 +126,64 @@ impl Display for TreeRowItem {
         write!(
             f,
             "{} {} {}\t{}",
-            self.perms, self.kind, self.hash, self.name
+            self.mode,
+            self.kind(),
+            self.hash,
+            self.name
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{TreeContents, TreeRowItem};
+    use crate::objects::{
+        hash::HashAlgorithm, id::ObjectId, kind::ObjectKind, mode::FileMode, object::Object,
+    };
+
+    const BLOB: &str = "ce013625030ba8dba906f756967f9e9ca394464a";
+    const TREE: &str = "e31a96220fbfbe7601ecc086a36b96dc27a8867e";
+
+    #[test]
+    fn serialize() {
+        let (blob, subtree) = (BLOB.parse().unwrap(), TREE.parse().unwrap());
+        let tree = TreeContents {
+            lines: vec![
+                TreeRowItem::new(FileMode::Tree, subtree, "src"),
+                TreeRowItem::new(FileMode::Regular, blob, "src.rs"),
+                TreeRowItem::new(FileMode::Executable, blob, "b"),
+                TreeRowItem::new(FileMode::Regular, blob, "a.txt"),
+            ],
+        };
+        let body = tree.serialize();
+
+        let parsed = TreeContents::parse(&body, HashAlgorithm::Sha1).unwrap();
+        let names: Vec<_> = parsed.lines.iter().map(|line| line.name.as_str()).collect();
+        assert_eq!(names, ["a.txt", "b", "src.rs", "src"]);
+        assert_eq!(parsed.serialize(), body);
+
+        let object = Object::from_raw(ObjectKind::Tree, &body, HashAlgorithm::Sha1).unwrap();
+        assert_eq!(
+            object.compute_hash(HashAlgorithm::Sha1),
+            "b67207be6104838c2e10caa80cfdb2c330fe3f45"
+        );
+    }
+
+    #[test]
+    fn non_canonical_modes() {
+        let (blob, subtree): (ObjectId, ObjectId) = (BLOB.parse().unwrap(), TREE.parse().unwrap());
+        let mut body = b"100664 a.txt\0".to_vec();
+        body.extend(blob.as_bytes());
+        body.extend(b"040000 src\0");
+        body.extend(subtree.as_bytes());
+
+        let tree = TreeContents::parse(&body, HashAlgorithm::Sha1).unwrap();
+        let modes: Vec<_> = tree.lines.iter().map(|line| line.mode()).collect();
+        assert_eq!(modes, [FileMode::Regular, FileMode::Tree]);
+        assert_eq!(
+            tree.lines[0].to_string(),
+            format!("100644 blob {BLOB}\ta.txt")
+        );
+        assert_eq!(tree.serialize(), body);
+    }
+}
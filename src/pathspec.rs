@@ -0,0 +1,141 @@
+use std::{
+    env,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{context::Context, wildmatch::wildmatch};
+
+/// Paths (or glob patterns) that limit a command to some of the files, e.g.
+/// `git add src/ '*.md'`. They are given relative to the current directory (or
+/// as absolute paths), and stored relative to the repository root.
+pub(crate) struct Pathspec {
+    patterns: Vec<String>,
+}
+
+impl Pathspec {
+    pub(crate) fn new(context: &Context, specs: &[String]) -> Result<Self> {
+        let prefix = current_prefix(context);
+        let patterns = specs
+            .iter()
+            .map(|spec| repo_path(context, &prefix, spec))
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// The patterns, relative to the repository root ("" for the root).
+    pub(crate) fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Whether the path is matched by any of the patterns (or there are no
+    /// patterns).
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        self.patterns.is_empty() || self.patterns.iter().any(|p| matches(p, path))
+    }
+}
+
+/// The current directory, relative to the repository root ("" for the root, or
+/// outside of the working tree).
+pub(crate) fn current_prefix(context: &Context) -> PathBuf {
+    match env::current_dir() {
+        Ok(cwd) => cwd
+            .strip_prefix(&context.repo_root)
+            .map(|prefix| prefix.to_path_buf())
+            .unwrap_or_default(),
+        Err(_) => Default::default(),
+    }
+}
+
+/// The path (relative to the prefix, or absolute) relative to the repository
+/// root.
+pub(crate) fn repo_path(context: &Context, prefix: &Path, path: &str) -> Result<String> {
+    match Path::new(path).strip_prefix(&context.repo_root) {
+        Ok(relative) => normalize(relative, ""),
+        Err(_) => normalize(prefix, path),
+    }
+}
+
+/// The path (relative to the repository root) relative to the prefix, e.g.
+/// `../README.md` for `README.md` in `src`.
+pub(crate) fn relative_to(prefix: &Path, path: &str) -> String {
+    let mut prefix = prefix.components().peekable();
+    let mut path = Path::new(path).components().peekable();
+    while prefix.peek().is_some() && prefix.peek() == path.peek() {
+        prefix.next();
+        path.next();
+    }
+    let mut relative: Vec<_> = prefix.map(|_| "..".into()).collect();
+    relative.extend(path.map(|c| c.as_os_str().to_string_lossy().into_owned()));
+    relative.join("/")
+}
+
+/// Whether the path matches the pattern: the path itself, a path in the
+/// directory, or a glob (where `*` also matches `/`).
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    if pattern.is_empty() || path == pattern {
+        return true;
+    }
+    if path
+        .strip_prefix(pattern)
+        .is_some_and(|rest| rest.starts_with('/'))
+    {
+        return true;
+    }
+    is_glob(pattern) && wildmatch(pattern, path, false)
+}
+
+pub(crate) fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '\\'])
+}
+
+/// Joins the spec to the prefix (the current directory), and resolves the `.`
+/// and `..` components.
+fn normalize(prefix: &Path, spec: &str) -> Result<String> {
+    let mut components: Vec<String> = Vec::new();
+    for component in prefix.join(spec).components() {
+        match component {
+            Component::Normal(name) => components.push(name.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                if components.pop().is_none() {
+                    bail!("{spec}: '{spec}' is outside repository");
+                }
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => {
+                bail!("{spec}: '{spec}' is outside repository")
+            }
+        }
+    }
+    Ok(components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{matches, normalize, relative_to};
+
+    #[test]
+    fn pathspecs() {
+        assert_eq!(normalize(Path::new(""), ".").unwrap(), "");
+        assert_eq!(normalize(Path::new("src"), "lib.rs").unwrap(), "src/lib.rs");
+        assert_eq!(normalize(Path::new("src/a"), "../b/").unwrap(), "src/b");
+        assert!(normalize(Path::new(""), "../x").is_err());
+
+        assert!(matches("", "src/lib.rs"));
+        assert!(matches("src", "src/lib.rs"));
+        assert!(matches("src/lib.rs", "src/lib.rs"));
+        assert!(!matches("src", "src.rs"));
+        assert!(matches("*.rs", "src/lib.rs"));
+        assert!(!matches("*.rs", "README.md"));
+
+        assert_eq!(relative_to(Path::new(""), "src/lib.rs"), "src/lib.rs");
+        assert_eq!(relative_to(Path::new("src"), "src/lib.rs"), "lib.rs");
+        assert_eq!(
+            relative_to(Path::new("src/a"), "README.md"),
+            "../../README.md"
+        );
+    }
+}
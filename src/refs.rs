@@ -0,0 +1,472 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    context::Context,
+    error::{Error, Result},
+    lockfile::LockFile,
+    objects::id::ObjectId,
+};
+
+/// Symbolic refs pointing to symbolic refs are followed up to this depth.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+/// The value of a ref: either an object name, or the name of another ref (e.g.
+/// `HEAD` is usually `ref: refs/heads/main`).
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Ref {
+    Direct(ObjectId),
+    Symbolic(String),
+}
+
+/// A ref stored in `packed-refs`:
+///
+/// ```txt
+/// # pack-refs with: peeled fully-peeled sorted
+/// <hash> refs/heads/main
+/// <hash> refs/tags/v1.0
+/// ^<hash of the object the tag points to>
+/// ```
+struct PackedRef {
+    name: String,
+    hash: ObjectId,
+    peeled: Option<ObjectId>,
+}
+
+/// Reads the ref (without following symbolic refs). Loose refs (a file under
+/// `.git`) take precedence over the packed ones.
+pub(crate) fn read_ref(context: &Context, name: &str) -> Result<Option<Ref>> {
+    let path = context.git_dir.join(name);
+    match fs::read_to_string(&path) {
+        Ok(contents) => return parse_ref(name, &contents).map(Some),
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        // A directory, e.g. `refs/heads` while looking up `refs/heads`.
+        Err(_) if path.is_dir() => {}
+        Err(err) => return Err(err.into()),
+    }
+    let packed = read_packed_refs(&context.git_dir)?;
+    Ok(packed
+        .into_iter()
+        .find(|packed| packed.name == name)
+        .map(|packed| Ref::Direct(packed.hash)))
+}
+
+/// Follows the symbolic refs, and returns the name of the last ref along with
+/// its object name (if the ref exists).
+pub(crate) fn resolve_ref(context: &Context, name: &str) -> Result<(String, Option<ObjectId>)> {
+    let mut name = name.to_string();
+    for _ in 0..=MAX_SYMREF_DEPTH {
+        match read_ref(context, &name)? {
+            Some(Ref::Symbolic(target)) => name = target,
+            Some(Ref::Direct(hash)) => return Ok((name, Some(hash))),
+            None => return Ok((name, None)),
+        }
+    }
+    Err(anyhow!("Too many levels of symbolic refs: {name}").into())
+}
+
+/// Points the ref to `new`, if its current value is `old`. A null `old` value
+/// means that the ref must not exist yet.
+///
+/// Symbolic refs are followed (and the ref they point to is updated) if
+/// `deref` is set, otherwise the ref itself is overwritten.
+pub(crate) fn update_ref(
+    context: &Context,
+    name: &str,
+    new: &ObjectId,
+    old: Option<&ObjectId>,
+    deref: bool,
+) -> Result<()> {
+    let name = target_name(context, name, deref)?;
+    let path = context.git_dir.join(&name);
+    create_parent_dirs(context, &name, &path)?;
+    let mut lock = LockFile::acquire(&path).map_err(|err| cannot_lock(&name, err))?;
+    verify_old_value(context, &name, old)?;
+    lock.write(format!("{new}\n").as_bytes())?;
+    lock.commit()
+}
+
+/// Deletes the ref (both the loose and the packed one), if its current value
+/// is `old`.
+pub(crate) fn delete_ref(
+    context: &Context,
+    name: &str,
+    old: Option<&ObjectId>,
+    deref: bool,
+) -> Result<()> {
+    let name = target_name(context, name, deref)?;
+    let path = context.git_dir.join(&name);
+    create_parent_dirs(context, &name, &path)?;
+    let lock = LockFile::acquire(&path).map_err(|err| cannot_lock(&name, err))?;
+    verify_old_value(context, &name, old)?;
+
+    let mut packed_lock = LockFile::acquire(&context.git_dir.join("packed-refs"))?;
+    let packed = read_packed_refs(&context.git_dir)?;
+    if packed.iter().any(|packed| packed.name == name) {
+        // The other lines (including the header and its traits) are kept as is.
+        let contents = fs::read_to_string(context.git_dir.join("packed-refs"))?;
+        let mut removed = false;
+        for line in contents.split_inclusive('\n') {
+            if line.starts_with('^') && removed {
+                continue;
+            }
+            removed = line
+                .trim_end()
+                .split_once(' ')
+                .is_some_and(|(_, packed)| !line.starts_with('#') && packed == name);
+            if !removed {
+                packed_lock.write(line.as_bytes())?;
+            }
+        }
+        packed_lock.commit()?;
+    }
+    match fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    drop(lock);
+    remove_empty_parent_dirs(context, &path);
+    Ok(())
+}
+
+/// The refs starting with the prefix (e.g. `refs/tags/`), loose and packed,
+/// sorted by name, with their object names (after following the symbolic
+/// refs).
+pub(crate) fn list_refs(context: &Context, prefix: &str) -> Result<Vec<(String, ObjectId)>> {
+    let mut names = Vec::new();
+    let dir = match prefix.rsplit_once('/') {
+        Some((dir, _)) => dir,
+        None => "",
+    };
+    let mut dirs = vec![context.git_dir.join(dir)];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let Ok(name) = path.strip_prefix(&context.git_dir) else {
+                continue;
+            };
+            let name = name.to_string_lossy().replace('\\', "/");
+            if name.starts_with(prefix) && is_valid_name(&name) {
+                names.push(name);
+            }
+        }
+    }
+    for packed in read_packed_refs(&context.git_dir)? {
+        if packed.name.starts_with(prefix) {
+            names.push(packed.name);
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    let mut refs = Vec::new();
+    for name in names {
+        if let (_, Some(hash)) = resolve_ref(context, &name)? {
+            refs.push((name, hash));
+        }
+    }
+    Ok(refs)
+}
+
+/// Whether the ref name is valid: `HEAD`-like names (e.g. `ORIG_HEAD`), or the
+/// names under `refs/` that follow the rules of `git check-ref-format`.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    if !name.contains('/') {
+        return name.chars().all(|c| c.is_ascii_uppercase() || c == '_') && name.ends_with("HEAD");
+    }
+    if !name.starts_with("refs/") || name.ends_with('/') || name.ends_with('.') {
+        return false;
+    }
+    if name.contains("..") || name.contains("@{") {
+        return false;
+    }
+    let invalid_char = |c: char| c.is_ascii_control() || " ~^:?*[\\".contains(c);
+    name.split('/').all(|component| {
+        !component.is_empty()
+            && !component.starts_with('.')
+            && !component.ends_with(".lock")
+            && !component.chars().any(invalid_char)
+    })
+}
+
+fn parse_ref(name: &str, contents: &str) -> Result<Ref> {
+    let contents = contents.trim_end();
+    if let Some(target) = contents.strip_prefix("ref:") {
+        return Ok(Ref::Symbolic(target.trim().to_string()));
+    }
+    // The object format isn't checked: any valid length is accepted.
+    match contents.parse() {
+        Ok(id) => Ok(Ref::Direct(id)),
+        Err(_) => Err(anyhow!("Invalid ref {name}: {contents}").into()),
+    }
+}
+
+fn read_packed_refs(git_dir: &Path) -> Result<Vec<PackedRef>> {
+    let contents = match fs::read_to_string(git_dir.join("packed-refs")) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let invalid = |line: &str| Error::from(anyhow!("Invalid line in packed-refs: {line}"));
+    let mut refs: Vec<PackedRef> = Vec::new();
+    for line in contents.lines() {
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        if let Some(peeled) = line.strip_prefix('^') {
+            let last = refs.last_mut().ok_or_else(|| invalid(line))?;
+            last.peeled = Some(peeled.parse().map_err(|_| invalid(line))?);
+            continue;
+        }
+        let (hash, name) = line.split_once(' ').ok_or_else(|| invalid(line))?;
+        refs.push(PackedRef {
+            name: name.to_string(),
+            hash: hash.parse().map_err(|_| invalid(line))?,
+            peeled: None,
+        });
+    }
+    Ok(refs)
+}
+
+/// The name of the ref that will actually be changed.
+fn target_name(context: &Context, name: &str, deref: bool) -> Result<String> {
+    if !is_valid_name(name) {
+        return Err(Error::InvalidRef(name.to_string()));
+    }
+    if deref {
+        Ok(resolve_ref(context, name)?.0)
+    } else {
+        Ok(name.to_string())
+    }
+}
+
+/// Verifies the current value of the ref (while it's locked).
+fn verify_old_value(context: &Context, name: &str, old: Option<&ObjectId>) -> Result<()> {
+    let Some(old) = old else {
+        return Ok(());
+    };
+    let current = match read_ref(context, name)? {
+        Some(Ref::Direct(hash)) => Some(hash),
+        Some(Ref::Symbolic(_)) => resolve_ref(context, name)?.1,
+        None => None,
+    };
+    let reason = match current {
+        Some(_) if old.is_null() => "reference already exists".to_string(),
+        Some(current) if current != *old => format!("is at {current} but expected {old}"),
+        None if !old.is_null() => format!("unable to resolve reference '{name}'"),
+        _ => return Ok(()),
+    };
+    Err(cannot_lock(name, reason))
+}
+
+fn cannot_lock(name: &str, reason: impl ToString) -> Error {
+    Error::RefLock {
+        name: name.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn create_parent_dirs(context: &Context, name: &str, path: &Path) -> Result<()> {
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
+    if fs::create_dir_all(parent).is_err() {
+        // A file where the directory should be, e.g. `refs/heads/main` while
+        // creating `refs/heads/main/topic`.
+        let mut dir = PathBuf::from(name);
+        while dir.pop() && !context.git_dir.join(&dir).is_file() {}
+        let reason = format!("'{}' exists; cannot create '{name}'", dir.display());
+        return Err(cannot_lock(name, reason));
+    }
+    Ok(())
+}
+
+/// Removes the directories left empty after deleting a ref, except for the
+/// top-level ones (e.g. `refs/heads`).
+fn remove_empty_parent_dirs(context: &Context, path: &Path) {
+    let top_level = [
+        context.git_dir.join("refs/heads"),
+        context.git_dir.join("refs/tags"),
+    ];
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if !current.starts_with(context.git_dir.join("refs"))
+            || current == context.git_dir.join("refs")
+            || top_level.iter().any(|top| top == current)
+            || fs::remove_dir(current).is_err()
+        {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{delete_ref, is_valid_name, list_refs, read_ref, resolve_ref, update_ref, Ref};
+    use crate::{
+        context::tests::TestContext,
+        error::Error,
+        objects::{hash::HashAlgorithm, id::ObjectId},
+    };
+
+    const A: &str = "8eabb745da068292912426b350eb9e7b1f396c55";
+    const B: &str = "b67207be6104838c2e10caa80cfdb2c330fe3f45";
+
+    fn id(hex: &str) -> ObjectId {
+        hex.parse().unwrap()
+    }
+
+    #[test]
+    fn read_symbolic_and_packed() {
+        let context = TestContext::init();
+        let context = &context.context;
+        fs::write(
+            context.git_dir.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted \n\
+                {A} refs/heads/main\n\
+                {B} refs/tags/v1.0\n\
+                ^{A}\n"
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_ref(context, "HEAD").unwrap(),
+            Some(Ref::Symbolic("refs/heads/main".to_string()))
+        );
+        assert_eq!(
+            resolve_ref(context, "HEAD").unwrap(),
+            ("refs/heads/main".to_string(), Some(id(A)))
+        );
+        assert_eq!(
+            read_ref(context, "refs/tags/v1.0").unwrap(),
+            Some(Ref::Direct(id(B)))
+        );
+
+        // Loose refs take precedence over the packed ones.
+        fs::write(context.git_dir.join("refs/heads/main"), format!("{B}\n")).unwrap();
+        assert_eq!(resolve_ref(context, "HEAD").unwrap().1, Some(id(B)));
+        assert_eq!(read_ref(context, "refs/heads/missing").unwrap(), None);
+
+        fs::create_dir_all(context.git_dir.join("refs/tags/nested")).unwrap();
+        fs::write(
+            context.git_dir.join("refs/tags/nested/v2"),
+            format!("{A}\n"),
+        )
+        .unwrap();
+        fs::write(context.git_dir.join("refs/tags/v1.0.lock"), "").unwrap();
+        let tags = |prefix| {
+            let refs = list_refs(context, prefix).unwrap();
+            refs.into_iter().map(|(name, _)| name).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            tags("refs/tags/"),
+            ["refs/tags/nested/v2", "refs/tags/v1.0"]
+        );
+        assert_eq!(tags("refs/tags/v"), ["refs/tags/v1.0"]);
+        assert_eq!(tags("refs/").len(), 3);
+    }
+
+    #[test]
+    fn update_and_delete() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let main = context.git_dir.join("refs/heads/main");
+
+        // HEAD is followed to the (unborn) branch.
+        let (a, b, null) = (id(A), id(B), ObjectId::null(HashAlgorithm::Sha1));
+        update_ref(context, "HEAD", &a, Some(&null), true).unwrap();
+        assert_eq!(fs::read_to_string(&main).unwrap(), format!("{A}\n"));
+        assert!(matches!(
+            update_ref(context, "refs/heads/main", &b, Some(&null), true),
+            Err(Error::RefLock { .. })
+        ));
+        assert!(update_ref(context, "refs/heads/main", &b, Some(&b), true).is_err());
+        update_ref(context, "refs/heads/main", &b, Some(&a), true).unwrap();
+        assert_eq!(fs::read_to_string(&main).unwrap(), format!("{B}\n"));
+        assert!(!context.git_dir.join("refs/heads/main.lock").exists());
+
+        // An existing lock prevents the update.
+        fs::write(context.git_dir.join("refs/heads/main.lock"), "").unwrap();
+        assert!(update_ref(context, "refs/heads/main", &a, None, true).is_err());
+        fs::remove_file(context.git_dir.join("refs/heads/main.lock")).unwrap();
+
+        update_ref(context, "refs/heads/topic/nested", &a, None, true).unwrap();
+        assert!(update_ref(context, "refs/heads/main/nested", &a, None, true).is_err());
+        assert!(matches!(
+            update_ref(context, "refs/heads/a..b", &a, None, true),
+            Err(Error::InvalidRef(_))
+        ));
+
+        // Neither sorted nor peeled, and without a header: the other entries
+        // are kept as they were.
+        fs::write(
+            context.git_dir.join("packed-refs"),
+            format!("{B} refs/tags/v1.0\n{A} refs/heads/topic/nested\n^{B}\n{A} refs/heads/a\n"),
+        )
+        .unwrap();
+        assert!(delete_ref(context, "refs/heads/topic/nested", Some(&b), true).is_err());
+        delete_ref(context, "refs/heads/topic/nested", Some(&a), true).unwrap();
+        assert_eq!(read_ref(context, "refs/heads/topic/nested").unwrap(), None);
+        assert!(!context.git_dir.join("refs/heads/topic").exists());
+        assert_eq!(
+            fs::read_to_string(context.git_dir.join("packed-refs")).unwrap(),
+            format!("{B} refs/tags/v1.0\n{A} refs/heads/a\n")
+        );
+        assert!(!context.git_dir.join("packed-refs.lock").exists());
+
+        let header = "# pack-refs with: peeled \n";
+        fs::write(
+            context.git_dir.join("packed-refs"),
+            format!("{header}{B} refs/tags/v1.0\n^{A}\n{A} refs/heads/a\n"),
+        )
+        .unwrap();
+        delete_ref(context, "refs/heads/a", None, true).unwrap();
+        assert_eq!(
+            fs::read_to_string(context.git_dir.join("packed-refs")).unwrap(),
+            format!("{header}{B} refs/tags/v1.0\n^{A}\n")
+        );
+
+        // Detach HEAD.
+        update_ref(context, "HEAD", &a, None, false).unwrap();
+        assert_eq!(read_ref(context, "HEAD").unwrap(), Some(Ref::Direct(a)));
+    }
+
+    #[test]
+    fn valid_names() {
+        for name in ["HEAD", "ORIG_HEAD", "refs/heads/main", "refs/tags/v1.0"] {
+            assert!(is_valid_name(name), "{name}");
+        }
+        for name in [
+            "main",
+            "refs/heads/",
+            "refs/heads/a..b",
+            "refs/heads/.hidden",
+            "refs/heads/main.lock",
+            "refs/heads/a b",
+            "refs/heads/a@{1}",
+            "refs//heads",
+        ] {
+            assert!(!is_valid_name(name), "{name}");
+        }
+    }
+}
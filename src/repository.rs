@@ -0,0 +1,249 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    context::Context,
+    error::{Error, Result},
+    index::Index,
+    objects::{hash::HashAlgorithm, id::ObjectId, object::Object},
+    revision,
+    revwalk::RevWalk,
+    utils::{find_repo_root, is_repo_root},
+};
+
+pub(crate) const DEFAULT_BRANCH: &str = "main";
+
+/// A repository, with its working tree and its `.git` directory.
+///
+/// ```no_run
+/// use git::{Contents, Repository};
+///
+/// let repository = Repository::discover(".")?;
+/// let head = repository.resolve("HEAD")?;
+/// let mut walk = repository.walk();
+/// walk.push(&head)?;
+/// for commit in walk {
+///     let (id, commit) = commit?;
+///     println!("{id:.7} {}", commit.message().lines().next().unwrap_or(""));
+/// }
+/// if let Contents::Tree(tree) = repository.read_object(&repository.resolve("HEAD^{tree}")?)?.contents {
+///     tree.lines.iter().for_each(|line| println!("{line}"));
+/// }
+/// # Ok::<(), git::Error>(())
+/// ```
+pub struct Repository {
+    context: Context,
+}
+
+impl Repository {
+    /// Opens the repository whose working tree is at the path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let root = absolute(path.as_ref())?;
+        if !is_repo_root(&root) {
+            return Err(Error::NotARepository);
+        }
+        Self::load(root)
+    }
+
+    /// Opens the repository containing the path: the first of the path and
+    /// its parents that is a working tree.
+    pub fn discover(path: impl AsRef<Path>) -> Result<Self> {
+        let root = find_repo_root(absolute(path.as_ref())?).ok_or(Error::NotARepository)?;
+        Self::load(root)
+    }
+
+    /// Creates an empty repository (SHA-1, with `main` as the initial branch)
+    /// in the directory, and opens it.
+    pub fn init(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init_with(path, DEFAULT_BRANCH, None)
+    }
+
+    /// Creates an empty repository in the directory, and opens it. The
+    /// directory is created if it's missing, and the object format defaults
+    /// to SHA-1.
+    ///
+    /// Like `git init`, an existing repository is only reinitialized: the
+    /// missing directories are created, but its `HEAD` and its config are
+    /// left alone (so `initial_branch` is ignored), and asking for another
+    /// object format is an error.
+    pub fn init_with(
+        path: impl AsRef<Path>,
+        initial_branch: &str,
+        object_format: Option<HashAlgorithm>,
+    ) -> Result<Self> {
+        let root = absolute(path.as_ref())?;
+        let git_dir = root.join(".git");
+        let head = git_dir.join("HEAD");
+        if head.exists() {
+            let existing = HashAlgorithm::from_repository(&git_dir)?;
+            if object_format.is_some_and(|format| format != existing) {
+                return Err(Error::Other(anyhow!(
+                    "attempt to reinitialize repository with different hash"
+                )));
+            }
+        }
+
+        for dir in ["objects/info", "objects/pack", "refs/heads", "refs/tags"] {
+            fs::create_dir_all(git_dir.join(dir))?;
+        }
+        if !head.exists() {
+            fs::write(head, format!("ref: refs/heads/{initial_branch}\n"))?;
+        }
+        // Repositories using extensions (the object format) are version 1.
+        let config_path = git_dir.join("config");
+        if !config_path.exists() {
+            let config = match object_format.unwrap_or_default() {
+                HashAlgorithm::Sha1 => "[core]\n\trepositoryformatversion = 0\n".to_string(),
+                format => format!(
+                    "[core]\n\trepositoryformatversion = 1\n[extensions]\n\tobjectformat = {format}\n"
+                ),
+            };
+            fs::write(config_path, config)?;
+        }
+        Self::load(root)
+    }
+
+    fn load(root: PathBuf) -> Result<Self> {
+        Ok(Self {
+            context: Context::new(root)?,
+        })
+    }
+
+    /// The root of the working tree.
+    pub fn path(&self) -> &Path {
+        &self.context.repo_root
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.context.git_dir
+    }
+
+    /// The hash function naming the objects.
+    pub fn object_format(&self) -> HashAlgorithm {
+        self.context.hash_algorithm()
+    }
+
+    pub fn read_object(&self, id: &ObjectId) -> Result<Object> {
+        self.context.database.read(id)
+    }
+
+    pub fn has_object(&self, id: &ObjectId) -> Result<bool> {
+        self.context.database.exists(id)
+    }
+
+    /// Resolves the revision (e.g. `main~2`, `v1.0^{tree}` or `HEAD:src`) to
+    /// the object it names. See `git help revisions`.
+    pub fn resolve(&self, revision: &str) -> Result<ObjectId> {
+        revision::resolve_revision(&self.context, revision)
+    }
+
+    /// A walk of the history, starting from the commits pushed to it.
+    pub fn walk(&self) -> RevWalk<'_> {
+        RevWalk::new(&self.context)
+    }
+
+    /// The staging area (empty if there's no index yet).
+    pub fn index(&self) -> Result<Index> {
+        Ok(Index::read(&self.context)?)
+    }
+
+    pub(crate) fn context(&self) -> &Context {
+        &self.context
+    }
+}
+
+/// The path relative to the current directory, if it isn't absolute.
+fn absolute(path: &Path) -> Result<PathBuf> {
+    Ok(env::current_dir()?.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::Repository;
+    use crate::{
+        error::Error,
+        objects::{
+            commit::{tests::author, CommitBuilder},
+            hash::HashAlgorithm,
+            object::{Contents, Object},
+            tree::TreeContents,
+        },
+        refs,
+    };
+
+    #[test]
+    fn open_and_read() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("repo");
+        assert!(matches!(
+            Repository::open(&root),
+            Err(Error::NotARepository)
+        ));
+
+        let repository =
+            Repository::init_with(&root, "trunk", Some(HashAlgorithm::Sha256)).unwrap();
+        assert_eq!(repository.object_format(), HashAlgorithm::Sha256);
+        assert!(repository.index().unwrap().entries.is_empty());
+        assert!(matches!(
+            repository.resolve("HEAD"),
+            Err(Error::UnknownRevision(_))
+        ));
+
+        let context = repository.context();
+        let tree = context
+            .database
+            .write(&Object::new_tree(TreeContents { lines: Vec::new() }))
+            .unwrap();
+        let commit = CommitBuilder::new(tree)
+            .author(author(1700000000))
+            .message("Initial commit\n".to_string())
+            .build()
+            .unwrap();
+        let commit = context.database.write(&Object::new_commit(commit)).unwrap();
+        refs::update_ref(context, "HEAD", &commit, None, true).unwrap();
+
+        fs::create_dir(root.join("src")).unwrap();
+        let repository = Repository::discover(root.join("src")).unwrap();
+        assert_eq!(repository.path(), root);
+        assert_eq!(repository.resolve("trunk").unwrap(), commit);
+        assert_eq!(repository.resolve("HEAD^{tree}").unwrap(), tree);
+        assert!(repository.has_object(&tree).unwrap());
+        let Contents::Commit(contents) = repository.read_object(&commit).unwrap().contents else {
+            panic!("not a commit");
+        };
+        assert_eq!(*contents.tree(), tree);
+
+        let mut walk = repository.walk();
+        walk.push(&commit).unwrap();
+        let commits: Vec<_> = walk.map(|commit| commit.unwrap().0).collect();
+        assert_eq!(commits, [commit]);
+    }
+
+    #[test]
+    fn reinit() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let repository = Repository::init_with(root, "trunk", None).unwrap();
+        assert!(repository.git_dir().join("objects/pack").is_dir());
+        assert!(repository.git_dir().join("objects/info").is_dir());
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/other\n").unwrap();
+
+        let repository = Repository::init(root).unwrap();
+        assert_eq!(repository.object_format(), HashAlgorithm::Sha1);
+        let head = fs::read_to_string(root.join(".git/HEAD")).unwrap();
+        assert_eq!(head, "ref: refs/heads/other\n");
+        assert!(Repository::init_with(root, "trunk", Some(HashAlgorithm::Sha1)).is_ok());
+        assert!(matches!(
+            Repository::init_with(root, "trunk", Some(HashAlgorithm::Sha256)),
+            Err(Error::Other(_))
+        ));
+    }
+}
@@ -0,0 +1,393 @@
+use std::fs;
+
+use anyhow::anyhow;
+
+use crate::{
+    context::Context,
+    error::{Error, Result},
+    index::Index,
+    objects::{
+        id::{ObjectId, ObjectIdPrefix},
+        kind::ObjectKind,
+        object::Contents,
+    },
+    refs,
+};
+
+/// Rules to expand a short name (e.g. `main`) to the full name of a ref, in
+/// the order of precedence.
+const REF_RULES: [&str; 6] = [
+    "{}",
+    "refs/{}",
+    "refs/tags/{}",
+    "refs/heads/{}",
+    "refs/remotes/{}",
+    "refs/remotes/{}/HEAD",
+];
+
+/// Resolves a revision to the object it names (see `git help revisions`):
+/// - `<hash>` (full or abbreviated), `HEAD`, `@`, `<branch>`, `<tag>`, `@{-<n>}`
+/// - `<rev>~<n>`: the n-th generation ancestor, following the first parents
+/// - `<rev>^<n>`: the n-th parent
+/// - `<rev>^{<type>}`, `<rev>^{}`: the object peeled to the type
+/// - `<rev>:<path>`: the object at the path in the tree of `<rev>`
+/// - `:<path>`, `:<n>:<path>`: the object at the path in the index (at stage n)
+pub(crate) fn resolve_revision(context: &Context, rev: &str) -> Result<ObjectId> {
+    if let Some(path) = rev.strip_prefix(':') {
+        return resolve_index_path(context, path);
+    }
+    let (base, path) = split_path(rev);
+    let (name, mut operators) = split_operators(base);
+    let unknown = || Error::UnknownRevision(rev.to_string());
+    let mut hash = resolve_name(context, name)?.ok_or_else(unknown)?;
+
+    while !operators.is_empty() {
+        let (operator, rest) = operators.split_at(1);
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        operators = &rest[digits..];
+        let n = match &rest[..digits] {
+            "" => 1,
+            n => n.parse().map_err(|_| unknown())?,
+        };
+        match operator {
+            "~" => {
+                for _ in 0..n {
+                    hash = parent(context, &hash, 1, rev)?;
+                }
+            }
+            "^" if digits == 0 && rest.starts_with('{') => {
+                let end = rest.find('}').ok_or_else(unknown)?;
+                operators = &rest[end + 1..];
+                hash = peel(context, &hash, &rest[1..end], rev)?;
+            }
+            "^" => hash = parent(context, &hash, n, rev)?,
+            _ => return Err(unknown()),
+        }
+    }
+
+    match path {
+        Some(path) => resolve_tree_path(context, &hash, path, base),
+        None => Ok(hash),
+    }
+}
+
+/// The full name of the ref that the revision refers to, after following the
+/// symbolic refs (e.g. `refs/heads/main` for `HEAD` or `main`). `HEAD` itself
+/// is returned if it's detached.
+pub(crate) fn resolve_ref_name(context: &Context, rev: &str) -> Result<Option<String>> {
+    let name = match rev {
+        "@" => "HEAD".to_string(),
+        rev if rev.starts_with("@{-") => match previous_branch(context, rev)? {
+            Some(branch) => branch,
+            None => return Ok(None),
+        },
+        rev => rev.to_string(),
+    };
+    for rule in REF_RULES {
+        let candidate = rule.replace("{}", &name);
+        if !refs::is_valid_name(&candidate) {
+            continue;
+        }
+        if let (full_name, Some(_)) = refs::resolve_ref(context, &candidate)? {
+            return Ok(Some(full_name));
+        }
+    }
+    Ok(None)
+}
+
+/// Resolves the name (before any operators) to an object name.
+fn resolve_name(context: &Context, name: &str) -> Result<Option<ObjectId>> {
+    if name.len() == context.hash_algorithm().hex_len() {
+        if let Ok(id) = name.parse() {
+            return Ok(Some(id));
+        }
+    }
+    let name = match name {
+        "@" => "HEAD",
+        name if name.starts_with("@{-") => match previous_branch(context, name)? {
+            Some(branch) => return resolve_name(context, &branch),
+            None => return Ok(None),
+        },
+        name => name,
+    };
+    for rule in REF_RULES {
+        let candidate = rule.replace("{}", name);
+        if !refs::is_valid_name(&candidate) {
+            continue;
+        }
+        if let (_, Some(hash)) = refs::resolve_ref(context, &candidate)? {
+            return Ok(Some(hash));
+        }
+    }
+    if let Ok(prefix) = name.parse::<ObjectIdPrefix>() {
+        return match context.database.find_hash(&prefix) {
+            Err(Error::NotFound(_)) => Ok(None),
+            result => result.map(Some),
+        };
+    }
+    Ok(None)
+}
+
+/// The branch (or commit, if HEAD was detached) checked out before the n-th
+/// last checkout, as recorded in the reflog of HEAD: `@{-<n>}`.
+fn previous_branch(context: &Context, name: &str) -> Result<Option<String>> {
+    let n: usize = name
+        .strip_prefix("@{-")
+        .and_then(|n| n.strip_suffix('}'))
+        .and_then(|n| n.parse().ok())
+        .filter(|n| *n > 0)
+        .ok_or_else(|| Error::UnknownRevision(name.to_string()))?;
+    let reflog = match fs::read_to_string(context.git_dir.join("logs").join("HEAD")) {
+        Ok(reflog) => reflog,
+        Err(_) => return Ok(None),
+    };
+    let previous = reflog
+        .lines()
+        .rev()
+        .filter_map(|line| {
+            line.split_once('\t')?
+                .1
+                .strip_prefix("checkout: moving from ")
+        })
+        .filter_map(|message| message.split_once(" to "))
+        .nth(n - 1)
+        .map(|(from, _)| from.to_string());
+    Ok(previous)
+}
+
+/// Splits `<rev>:<path>` at the first colon outside of braces (e.g.
+/// `HEAD^{tree}:src`).
+fn split_path(rev: &str) -> (&str, Option<&str>) {
+    let mut depth = 0;
+    for (i, c) in rev.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ':' if depth == 0 => return (&rev[..i], Some(&rev[i + 1..])),
+            _ => {}
+        }
+    }
+    (rev, None)
+}
+
+/// Splits the name from the operators (`~`, `^`) that follow it.
+fn split_operators(rev: &str) -> (&str, &str) {
+    match rev.find(['~', '^']) {
+        Some(i) => rev.split_at(i),
+        None => (rev, ""),
+    }
+}
+
+/// The n-th parent of the commit (`^0` is the commit itself).
+fn parent(context: &Context, hash: &ObjectId, n: usize, rev: &str) -> Result<ObjectId> {
+    let commit = peel(context, hash, "commit", rev)?;
+    if n == 0 {
+        return Ok(commit);
+    }
+    match context.database.read(&commit)?.contents {
+        Contents::Commit(commit) => commit
+            .parents()
+            .get(n - 1)
+            .copied()
+            .ok_or_else(|| Error::UnknownRevision(rev.to_string())),
+        _ => unreachable!("Peeled to a commit"),
+    }
+}
+
+/// Peels the object to the type (`^{<type>}`), following the tags (and the
+/// tree of a commit). An empty type (`^{}`) only peels tags, and `object` only
+/// verifies that the object exists.
+pub(crate) fn peel(context: &Context, hash: &ObjectId, kind: &str, rev: &str) -> Result<ObjectId> {
+    let target = match kind {
+        "object" => return context.database.read(hash).map(|_| *hash),
+        "" => None,
+        kind => Some(kind.parse::<ObjectKind>()?),
+    };
+    let mut hash = *hash;
+    loop {
+        let object = context.database.read(&hash)?;
+        if Some(object.kind()) == target {
+            return Ok(hash);
+        }
+        match object.contents {
+            Contents::Tag(tag) => {
+                let kind = context.database.read(&tag.object)?.kind();
+                if kind != tag.kind {
+                    let message = format!("object {} is a {kind}, not a {}", tag.object, tag.kind);
+                    return Err(anyhow!(message).into());
+                }
+                hash = tag.object;
+            }
+            _ if target.is_none() => return Ok(hash),
+            Contents::Commit(commit) if target == Some(ObjectKind::Tree) => {
+                return Ok(*commit.tree())
+            }
+            _ => {
+                return Err(anyhow!(
+                    "{rev}: expected {} type, but the object dereferences to {} type",
+                    kind,
+                    object.kind()
+                )
+                .into())
+            }
+        }
+    }
+}
+
+fn resolve_tree_path(
+    context: &Context,
+    hash: &ObjectId,
+    path: &str,
+    base: &str,
+) -> Result<ObjectId> {
+    let mut hash = peel(context, hash, "tree", base)?;
+    let not_found = || Error::from(anyhow!("path '{path}' does not exist in '{base}'"));
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let Contents::Tree(tree) = context.database.read(&hash)?.contents else {
+            return Err(not_found());
+        };
+        hash = tree
+            .lines
+            .into_iter()
+            .find(|line| line.name == name)
+            .ok_or_else(not_found)?
+            .hash;
+    }
+    Ok(hash)
+}
+
+/// Resolves `<path>` or `<stage>:<path>` in the index.
+fn resolve_index_path(context: &Context, path: &str) -> Result<ObjectId> {
+    let (stage, path) = match path.split_once(':') {
+        Some((stage @ ("0" | "1" | "2" | "3"), path)) => {
+            (stage.parse().expect("A stage digit"), path)
+        }
+        _ => (0, path),
+    };
+    let index = Index::read(context)?;
+    index
+        .entries
+        .into_iter()
+        .find(|entry| entry.path == path.as_bytes() && entry.stage() == stage)
+        .map(|entry| entry.hash)
+        .ok_or_else(|| anyhow!("path '{path}' is not in the index (at stage {stage})").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{resolve_ref_name, resolve_revision};
+    use crate::{
+        context::tests::TestContext,
+        error::Error,
+        objects::{
+            commit::tests::author,
+            id::ObjectId,
+            kind::ObjectKind,
+            mode::FileMode,
+            object::Object,
+            tag::TagContents,
+            tree::{TreeContents, TreeRowItem},
+        },
+        refs,
+        revwalk::tests::commit_tree,
+    };
+
+    #[test]
+    fn resolve() {
+        let context = TestContext::init();
+        let context = &context.context;
+        let blob = context.database.write(&Object::new_blob(b"fn main() {}\n"));
+        let blob = blob.unwrap();
+        let src = Object::new_tree(TreeContents {
+            lines: vec![TreeRowItem::new(FileMode::Regular, blob, "lib.rs")],
+        });
+        let src = context.database.write(&src).unwrap();
+        let root = Object::new_tree(TreeContents {
+            lines: vec![TreeRowItem::new(FileMode::Tree, src, "src")],
+        });
+        let root = context.database.write(&root).unwrap();
+
+        // c1 <- c2 <- c3 (merge of c2 and side) <- c4
+        let c1 = commit_tree(context, &root, &[], 100);
+        let c2 = commit_tree(context, &root, &[&c1], 200);
+        let side = commit_tree(context, &root, &[&c1], 300);
+        let c3 = commit_tree(context, &root, &[&c2, &side], 400);
+        let c4 = commit_tree(context, &root, &[&c3], 500);
+        refs::update_ref(context, "HEAD", &c4, None, true).unwrap();
+        refs::update_ref(context, "refs/tags/v1.0", &c2, None, true).unwrap();
+
+        let resolve = |rev: &str| resolve_revision(context, rev).unwrap();
+        assert_eq!(resolve("HEAD"), c4);
+        assert_eq!(resolve("@"), c4);
+        assert_eq!(resolve("main"), c4);
+        assert_eq!(resolve("refs/heads/main"), c4);
+        assert_eq!(resolve(&format!("{c4:.8}")), c4);
+        assert_eq!(resolve("main~3"), c1);
+        assert_eq!(resolve("HEAD~^2"), side);
+        assert_eq!(resolve("HEAD^^"), c2);
+        assert_eq!(resolve("HEAD^0"), c4);
+        assert_eq!(resolve("v1.0"), c2);
+        assert_eq!(resolve("v1.0^{tree}"), root);
+        assert_eq!(resolve("v1.0^{}"), c2);
+
+        // An annotated tag of an annotated tag.
+        let tagger = author(1700000000);
+        let tag = |object: &ObjectId, kind, name: &str| {
+            let tag = TagContents::new(
+                *object,
+                kind,
+                name.to_string(),
+                tagger.clone(),
+                "Tag\n".to_string(),
+            );
+            context.database.write(&Object::new_tag(tag)).unwrap()
+        };
+        let inner = tag(&c3, ObjectKind::Commit, "inner");
+        let outer = tag(&inner, ObjectKind::Tag, "v2.0");
+        refs::update_ref(context, "refs/tags/v2.0", &outer, None, true).unwrap();
+        assert_eq!(resolve("v2.0"), outer);
+        assert_eq!(resolve("v2.0^{}"), c3);
+        assert_eq!(resolve("v2.0^{tag}"), outer);
+        assert_eq!(resolve("v2.0^{commit}"), c3);
+        assert_eq!(resolve("v2.0^{tree}"), root);
+        assert_eq!(resolve("v2.0~1"), c2);
+        assert_eq!(resolve("v2.0:src"), src);
+
+        assert_eq!(resolve("HEAD:src"), src);
+        assert_eq!(resolve("HEAD:src/lib.rs"), blob);
+        assert_eq!(resolve("HEAD^{tree}:src/lib.rs"), blob);
+        assert!(resolve_revision(context, "HEAD~4").is_err());
+        assert!(resolve_revision(context, "HEAD:missing").is_err());
+        assert!(resolve_revision(context, "HEAD:src/lib.rs^{tree}").is_err());
+        assert!(resolve_revision(context, "v1.0^{blob}").is_err());
+        assert!(matches!(
+            resolve_revision(context, "missing"),
+            Err(Error::UnknownRevision(_))
+        ));
+
+        fs::create_dir_all(context.git_dir.join("logs")).unwrap();
+        fs::write(
+            context.git_dir.join("logs/HEAD"),
+            format!(
+                "{c1} {c2} A <a@b> 1700000000 +0000\tcheckout: moving from main to topic\n\
+                {c2} {c4} A <a@b> 1700000000 +0000\tcommit: fourth\n\
+                {c4} {c4} A <a@b> 1700000000 +0000\tcheckout: moving from topic to main\n"
+            ),
+        )
+        .unwrap();
+        refs::update_ref(context, "refs/heads/topic", &c2, None, true).unwrap();
+        assert_eq!(resolve("@{-1}"), c2);
+        assert_eq!(resolve("@{-2}~"), c3);
+        assert_eq!(
+            resolve_ref_name(context, "@{-1}").unwrap(),
+            Some("refs/heads/topic".to_string())
+        );
+        assert_eq!(
+            resolve_ref_name(context, "HEAD").unwrap(),
+            Some("refs/heads/main".to_string())
+        );
+        assert_eq!(resolve_ref_name(context, &c4.to_string()).unwrap(), None);
+    }
+}
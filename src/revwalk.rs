@@ -0,0 +1,408 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, VecDeque},
+    rc::Rc,
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    context::Context,
+    error,
+    objects::{commit::CommitContents, id::ObjectId, mode::FileMode, object::Contents},
+    pathspec,
+};
+
+/// The commit has been queued (or walked).
+const SEEN: u8 = 1;
+/// The commit is reachable from a hidden commit, and won't be shown.
+const UNINTERESTING: u8 = 1 << 1;
+/// The commit doesn't change the limiting paths (compared to a parent).
+const TREESAME: u8 = 1 << 2;
+
+/// When walking ranges, the walk only stops after this many uninteresting
+/// commits, in case the commit dates are skewed.
+const SLOP: usize = 5;
+
+/// Walks the history from the pushed commits, newest (by committer date)
+/// first, skipping the commits reachable from the hidden ones (e.g. `^v1.0` or
+/// `v1.0..main`).
+///
+/// ```ignore
+/// let mut walk = repository.walk();
+/// walk.push(&main)?;
+/// walk.hide(&tag)?;
+/// for commit in walk {
+///     let (hash, commit) = commit?;
+/// }
+/// ```
+pub struct RevWalk<'a> {
+    context: &'a Context,
+    commits: HashMap<ObjectId, Rc<CommitContents>>,
+    flags: HashMap<ObjectId, u8>,
+    queue: BinaryHeap<QueueEntry>,
+    /// Number of commits queued so far, to keep the insertion order of the
+    /// commits with the same date.
+    queued: usize,
+    first_parent: bool,
+    /// Only show the commits that change these paths (relative to the
+    /// repository root), simplifying the merges that don't.
+    paths: Vec<String>,
+    /// Files (and directories) matching `paths`, by tree.
+    path_states: HashMap<ObjectId, Rc<Vec<(String, ObjectId)>>>,
+    has_hidden: bool,
+    /// With hidden commits, the walk is done upfront, and the commits are
+    /// returned from here.
+    limited: Option<VecDeque<ObjectId>>,
+}
+
+struct QueueEntry {
+    timestamp: i64,
+    order: Reverse<usize>,
+    hash: ObjectId,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.timestamp, self.order).cmp(&(other.timestamp, other.order))
+    }
+}
+
+impl<'a> RevWalk<'a> {
+    pub(crate) fn new(context: &'a Context) -> Self {
+        Self {
+            context,
+            commits: HashMap::new(),
+            flags: HashMap::new(),
+            queue: BinaryHeap::new(),
+            queued: 0,
+            first_parent: false,
+            paths: Vec::new(),
+            path_states: HashMap::new(),
+            has_hidden: false,
+            limited: None,
+        }
+    }
+
+    /// Only follow the first parent of the merge commits.
+    pub fn first_parent(&mut self, first_parent: bool) {
+        self.first_parent = first_parent;
+    }
+
+    /// Only show the commits changing the paths (relative to the repository
+    /// root). The merges are simplified: if the paths are the same as in one
+    /// of the parents, only that parent is followed.
+    pub fn paths(&mut self, paths: Vec<String>) {
+        self.paths = paths;
+    }
+
+    /// Starts the walk from the commit.
+    pub fn push(&mut self, hash: &ObjectId) -> error::Result<()> {
+        Ok(self.enqueue(hash)?)
+    }
+
+    /// Hides the commit, and its ancestors.
+    pub fn hide(&mut self, hash: &ObjectId) -> error::Result<()> {
+        self.enqueue(hash)?;
+        self.has_hidden = true;
+        self.mark_uninteresting(hash);
+        Ok(())
+    }
+
+    fn enqueue(&mut self, hash: &ObjectId) -> Result<()> {
+        if self.flag(hash, SEEN) {
+            return Ok(());
+        }
+        *self.flags.entry(*hash).or_default() |= SEEN;
+        let commit = self.commit(hash)?;
+        let timestamp = commit.committer().timestamp;
+        self.queued += 1;
+        self.queue.push(QueueEntry {
+            timestamp,
+            order: Reverse(self.queued),
+            hash: *hash,
+        });
+        Ok(())
+    }
+
+    fn commit(&mut self, hash: &ObjectId) -> Result<Rc<CommitContents>> {
+        if let Some(commit) = self.commits.get(hash) {
+            return Ok(commit.clone());
+        }
+        let Contents::Commit(commit) = self.context.database.read(hash)?.contents else {
+            bail!("object {hash} is not a commit");
+        };
+        let commit = Rc::new(commit);
+        self.commits.insert(*hash, commit.clone());
+        Ok(commit)
+    }
+
+    fn flag(&self, hash: &ObjectId, flag: u8) -> bool {
+        self.flags.get(hash).is_some_and(|flags| flags & flag != 0)
+    }
+
+    /// Marks the commit, and the ancestors walked so far, as uninteresting.
+    fn mark_uninteresting(&mut self, hash: &ObjectId) {
+        let mut stack = vec![*hash];
+        while let Some(hash) = stack.pop() {
+            let flags = self.flags.entry(hash).or_default();
+            if *flags & UNINTERESTING != 0 {
+                continue;
+            }
+            *flags |= UNINTERESTING;
+            if let Some(commit) = self.commits.get(&hash) {
+                stack.extend(
+                    commit
+                        .parents()
+                        .iter()
+                        .filter(|p| self.flag(p, SEEN))
+                        .copied(),
+                );
+            }
+        }
+    }
+
+    /// Takes the newest commit from the queue, and queues its parents.
+    fn pop(&mut self) -> Result<Option<ObjectId>> {
+        let Some(QueueEntry { hash, .. }) = self.queue.pop() else {
+            return Ok(None);
+        };
+        let commit = self.commit(&hash)?;
+        let uninteresting = self.flag(&hash, UNINTERESTING);
+        let parents = match self.paths.is_empty() || uninteresting {
+            true => commit.parents().to_vec(),
+            false => self.simplify(&hash, &commit)?,
+        };
+        let parents = match self.first_parent {
+            true => parents.into_iter().take(1).collect(),
+            false => parents,
+        };
+        for parent in parents.iter() {
+            self.enqueue(parent)?;
+            if uninteresting {
+                self.mark_uninteresting(parent);
+            }
+        }
+        Ok(Some(hash))
+    }
+
+    /// The parents to follow when limiting the walk to paths. The commit is
+    /// marked as TREESAME if it doesn't change the paths.
+    fn simplify(&mut self, hash: &ObjectId, commit: &CommitContents) -> Result<Vec<ObjectId>> {
+        let state = self.path_state(commit.tree())?;
+        if commit.parents().is_empty() {
+            if state.is_empty() {
+                *self.flags.entry(*hash).or_default() |= TREESAME;
+            }
+            return Ok(Vec::new());
+        }
+        let parents = match self.first_parent {
+            true => &commit.parents()[..1],
+            false => commit.parents(),
+        };
+        for parent in parents.iter() {
+            let tree = *self.commit(parent)?.tree();
+            if self.path_state(&tree)? == state {
+                *self.flags.entry(*hash).or_default() |= TREESAME;
+                return Ok(vec![*parent]);
+            }
+        }
+        Ok(commit.parents().to_vec())
+    }
+
+    /// The files (and directories) in the tree matching the paths, with their
+    /// object names.
+    fn path_state(&mut self, tree: &ObjectId) -> Result<Rc<Vec<(String, ObjectId)>>> {
+        if let Some(state) = self.path_states.get(tree) {
+            return Ok(state.clone());
+        }
+        let mut state = Vec::new();
+        self.collect_paths(tree, "", &mut state)?;
+        let state = Rc::new(state);
+        self.path_states.insert(*tree, state.clone());
+        Ok(state)
+    }
+
+    fn collect_paths(
+        &self,
+        tree: &ObjectId,
+        prefix: &str,
+        state: &mut Vec<(String, ObjectId)>,
+    ) -> Result<()> {
+        let Contents::Tree(tree) = self.context.database.read(tree)?.contents else {
+            bail!("object {tree} is not a tree");
+        };
+        for line in tree.lines {
+            let path = format!("{prefix}{}", line.name);
+            if self.paths.iter().any(|p| pathspec::matches(p, &path)) {
+                state.push((path, line.hash));
+                continue;
+            }
+            let dir = format!("{path}/");
+            let is_tree = line.mode() == FileMode::Tree;
+            if is_tree
+                && self
+                    .paths
+                    .iter()
+                    .any(|p| p.starts_with(&dir) || pathspec::is_glob(p))
+            {
+                self.collect_paths(&line.hash, &dir, state)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Walks all the commits upfront, until only uninteresting commits are
+    /// left, so that the commits reachable from the hidden ones are never
+    /// returned.
+    fn limit(&mut self) -> Result<VecDeque<ObjectId>> {
+        let mut commits = Vec::new();
+        let mut slop = SLOP;
+        while let Some(hash) = self.pop()? {
+            commits.push(hash);
+            let everybody_uninteresting = self
+                .queue
+                .iter()
+                .all(|entry| self.flag(&entry.hash, UNINTERESTING));
+            if !everybody_uninteresting {
+                slop = SLOP;
+            } else if slop == 0 {
+                break;
+            } else {
+                slop -= 1;
+            }
+        }
+        Ok(commits
+            .into_iter()
+            .filter(|hash| !self.flag(hash, UNINTERESTING))
+            .collect())
+    }
+
+    fn next_commit(&mut self) -> Result<Option<(ObjectId, Rc<CommitContents>)>> {
+        loop {
+            let hash = match (&mut self.limited, self.has_hidden) {
+                (Some(limited), _) => limited.pop_front(),
+                (None, true) => {
+                    self.limited = Some(self.limit()?);
+                    continue;
+                }
+                (None, false) => self.pop()?,
+            };
+            let Some(hash) = hash else {
+                return Ok(None);
+            };
+            if self.flag(&hash, UNINTERESTING | TREESAME) {
+                continue;
+            }
+            let commit = self.commit(&hash)?;
+            return Ok(Some((hash, commit)));
+        }
+    }
+}
+
+impl Iterator for RevWalk<'_> {
+    type Item = error::Result<(ObjectId, Rc<CommitContents>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_commit().map_err(Into::into).transpose()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::RevWalk;
+    use crate::{
+        context::{tests::TestContext, Context},
+        objects::{
+            commit::{tests::author, CommitBuilder},
+            id::ObjectId,
+            mode::FileMode,
+            object::Object,
+            tree::{TreeContents, TreeRowItem},
+        },
+    };
+
+    /// Writes a commit with a single file, at the timestamp.
+    pub(crate) fn commit(
+        context: &Context,
+        file: (&str, &str),
+        parents: &[&ObjectId],
+        timestamp: i64,
+    ) -> ObjectId {
+        let blob = context
+            .database
+            .write(&Object::new_blob(file.1.as_bytes()))
+            .unwrap();
+        let tree = TreeContents {
+            lines: vec![TreeRowItem::new(FileMode::Regular, blob, file.0)],
+        };
+        let tree = context.database.write(&Object::new_tree(tree)).unwrap();
+        commit_tree(context, &tree, parents, timestamp)
+    }
+
+    /// Writes a commit of the tree, at the timestamp.
+    pub(crate) fn commit_tree(
+        context: &Context,
+        tree: &ObjectId,
+        parents: &[&ObjectId],
+        timestamp: i64,
+    ) -> ObjectId {
+        let mut builder = CommitBuilder::new(*tree).author(author(timestamp));
+        for parent in parents {
+            builder = builder.parent(**parent);
+        }
+        let commit = builder
+            .message(format!("Commit at {timestamp}\n"))
+            .build()
+            .unwrap();
+        context.database.write(&Object::new_commit(commit)).unwrap()
+    }
+
+    #[test]
+    fn walk() {
+        let context = TestContext::in_memory();
+        let context = &context.context;
+        // a - b - d - e
+        //   \ c  /
+        let a = commit(context, ("a", "1"), &[], 100);
+        let b = commit(context, ("a", "2"), &[&a], 200);
+        let c = commit(context, ("b", "1"), &[&a], 300);
+        let d = commit(context, ("a", "2"), &[&b, &c], 400);
+        let e = commit(context, ("a", "3"), &[&d], 500);
+
+        let hashes = |walk: RevWalk| -> Vec<ObjectId> { walk.map(|c| c.unwrap().0).collect() };
+        let mut walk = RevWalk::new(context);
+        walk.push(&e).unwrap();
+        assert_eq!(hashes(walk), [e, d, c, b, a]);
+
+        let mut walk = RevWalk::new(context);
+        walk.push(&e).unwrap();
+        walk.first_parent(true);
+        assert_eq!(hashes(walk), [e, d, b, a]);
+
+        let mut walk = RevWalk::new(context);
+        walk.push(&e).unwrap();
+        walk.hide(&b).unwrap();
+        assert_eq!(hashes(walk), [e, d, c]);
+
+        // The merge is the same as `b`, so `c` isn't followed.
+        let mut walk = RevWalk::new(context);
+        walk.push(&e).unwrap();
+        walk.paths(vec!["a".to_string()]);
+        assert_eq!(hashes(walk), [e, b, a]);
+    }
+}
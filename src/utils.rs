@@ -1,11 +1,14 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
 use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 
 /// Checks if the path is the path is a git repository root
-fn is_repo_root(path: &Path) -> bool {
+pub(crate) fn is_repo_root(path: &Path) -> bool {
     let git = path.join(".git");
 
     // TODO: verify the HEAD file contents (currently assumed to be valid)
@@ -43,18 +46,53 @@ pub fn zlib_encode(bytes: &[u8]) -> Result<Vec<u8>> {
     Ok(buffer)
 }
 
+/// The path of an index entry (which may not be valid UTF-8).
+pub(crate) fn path_from_bytes(path: &[u8]) -> &Path {
+    Path::new(OsStr::from_bytes(path))
+}
+
+/// Quotes the path like git does by default (`core.quotePath`): paths with
+/// control characters, double quotes, backslashes or non-ASCII bytes are
+/// enclosed in double quotes, with these bytes escaped.
+pub(crate) fn quote_path(path: &(impl AsRef<[u8]> + ?Sized)) -> Cow<'_, str> {
+    let path = path.as_ref();
+    let needs_quoting = |b: u8| !(0x20..0x7f).contains(&b) || b == b'"' || b == b'\\';
+    if !path.iter().copied().any(needs_quoting) {
+        return String::from_utf8_lossy(path);
+    }
+    let mut quoted = String::from("\"");
+    for &b in path {
+        match b {
+            b'\x07' => quoted.push_str("\\a"),
+            b'\x08' => quoted.push_str("\\b"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\x0b' => quoted.push_str("\\v"),
+            b'\x0c' => quoted.push_str("\\f"),
+            b'\r' => quoted.push_str("\\r"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b if needs_quoting(b) => quoted.push_str(&format!("\\{b:03o}")),
+            b => quoted.push(b as char),
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
 
     use super::find_repo_root;
     use super::is_repo_root;
+    use super::quote_path;
     use crate::commands::hash_object::hash_object;
     use crate::commands::init::init;
     use crate::commands::HashObjectOptions;
     use crate::commands::InitOptions;
     use crate::context::tests::TestContext;
-    use crate::objects::find_hash;
+    use crate::objects::id::ObjectIdPrefix;
 
     #[test]
     fn test_is_repo_root() {
@@ -107,23 +145,33 @@ mod tests {
         fs::write(&fp, "This is a test file.\n").unwrap();
 
         let options = HashObjectOptions {
-            path: fp.to_str().unwrap().to_string(),
+            paths: vec![fp.to_str().unwrap().to_string()],
             write: true,
+            ..Default::default()
         };
-        let hash = hash_object(&context, options).unwrap(); // 6de7b8c69d65923eb48b10a560f3d72939df256a
+        let hash = hash_object(context, options).unwrap().remove(0); // 6de7b8c69d65923eb48b10a560f3d72939df256a
 
-        let found = find_hash(&context, &hash);
+        let found = context.database.find_hash(&hash.into());
         assert!(found.is_ok());
         assert_eq!(found.unwrap(), hash);
 
-        let found = find_hash(&context, &hash[..4]);
+        let found = context.database.find_hash(&hash.prefix(4));
         assert!(found.is_ok());
         assert_eq!(found.unwrap(), hash);
 
-        let found = find_hash(context, "aaaaa");
+        let found = context.database.find_hash(&"aaaaa".parse().unwrap());
         assert!(found.is_err());
 
-        let found = find_hash(context, &hash[..3]);
-        assert!(found.is_err());
+        assert!(format!("{hash:.3}").parse::<ObjectIdPrefix>().is_err());
+    }
+
+    #[test]
+    fn test_quote_path() {
+        assert_eq!(quote_path("src/lib.rs"), "src/lib.rs");
+        assert_eq!(quote_path("a b"), "a b");
+        assert_eq!(quote_path("tab\there"), "\"tab\\there\"");
+        assert_eq!(quote_path("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_path("caf\u{e9}"), "\"caf\\303\\251\"");
+        assert_eq!(quote_path(b"caf\xe9"), "\"caf\\351\"");
     }
 }
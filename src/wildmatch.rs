@@ -0,0 +1,179 @@
+/// Matches the text against a glob pattern, like git's `wildmatch`:
+/// - `?` matches any character, and `*` any sequence of characters
+/// - `[abc]`, `[a-z]`, `[[:alpha:]]` match a character in the class, and
+///   `[!...]` (or `[^...]`) a character outside of it
+/// - `\` matches the next character literally
+///
+/// With `pathname`, the wildcards don't match a `/`, and `**` between slashes
+/// (or at the start/end of the pattern) matches any number of directories.
+pub(crate) fn wildmatch(pattern: &str, text: &str, pathname: bool) -> bool {
+    matches(pattern.as_bytes(), text.as_bytes(), pathname)
+}
+
+fn matches(pattern: &[u8], text: &[u8], pathname: bool) -> bool {
+    let (mut p, mut t) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            b'?' => {
+                if t >= text.len() || (pathname && text[t] == b'/') {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+            b'*' => {
+                let start = p;
+                while p < pattern.len() && pattern[p] == b'*' {
+                    p += 1;
+                }
+                let double = p - start >= 2;
+                let at_start = start == 0 || pattern[start - 1] == b'/';
+                let at_end = p == pattern.len() || pattern[p] == b'/';
+                if pathname && double && at_start && at_end {
+                    if p == pattern.len() {
+                        return true;
+                    }
+                    // `**/` matches zero or more leading directories.
+                    let rest = &pattern[p + 1..];
+                    return matches(rest, &text[t..], pathname)
+                        || (t..text.len())
+                            .any(|i| text[i] == b'/' && matches(rest, &text[i + 1..], pathname));
+                }
+                let rest = &pattern[p..];
+                for i in t..=text.len() {
+                    if matches(rest, &text[i..], pathname) {
+                        return true;
+                    }
+                    if pathname && i < text.len() && text[i] == b'/' {
+                        return false;
+                    }
+                }
+                return false;
+            }
+            b'[' => {
+                if t >= text.len() || (pathname && text[t] == b'/') {
+                    return false;
+                }
+                match match_class(&pattern[p + 1..], text[t]) {
+                    Some((true, len)) => {
+                        p += 1 + len;
+                        t += 1;
+                    }
+                    _ => return false,
+                }
+            }
+            c => {
+                let c = match c {
+                    b'\\' if p + 1 < pattern.len() => {
+                        p += 1;
+                        pattern[p]
+                    }
+                    c => c,
+                };
+                if t >= text.len() || text[t] != c {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+        }
+    }
+    t == text.len()
+}
+
+/// Matches the character against the class following a `[`, and returns
+/// whether it matched along with the length of the class (including the `]`).
+/// Returns `None` if the class isn't terminated.
+fn match_class(class: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 0;
+    let negated = matches!(class.first(), Some(b'!' | b'^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let mut start = *class.get(i)?;
+        if start == b']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if start == b'[' && class.get(i + 1) == Some(&b':') {
+            let end = class[i + 2..].windows(2).position(|w| w == b":]")?;
+            let name = &class[i + 2..i + 2 + end];
+            matched |= match name {
+                b"alnum" => c.is_ascii_alphanumeric(),
+                b"alpha" => c.is_ascii_alphabetic(),
+                b"blank" => c == b' ' || c == b'\t',
+                b"cntrl" => c.is_ascii_control(),
+                b"digit" => c.is_ascii_digit(),
+                b"graph" => c.is_ascii_graphic(),
+                b"lower" => c.is_ascii_lowercase(),
+                b"print" => c.is_ascii_graphic() || c == b' ',
+                b"punct" => c.is_ascii_punctuation(),
+                b"space" => c.is_ascii_whitespace(),
+                b"upper" => c.is_ascii_uppercase(),
+                b"xdigit" => c.is_ascii_hexdigit(),
+                _ => return None,
+            };
+            i += 2 + end + 2;
+            continue;
+        }
+        if start == b'\\' {
+            i += 1;
+            start = *class.get(i)?;
+        }
+        i += 1;
+        if class.get(i) == Some(&b'-') && class.get(i + 1).is_some_and(|end| *end != b']') {
+            let mut end = class[i + 1];
+            i += 2;
+            if end == b'\\' {
+                end = *class.get(i)?;
+                i += 1;
+            }
+            matched |= start <= c && c <= end;
+        } else {
+            matched |= start == c;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wildmatch;
+
+    #[test]
+    fn patterns() {
+        let cases = [
+            ("foo", "foo", true),
+            ("foo", "foobar", false),
+            ("*.rs", "lib.rs", true),
+            ("*.rs", "src/lib.rs", false),
+            ("f?o", "foo", true),
+            ("f?o", "f/o", false),
+            ("[a-c]at", "bat", true),
+            ("[!a-c]at", "bat", false),
+            ("[^a-c]at", "rat", true),
+            ("[]]", "]", true),
+            ("[[:digit:]]x", "7x", true),
+            ("[[:digit:]]x", "ax", false),
+            ("\\*", "*", true),
+            ("\\*", "a", false),
+            ("[abc", "a", false),
+            ("**/foo", "foo", true),
+            ("**/foo", "a/b/foo", true),
+            ("a/**/b", "a/b", true),
+            ("a/**/b", "a/x/y/b", true),
+            ("a/**", "a/x/y", true),
+            ("a/**", "a", false),
+            ("a**b", "a/b", false),
+            ("a**b", "axxb", true),
+            ("src/*", "src/a/b", false),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(wildmatch(pattern, text, true), expected, "{pattern} {text}");
+        }
+        assert!(wildmatch("*.rs", "src/lib.rs", false));
+        assert!(wildmatch("src/*", "src/a/b", false));
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    collections::HashSet,
+    fs::{self, Metadata},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
+
+use anyhow::Result;
+
+use crate::{context::Context, ignore::Ignore, index::Index, objects::mode::FileMode};
+
+/// A file (or symlink) in the working tree.
+pub(crate) struct WorktreeFile {
+    /// Path relative to the repository root.
+    pub(crate) path: String,
+    pub(crate) metadata: Metadata,
+}
+
+/// A file in the working tree that isn't in the index.
+pub(crate) struct UntrackedFile {
+    /// Path relative to the repository root.
+    pub(crate) path: String,
+    /// The outermost parent directory without any tracked files (if any).
+    pub(crate) untracked_dir: Option<String>,
+}
+
+/// Lists the files in the working tree, sorted by path. The ignored files (and
+/// directories) are skipped, unless `ignore` is `None`. Nested repositories
+/// aren't entered.
+pub(crate) fn walk(context: &Context, ignore: Option<&Ignore>) -> Result<Vec<WorktreeFile>> {
+    let mut files = Vec::new();
+    walk_dir(&context.repo_root, "", ignore, &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Lists the files in the working tree that aren't in the index (at any
+/// stage), sorted by path. The ignored files are skipped, unless `ignore` is
+/// `None`.
+pub(crate) fn untracked(
+    context: &Context,
+    index: &Index,
+    ignore: Option<&Ignore>,
+) -> Result<Vec<UntrackedFile>> {
+    let tracked: HashSet<&[u8]> = index.entries.iter().map(|e| &e.path[..]).collect();
+    let mut tracked_dirs = HashSet::new();
+    for entry in index.entries.iter() {
+        let mut path = &entry.path[..];
+        while let Some(slash) = path.iter().rposition(|c| *c == b'/') {
+            path = &path[..slash];
+            if !tracked_dirs.insert(path) {
+                break;
+            }
+        }
+    }
+
+    let mut untracked = Vec::new();
+    for file in walk(context, ignore)? {
+        if tracked.contains(file.path.as_bytes()) {
+            continue;
+        }
+        let untracked_dir = file
+            .path
+            .match_indices('/')
+            .map(|(i, _)| &file.path[..i])
+            .find(|dir| !tracked_dirs.contains(dir.as_bytes()))
+            .map(str::to_string);
+        untracked.push(UntrackedFile {
+            path: file.path,
+            untracked_dir,
+        });
+    }
+    Ok(untracked)
+}
+
+fn walk_dir(
+    root: &Path,
+    dir: &str,
+    ignore: Option<&Ignore>,
+    files: &mut Vec<WorktreeFile>,
+) -> Result<()> {
+    for entry in fs::read_dir(root.join(dir))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == ".git" {
+            continue;
+        }
+        let path = match dir {
+            "" => name,
+            dir => format!("{dir}/{name}"),
+        };
+        let metadata = fs::symlink_metadata(entry.path())?;
+        let is_dir = metadata.is_dir();
+        if let Some(ignore) = ignore {
+            if ignore.is_ignored(&path, is_dir)? {
+                continue;
+            }
+        }
+        if is_dir {
+            if !entry.path().join(".git").exists() {
+                walk_dir(root, &path, ignore, files)?;
+            }
+        } else {
+            files.push(WorktreeFile { path, metadata });
+        }
+    }
+    Ok(())
+}
+
+/// The contents of the file, or the target of the symlink.
+pub(crate) fn read_file(path: &Path, metadata: &Metadata) -> Result<Vec<u8>> {
+    if metadata.is_symlink() {
+        Ok(fs::read_link(path)?.into_os_string().into_encoded_bytes())
+    } else {
+        Ok(fs::read(path)?)
+    }
+}
+
+/// The mode of the file, as recorded in the index and trees.
+pub(crate) fn file_mode(metadata: &Metadata) -> u32 {
+    if metadata.is_symlink() {
+        FileMode::Symlink.bits()
+    } else if metadata.permissions().mode() & 0o111 != 0 {
+        FileMode::Executable.bits()
+    } else {
+        FileMode::Regular.bits()
+    }
+}